@@ -3,7 +3,13 @@ use std::env;
 fn main() {
     let target = env::var("TARGET").unwrap();
 
-    if target.starts_with("thumbv7m") | target.starts_with("thumbv7em") {
+    if target.starts_with("thumbv6m") {
+        println!("cargo:rustc-cfg=armv6m")
+    } else if target.starts_with("thumbv7m") | target.starts_with("thumbv7em") {
         println!("cargo:rustc-cfg=armv7m")
+    } else if target.starts_with("thumbv8m.base") {
+        println!("cargo:rustc-cfg=armv8m_base")
+    } else if target.starts_with("thumbv8m.main") {
+        println!("cargo:rustc-cfg=armv8m_main")
     }
 }
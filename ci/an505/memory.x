@@ -0,0 +1,8 @@
+/* Memory layout of the MPS2+ AN505 (Cortex-M33 IoT kit) in the secure state, where QEMU boots */
+MEMORY
+{
+  /* ZBT SSRAM 1; the initial VTOR (secure) points here */
+  FLASH : ORIGIN = 0x10000000, LENGTH = 4M
+  /* ZBT SSRAM 2 and 3 */
+  RAM : ORIGIN = 0x38000000, LENGTH = 4M
+}
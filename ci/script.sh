@@ -12,8 +12,58 @@ main() {
         return
     fi
 
+    if [ $T = thumbv8m.base-none-eabi ] || [ $T = thumbv8m.main-none-eabi ]; then
+        # the lm3s6965 device crate only exposes its full set of interrupts on ARMv7-M so only
+        # build the examples that use GPIOA..GPIOG / UART0 / UART1
+        local exs=(
+            idle
+            init
+            interrupt
+
+            resource
+            claim
+            late
+
+            task
+            message
+            topic
+
+            singleton
+            pool
+            control
+            stages
+            background
+        )
+
+        for ex in ${exs[@]}; do
+            cargo build --example $ex --target $T
+            cargo build --features timer-queue --example $ex --target $T
+        done
+
+        cargo build --features timer-queue --example edf --target $T
+        cargo build --features timer-queue --example delay --target $T
+
+        # these run on QEMU's mps2-an505 machine, with the memory layout in ci/an505 and the
+        # interrupt bindings of the lm3s6965 crate
+        local exs=(
+            claim
+            message
+        )
+
+        for ex in ${exs[@]}; do
+            cargo run --example $ex --target $T | \
+                diff -u ci/expected/$ex.run -
+            cargo run --features timer-queue --example $ex --target $T | \
+                diff -u ci/expected/$ex.run -
+        done
+        return
+    fi
+
     cargo check --target $T --examples
     cargo check --features timer-queue --target $T --examples
+    cargo check --features introspect --target $T --examples
+    cargo check --features wcet --target $T --examples
+    cargo check --features crash-report --target $T --examples
 
     case $T in
         thumbv6m-none-eabi | thumbv7m-none-eabi)
@@ -23,14 +73,20 @@ main() {
                 interrupt
 
                 resource
-                lock
+                claim
                 late
 
                 task
                 message
                 capacity
+                policy
+                topic
 
                 singleton
+                pool
+                control
+                stages
+                background
             )
 
             for ex in ${exs[@]}; do
@@ -40,6 +96,15 @@ main() {
                     diff -u ci/expected/$ex.run -
             done
 
+            cargo run --features introspect --example introspect --target $T | \
+                diff -u ci/expected/introspect.run -
+
+            cargo run --features timer-queue --example edf --target $T | \
+                diff -u ci/expected/edf.run -
+
+            cargo run --features timer-queue --example delay --target $T | \
+                diff -u ci/expected/delay.run -
+
             if [ $T != thumbv6m-none-eabi ]; then
                 cargo run --example ramfunc --target $T --release | \
                     diff -u ci/expected/ramfunc.run -
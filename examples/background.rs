@@ -0,0 +1,69 @@
+//! examples/background.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    // shared by `idle` and `flush` without locks
+    static mut LOGGED: u32 = 0;
+
+    #[init(spawn = [housekeeping])]
+    fn init() {
+        spawn.housekeeping().unwrap();
+    }
+
+    #[idle(resources = [LOGGED], pend = [UART0])]
+    fn idle() -> ! {
+        println!("idle");
+
+        *resources.LOGGED += 1;
+        pend.UART0();
+
+        // run the background tasks that are pending
+        resources.run_background();
+
+        println!("idle: LOGGED = {}", *resources.LOGGED);
+
+        debug::exit(debug::EXIT_SUCCESS);
+
+        loop {}
+    }
+
+    #[interrupt(spawn = [flush])]
+    fn UART0() {
+        println!("UART0");
+
+        spawn.flush().unwrap();
+    }
+
+    // background tasks don't need an interrupt in the `extern` block
+    #[task(priority = 0)]
+    fn housekeeping() {
+        println!("housekeeping");
+    }
+
+    #[task(priority = 0, resources = [LOGGED])]
+    fn flush() {
+        println!("flush: LOGGED = {}", *resources.LOGGED);
+
+        *resources.LOGGED = 0;
+    }
+};
@@ -8,7 +8,6 @@
 extern crate panic_semihosting;
 
 use cortex_m_semihosting::debug;
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -31,7 +30,7 @@ const APP: () = {
         spawn.foo().unwrap();
     }
 
-    #[task(schedule = [foo])]
+    #[task(pend = [UART0], schedule = [foo])]
     fn foo() {
         static mut ONCE: bool = true;
 
@@ -40,7 +39,7 @@ const APP: () = {
         if *ONCE {
             *ONCE = false;
 
-            rtfm::pend(Interrupt::UART0);
+            pend.UART0();
         } else {
             debug::exit(debug::EXIT_SUCCESS);
         }
@@ -8,7 +8,6 @@
 extern crate panic_semihosting;
 
 use cortex_m_semihosting::debug;
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -23,10 +22,10 @@ macro_rules! println {
 
 #[app(device = lm3s6965)]
 const APP: () = {
-    #[init(spawn = [foo])]
+    #[init(pend = [UART0], spawn = [foo])]
     fn init() {
         println!("init");
-        rtfm::pend(Interrupt::UART0);
+        pend.UART0();
     }
 
     #[idle]
@@ -8,7 +8,6 @@
 extern crate panic_semihosting;
 
 use cortex_m_semihosting::debug;
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -25,13 +24,13 @@ macro_rules! println {
 const APP: () = {
     static mut SHARED: u32 = 0;
 
-    #[init]
+    #[init(pend = [GPIOA])]
     fn init() {
-        rtfm::pend(Interrupt::GPIOA);
+        pend.GPIOA();
     }
 
     // when omitted priority is assumed to be `1`
-    #[interrupt(resources = [SHARED])]
+    #[interrupt(pend = [GPIOB, GPIOC], resources = [SHARED])]
     fn GPIOA() {
         println!("A");
 
@@ -41,12 +40,12 @@ const APP: () = {
             *shared += 1;
 
             // GPIOB will *not* run right now due to the critical section
-            rtfm::pend(Interrupt::GPIOB);
+            pend.GPIOB();
 
             println!("B - SHARED = {}", *shared);
 
             // GPIOC does not contend for `SHARED` so it's allowed to run now
-            rtfm::pend(Interrupt::GPIOC);
+            pend.GPIOC();
         });
 
         // critical section is over: GPIOB can now start
@@ -58,7 +57,6 @@ const APP: () = {
 
     #[interrupt(priority = 2, resources = [SHARED])]
     fn GPIOB() {
-        resources.SHARED.claim(|shared| {});
         // the higher priority task does *not* need a critical section
         *resources.SHARED += 1;
 
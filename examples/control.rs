@@ -0,0 +1,58 @@
+//! examples/control.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(pend = [UART0, UART1])]
+    fn init() {
+        pend.UART0();
+        pend.UART1();
+    }
+
+    // `UART0` stays disabled after `init` returns
+    #[interrupt(enabled = false, controls = [UART0])]
+    fn UART0() {
+        println!("UART0");
+
+        // `UART0` won't run again until it's re-enabled
+        controls.UART0.disable();
+        controls.UART0.pend();
+
+        println!("UART0: pending = {}", controls.UART0.is_pending());
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    #[interrupt(controls = [UART0])]
+    fn UART1() {
+        println!(
+            "UART1: UART0 enabled = {}, pending = {}",
+            controls.UART0.is_enabled(),
+            controls.UART0.is_pending()
+        );
+
+        // `UART0` runs after `UART1` returns because they have the same priority
+        controls.UART0.enable();
+
+        println!("UART1: UART0 enabled = {}", controls.UART0.is_enabled());
+    }
+};
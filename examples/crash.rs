@@ -0,0 +1,62 @@
+//! examples/crash.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use core::panic::PanicInfo;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT properly work on QEMU
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo])]
+    fn init() {
+        // `crash` is the report of the previous crash, if the last reset was caused by one
+        if let Some(report) = crash {
+            println!("cause = {:?}", report.cause());
+            println!("task = {:?}", report.task());
+            for (task, pending) in report.queues() {
+                println!("{}: {}", task, pending);
+            }
+
+            debug::exit(debug::EXIT_SUCCESS);
+        } else {
+            spawn.foo().unwrap();
+        }
+    }
+
+    #[task(spawn = [bar])]
+    fn foo() {
+        spawn.bar().unwrap();
+
+        // `bar` runs at the same priority so it's still pending when `foo` panics
+        panic!("foo");
+    }
+
+    #[task]
+    fn bar() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
+
+#[panic_handler]
+fn panic(_: &PanicInfo) -> ! {
+    // record the crash and reset the device
+    rtfm::crash::panic()
+}
@@ -0,0 +1,71 @@
+//! examples/delay.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::{app, Signal};
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// replies of `bar` to the requests of `foo`
+static REPLY: Signal<u32> = Signal::new();
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+
+    #[init(spawn = [foo])]
+    fn init() {
+        spawn.foo(3).unwrap();
+    }
+
+    // `async` tasks can be suspended; while `foo` waits other tasks can run
+    #[task(resources = [SHARED], spawn = [bar])]
+    async fn foo(n: u32) {
+        for i in 0..n {
+            println!("foo: request {}", i);
+
+            spawn.bar(i).unwrap();
+
+            // wait for the reply of `bar` but give up 4e6 cycles (clock cycles) from now
+            match timer.timeout(4_000_000.cycles(), REPLY.wait()).await {
+                Some(reply) => println!("foo: reply {}", reply),
+                None => println!("foo: timeout"),
+            }
+
+            // resources are accessed as usual
+            resources.SHARED.claim(|shared| *shared += 1);
+        }
+
+        println!("foo: SHARED = {}", resources.SHARED.claim(|shared| *shared));
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    // `bar` runs while `foo` waits for its reply
+    #[task(resources = [SHARED])]
+    fn bar(request: u32) {
+        *resources.SHARED += 1;
+
+        println!("bar: request {}", request);
+
+        REPLY.signal(2 * request);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,58 @@
+//! examples/edf.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [log, control])]
+    fn init() {
+        // both instances have the same baseline, `start`
+        spawn.log().unwrap();
+        spawn.control().unwrap();
+    }
+
+    // the tasks that run at this priority level are dispatched earliest deadline first
+    #[task(deadline = 8_000_000)]
+    fn log() {
+        println!("log");
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    #[task(deadline = 100_000, spawn = [sample])]
+    fn control() {
+        println!("control");
+
+        spawn.sample().unwrap();
+    }
+
+    // tasks with shorter relative deadlines may run at higher priorities; they preempt the running
+    // task only if their absolute deadline is earlier
+    #[task(priority = 2, deadline = 10_000)]
+    fn sample() {
+        println!("sample");
+    }
+
+    extern "C" {
+        fn UART0();
+        fn UART1();
+    }
+};
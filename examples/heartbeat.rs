@@ -0,0 +1,69 @@
+//! examples/heartbeat.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+const PERIOD: u32 = 1_000_000;
+
+// NOTE: does NOT properly work on QEMU
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(schedule = [blink])]
+    fn init() {
+        schedule.blink(start + PERIOD.cycles(), 0).unwrap();
+    }
+
+    #[idle]
+    fn idle() -> ! {
+        loop {
+            // only feed the watchdog while all the heartbeats are healthy
+            if heartbeats::healthy() {
+                feed_watchdog();
+            }
+        }
+    }
+
+    // `blink` must complete an instance at least once every 1_500_000 clock cycles
+    #[task(heartbeat = 1_500_000, schedule = [blink])]
+    fn blink(n: u32) {
+        println!("blink({})", n);
+
+        // simulate a bug: `blink` stops rescheduling itself after its third run
+        if n < 2 {
+            schedule.blink(scheduled + PERIOD.cycles(), n + 1).unwrap();
+        }
+    }
+
+    // runs from the timer queue when a task misses its heartbeat
+    #[stalled]
+    fn stalled() {
+        println!("stalled(task = {:?})", task);
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
+
+fn feed_watchdog() {
+    // reload the counter of the device's watchdog timer here
+}
@@ -8,7 +8,6 @@
 extern crate panic_semihosting;
 
 use cortex_m_semihosting::debug;
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -23,22 +22,22 @@ macro_rules! println {
 
 #[app(device = lm3s6965)]
 const APP: () = {
-    #[init]
+    #[init(pend = [UART0])]
     fn init() {
         // Pends the UART0 interrupt but its handler won't run until *after*
         // `init` returns because interrupts are disabled
-        rtfm::pend(Interrupt::UART0);
+        pend.UART0();
 
         println!("init");
     }
 
-    #[idle]
+    #[idle(pend = [UART0])]
     fn idle() -> ! {
         // interrupts are enabled again; the `UART0` handler runs at this point
 
         println!("idle");
 
-        rtfm::pend(Interrupt::UART0);
+        pend.UART0();
 
         debug::exit(debug::EXIT_SUCCESS);
 
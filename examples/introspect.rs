@@ -0,0 +1,70 @@
+//! examples/introspect.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(pend = [UART0])]
+    fn init() {
+        pend.UART0();
+    }
+
+    #[idle]
+    fn idle() -> ! {
+        let foo = introspect::tasks::foo();
+        println!(
+            "foo: current = {}, peak = {}, capacity = {}",
+            foo.current(),
+            foo.peak(),
+            foo.capacity()
+        );
+
+        let p1 = introspect::ready_queue(1).unwrap();
+        println!(
+            "P1: current = {}, peak = {}, capacity = {}",
+            p1.current(),
+            p1.peak(),
+            p1.capacity()
+        );
+
+        println!("symbols: {}", introspect::symbols().len());
+
+        debug::exit(debug::EXIT_SUCCESS);
+
+        loop {}
+    }
+
+    #[interrupt(priority = 2, spawn = [foo])]
+    fn UART0() {
+        for x in 0..4 {
+            spawn.foo(x).unwrap();
+        }
+    }
+
+    #[task(capacity = 4)]
+    fn foo(x: u32) {
+        println!("foo({})", x);
+    }
+
+    extern "C" {
+        fn UART1();
+    }
+};
@@ -12,7 +12,6 @@ use heapless::{
     consts::*,
     spsc::{Consumer, Producer, Queue},
 };
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -45,7 +44,7 @@ const APP: () = {
         C = c;
     }
 
-    #[idle(resources = [C])]
+    #[idle(pend = [UART0], resources = [C])]
     fn idle() -> ! {
         loop {
             if let Some(byte) = resources.C.dequeue() {
@@ -53,7 +52,7 @@ const APP: () = {
 
                 debug::exit(debug::EXIT_SUCCESS);
             } else {
-                rtfm::pend(Interrupt::UART0);
+                pend.UART0();
             }
         }
     }
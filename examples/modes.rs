@@ -0,0 +1,70 @@
+//! examples/modes.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT properly work on QEMU
+// the application starts in the first mode, `Standby`
+#[app(device = lm3s6965, frame = 4_000_000, modes = [Standby, Measure, Calibrate])]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    // released at the start of every major frame, in all the modes
+    #[task(slots = [0..500_000])]
+    fn heartbeat() {
+        static mut FRAMES: u32 = 0;
+
+        *FRAMES += 1;
+
+        println!("heartbeat(scheduled = {:?})", scheduled);
+
+        if *FRAMES == 1 {
+            // takes effect at the end of this major frame
+            mode.switch(Mode::Measure);
+        }
+    }
+
+    // released twice per major frame, only in the `Measure` mode
+    #[task(slots = [1_000_000..2_000_000, 3_000_000..4_000_000], modes = [Measure])]
+    fn measure() {
+        static mut SAMPLES: u32 = 0;
+
+        *SAMPLES += 1;
+
+        println!("measure(scheduled = {:?})", scheduled);
+
+        if *SAMPLES == 2 {
+            mode.switch(Mode::Calibrate);
+        }
+    }
+
+    // slots of different modes can overlap
+    #[task(slots = [1_000_000..3_000_000], modes = [Calibrate])]
+    fn calibrate() {
+        println!("calibrate(scheduled = {:?})", scheduled);
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
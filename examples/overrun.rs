@@ -0,0 +1,61 @@
+//! examples/overrun.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT properly work on QEMU
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [filter])]
+    fn init() {
+        spawn.filter(1_000).unwrap();
+    }
+
+    // `filter` must complete within 100_000 clock cycles of starting
+    #[task(wcet = 100_000, spawn = [filter])]
+    fn filter(n: u32) {
+        println!("filter({})", n);
+
+        for _ in 0..n {
+            cortex_m::asm::nop();
+        }
+
+        if n < 1_000_000 {
+            // this instance will exceed its budget
+            spawn.filter(1_000_000).unwrap();
+        } else {
+            debug::exit(debug::EXIT_SUCCESS);
+        }
+    }
+
+    // runs, preempting `filter`, as soon as `filter` exceeds its budget
+    #[overrun]
+    fn overrun() {
+        static mut COUNT: u32 = 0;
+
+        *COUNT += 1;
+
+        println!("overrun(task = {:?}, count = {})", task, COUNT);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
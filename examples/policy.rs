@@ -0,0 +1,73 @@
+//! examples/policy.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// the tasks at priority 1 are dispatched in round-robin fashion
+#[app(device = lm3s6965, round_robin = [1])]
+const APP: () = {
+    #[init(pend = [UART0])]
+    fn init() {
+        pend.UART0();
+    }
+
+    #[interrupt(spawn = [foo, bar, baz, quux], priority = 3)]
+    fn UART0() {
+        spawn.foo(0).unwrap();
+        spawn.foo(1).unwrap();
+        spawn.foo(2).unwrap();
+        spawn.bar(0).unwrap();
+        spawn.bar(1).unwrap();
+
+        spawn.baz().unwrap();
+        spawn.quux().unwrap();
+    }
+
+    #[task(capacity = 3)]
+    fn foo(x: u32) {
+        println!("foo({})", x);
+
+        if x == 2 {
+            debug::exit(debug::EXIT_SUCCESS);
+        }
+    }
+
+    #[task(capacity = 2)]
+    fn bar(x: u32) {
+        println!("bar({})", x);
+    }
+
+    // `quux` runs before `baz` because it has a lower `order`
+    #[task(priority = 2, order = 1)]
+    fn baz() {
+        println!("baz");
+    }
+
+    #[task(priority = 2, order = 0)]
+    fn quux() {
+        println!("quux");
+    }
+
+    // Interrupt handlers used to dispatch software tasks
+    extern "C" {
+        fn UART1();
+        fn UART2();
+    }
+};
@@ -0,0 +1,67 @@
+//! examples/pool.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::{app, pool::Box};
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    // The capacity of the pool is inferred: 2 blocks are enough for `foo` and `bar`
+    #[pool]
+    static PACKET: [u8; 128] = [0; 128];
+
+    #[init(pend = [I2C0])]
+    fn init() {
+        pend.I2C0();
+    }
+
+    #[interrupt(priority = 2, spawn = [foo])]
+    fn I2C0() {
+        let mut packet = PACKET::alloc().unwrap();
+        packet[0] = 42;
+
+        // only a pointer to the block is moved into the queue of `foo`
+        spawn.foo(packet).unwrap();
+    }
+
+    #[task(spawn = [bar])]
+    fn foo(packet: Box<PACKET>) {
+        println!("foo({})", packet[0]);
+
+        // forward the block to another task
+        spawn.bar(packet).unwrap();
+    }
+
+    #[task]
+    fn bar(packet: Box<PACKET>) {
+        println!("bar({})", packet[0]);
+
+        drop(packet);
+
+        // the block has returned to the pool
+        let packet = PACKET::alloc().unwrap();
+        println!("alloc: {}", packet[0]);
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
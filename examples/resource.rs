@@ -8,7 +8,6 @@
 extern crate panic_semihosting;
 
 use cortex_m_semihosting::debug;
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -26,10 +25,10 @@ const APP: () = {
     // A resource
     static mut SHARED: u32 = 0;
 
-    #[init]
+    #[init(pend = [UART0, UART1])]
     fn init() {
-        rtfm::pend(Interrupt::UART0);
-        rtfm::pend(Interrupt::UART1);
+        pend.UART0();
+        pend.UART1();
     }
 
     #[idle]
@@ -0,0 +1,44 @@
+//! examples/retain.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m::peripheral::SCB;
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT properly work on QEMU
+#[app(device = lm3s6965)]
+const APP: () = {
+    // Keeps its value across resets; it's only set to `0` on a cold boot
+    #[retain]
+    static mut BOOTS: u32 = 0;
+
+    #[init(resources = [BOOTS])]
+    fn init() {
+        *resources.BOOTS += 1;
+
+        println!("boot #{}", resources.BOOTS);
+
+        if *resources.BOOTS < 3 {
+            // the next boot is a warm one
+            SCB::sys_reset();
+        } else {
+            debug::exit(debug::EXIT_SUCCESS);
+        }
+    }
+};
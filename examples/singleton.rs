@@ -9,7 +9,6 @@ extern crate panic_semihosting;
 
 use alloc_singleton::stable::pool::{Box, Pool};
 use cortex_m_semihosting::debug;
-use lm3s6965::Interrupt;
 use rtfm::app;
 
 macro_rules! println {
@@ -29,9 +28,9 @@ const APP: () = {
 
     static mut P: Pool<M> = ();
 
-    #[init(resources = [M])]
+    #[init(pend = [I2C0], resources = [M])]
     fn init() {
-        rtfm::pend(Interrupt::I2C0);
+        pend.I2C0();
 
         P = Pool::new(resources.M);
     }
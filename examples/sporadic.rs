@@ -0,0 +1,59 @@
+//! examples/sporadic.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::{app, Instant, U32Ext};
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT properly work on QEMU
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[idle(spawn = [button])]
+    fn idle() -> ! {
+        // a bouncing contact produces several presses in quick succession
+        for press in 0..3 {
+            if let Err(e) = spawn.button(press) {
+                println!("press {} rejected: {:?}", press, e);
+            }
+        }
+
+        let start = Instant::now();
+        while start.elapsed() < 2_000_000.cycles() {}
+
+        spawn.button(3).unwrap();
+
+        println!("violations = {}", arrivals::button().violations());
+
+        debug::exit(debug::EXIT_SUCCESS);
+
+        loop {}
+    }
+
+    // releases of `button` must be at least 1_000_000 clock cycles apart
+    #[task(min_inter_arrival = 1_000_000)]
+    fn button(press: u32) {
+        println!("button({})", press);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
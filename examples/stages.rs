@@ -0,0 +1,67 @@
+//! examples/stages.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut LOCKED: bool = false;
+
+    // Runs before RAM is initialized so `static` variables can NOT be used here
+    #[allow(unsafe_code)]
+    #[pre_init]
+    unsafe fn pre_init() {
+        // e.g. configure the external memory controller
+    }
+
+    // Runs with interrupts disabled
+    #[init]
+    fn init() {
+        println!("init");
+    }
+
+    // Runs with interrupts enabled, before `idle`
+    #[post_init(pend = [UART0], resources = [LOCKED])]
+    fn post_init() {
+        println!("post_init");
+
+        // e.g. start the PLL and wait for the lock interrupt
+        pend.UART0();
+        while !resources.LOCKED.claim(|locked| *locked) {}
+
+        println!("post_init: locked");
+    }
+
+    #[idle]
+    fn idle() -> ! {
+        println!("idle");
+
+        debug::exit(debug::EXIT_SUCCESS);
+
+        loop {}
+    }
+
+    #[interrupt(resources = [LOCKED])]
+    fn UART0() {
+        println!("UART0");
+
+        *resources.LOCKED = true;
+    }
+};
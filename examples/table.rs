@@ -0,0 +1,59 @@
+//! examples/table.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT properly work on QEMU
+// the major frame repeats every 8 million cycles
+#[app(device = lm3s6965, frame = 8_000_000)]
+const APP: () = {
+    static mut FRAMES: u32 = 0;
+
+    #[init]
+    fn init() {}
+
+    // released at the start of every major frame
+    #[task(slots = [0..1_000_000], resources = [FRAMES])]
+    fn sense() {
+        *resources.FRAMES += 1;
+
+        println!("sense(scheduled = {:?})", scheduled);
+    }
+
+    // released twice per major frame
+    #[task(slots = [2_000_000..3_000_000, 6_000_000..7_000_000], resources = [FRAMES])]
+    fn actuate() {
+        println!("actuate(scheduled = {:?})", scheduled);
+
+        if *resources.FRAMES == 2 {
+            println!("misses = {}", slots::sense().misses());
+
+            debug::exit(debug::EXIT_SUCCESS);
+        }
+    }
+
+    // event-driven hardware tasks can preempt the time-triggered ones
+    #[interrupt(priority = 2)]
+    fn UART0() {}
+
+    extern "C" {
+        fn UART1();
+    }
+};
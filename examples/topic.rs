@@ -0,0 +1,60 @@
+//! examples/topic.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    x: i16,
+    y: i16,
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    // messages published to this topic are delivered to all its subscribers
+    #[topic]
+    type Imu = Sample;
+
+    #[init(publish = [Imu])]
+    fn init() {
+        publish.imu(Sample { x: 1, y: 2 }).unwrap();
+
+        // there's room for a single message in the queues of `log` and `filter`
+        let err = publish.imu(Sample { x: 3, y: 4 }).unwrap_err();
+        println!("{:?}", err);
+    }
+
+    #[task(priority = 2, subscribe = [Imu])]
+    fn log(sample: Imu) {
+        println!("log({:?})", sample);
+    }
+
+    #[task(subscribe = [Imu])]
+    fn filter(sample: Imu) {
+        println!("filter(x = {}, y = {})", sample.x, sample.y);
+
+        debug::exit(debug::EXIT_SUCCESS);
+    }
+
+    extern "C" {
+        fn UART0();
+        fn UART1();
+    }
+};
@@ -0,0 +1,78 @@
+//! examples/wcet.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate panic_semihosting;
+
+use cortex_m_semihosting::debug;
+use rtfm::app;
+
+macro_rules! println {
+    ($($tt:tt)*) => {
+        if let Ok(mut stdout) = cortex_m_semihosting::hio::hstdout() {
+            use core::fmt::Write;
+
+            writeln!(stdout, $($tt)*).ok();
+        }
+    };
+}
+
+// NOTE: does NOT work on QEMU!
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo])]
+    fn init() {
+        spawn.foo(100).unwrap();
+    }
+
+    #[idle(spawn = [foo])]
+    fn idle() -> ! {
+        spawn.foo(1_000).unwrap();
+
+        // dump the measurements
+        for (name, times) in wcet::all().iter() {
+            println!(
+                "{}: count = {}, min = {:?}, max = {:?}, total = {}",
+                name,
+                times.count(),
+                times.min(),
+                times.max(),
+                times.total()
+            );
+        }
+
+        debug::exit(debug::EXIT_SUCCESS);
+
+        loop {}
+    }
+
+    #[interrupt(priority = 2, spawn = [bar])]
+    fn UART0() {
+        spawn.bar().unwrap();
+    }
+
+    #[task(pend = [UART0])]
+    fn foo(n: u32) {
+        if n > 100 {
+            // `UART0` and `bar` will preempt `foo`; their execution time is not accounted to `foo`
+            pend.UART0();
+        }
+
+        for _ in 0..n {
+            cortex_m::asm::nop();
+        }
+    }
+
+    #[task(priority = 3)]
+    fn bar() {
+        cortex_m::asm::nop();
+    }
+
+    extern "C" {
+        fn UART1();
+        fn UART2();
+    }
+};
@@ -1,31 +1,45 @@
-use std::{
-    cmp,
-    collections::{HashMap, HashSet},
-};
+use std::cmp;
 
-use syn::{Attribute, Ident, Type};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{
+    spanned::Spanned, Attribute, Expr, GenericArgument, Ident, Lit, Path, PathArguments, Type,
+};
 
-use syntax::{App, Idents};
+use syntax::{App, Idents, Map, Set};
 
-pub type Ownerships = HashMap<Ident, Ownership>;
+pub type Ownerships = Map<Ident, Ownership>;
 
 pub struct Analysis {
     /// Capacities of free queues
     pub capacities: Capacities,
     pub dispatchers: Dispatchers,
     // Ceilings of free queues
-    pub free_queues: HashMap<Ident, u8>,
+    pub free_queues: Map<Ident, u8>,
+    /// Topics whose messages need to be `Clone` (they have more than one subscriber)
+    pub needs_clone: Set<Ident>,
     /// Tasks whose inputs need to be Send
-    pub needs_send: HashSet<Ident>,
+    pub needs_send: Set<Ident>,
     /// Types of RO resources that need to be Sync
-    pub needs_sync: HashSet<Box<Type>>,
+    pub needs_sync: Set<Box<Type>>,
     // Resource ownership
     pub ownerships: Ownerships,
+    /// Sizes of the memory pools
+    pub pools: Map<Ident, PoolSize>,
     // Ceilings of ready queues
-    pub ready_queues: HashMap<u8, u8>,
+    pub ready_queues: Map<u8, u8>,
     pub timer_queue: TimerQueue,
 }
 
+pub struct PoolSize {
+    /// Number of blocks
+    pub capacity: usize,
+    /// Number of blocks the message queues and the resources can hold on to
+    pub demand: usize,
+    /// Types that refer to the pool but whose number of blocks can't be counted
+    pub opaque: Vec<Span>,
+}
+
 #[derive(Clone, Copy)]
 pub enum Ownership {
     // NOTE priorities and ceilings are "logical" (0 = lowest priority, 255 = highest priority)
@@ -49,26 +63,51 @@ impl Ownership {
 pub struct Dispatcher {
     /// Attributes to apply to the dispatcher
     pub attrs: Vec<Attribute>,
-    pub interrupt: Ident,
+    /// `None` for the background level (priority 0); its tasks are run from the idle loop
+    pub interrupt: Option<Ident>,
     /// Tasks dispatched at this priority level
     pub tasks: Vec<Ident>,
     // Queue capacity
     pub capacity: u8,
+    /// How the dispatcher picks the next task to run
+    pub policy: Policy,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Policy {
+    /// Tasks run in the order they were spawned (one ready queue shared by all the tasks)
+    Fifo,
+    /// Pending instances of the task with the lowest `order` run first (one ready queue per task)
+    SubPriority,
+    /// The dispatcher takes turns between the tasks that have pending instances (one ready queue
+    /// per task)
+    RoundRobin,
+    /// The pending instance with the earliest absolute deadline runs first (one ready queue, kept
+    /// sorted by deadline, shared by all the tasks)
+    Deadline,
 }
 
 /// Priority -> Dispatcher
-pub type Dispatchers = HashMap<u8, Dispatcher>;
+pub type Dispatchers = Map<u8, Dispatcher>;
 
-pub type Capacities = HashMap<Ident, u8>;
+pub type Capacities = Map<Ident, u8>;
 
 pub fn app(app: &App) -> Analysis {
     // Ceiling analysis of R/W resource and Sync analysis of RO resources
     // (Resource shared by tasks that run at different priorities need to be `Sync`)
-    let mut ownerships = Ownerships::new();
-    let mut needs_send = HashSet::new();
-    let mut needs_sync = HashSet::new();
+    let mut ownerships = Ownerships::default();
+    let mut needs_send = Set::default();
+    let mut needs_sync = Set::default();
 
-    for (priority, res) in app.resource_accesses() {
+    // The tasks that never run are included so that their bodies type check against the same
+    // resource proxies they would get if they were spawned
+    let dead_accesses = app.dead_tasks.values().flat_map(|t| {
+        t.args
+            .resources
+            .iter()
+            .map(move |res| (t.args.priority, res))
+    });
+    for (priority, res) in app.resource_accesses().chain(dead_accesses.clone()) {
         if let Some(ownership) = ownerships.get_mut(res) {
             match *ownership {
                 Ownership::Owned { priority: ceiling } | Ownership::Shared { ceiling } => {
@@ -91,17 +130,48 @@ pub fn app(app: &App) -> Analysis {
         ownerships.insert(res.clone(), Ownership::Owned { priority });
     }
 
+    // An async task may be suspended while it holds a reference to a resource so resources that
+    // async tasks share with other tasks (even with tasks that run at the same priority) must be
+    // accessed through a proxy (`claim`)
+    let mut accessors: Map<&Ident, usize> = Map::default();
+    for (_, res) in app.resource_accesses().chain(dead_accesses) {
+        *accessors.entry(res).or_insert(0) += 1;
+    }
+    for task in app
+        .tasks
+        .values()
+        .chain(app.dead_tasks.values())
+        .filter(|task| task.is_async)
+    {
+        for res in &task.args.resources {
+            if accessors[res] > 1 {
+                if let Some(Ownership::Owned { priority }) = ownerships.get(res).cloned() {
+                    ownerships.insert(res.clone(), Ownership::Shared { ceiling: priority });
+                }
+            }
+        }
+    }
+
     // Compute sizes of free queues
     // We assume at most one message per `spawn` / `schedule`
     let mut capacities: Capacities = app.tasks.keys().map(|task| (task.clone(), 0)).collect();
     for (_, task) in app.spawn_calls().chain(app.schedule_calls()) {
         *capacities.get_mut(task).expect("BUG: capacities.get_mut") += 1;
     }
+    // Each `publish` call sends one message to each subscriber
+    for (_, topic) in app.publish_calls() {
+        for task in app.subscribers(topic) {
+            *capacities.get_mut(task).expect("BUG: capacities.get_mut") += 1;
+        }
+    }
 
     // Override computed capacities if user specified a capacity in `#[task]`
+    // At most one instance of an async task can be in flight
     for (name, task) in &app.tasks {
         if let Some(cap) = task.args.capacity {
             *capacities.get_mut(name).expect("BUG: capacities.get_mut") = cap;
+        } else if task.is_async || !task.args.slots.is_empty() {
+            *capacities.get_mut(name).expect("BUG: capacities.get_mut") = 1;
         }
     }
 
@@ -110,49 +180,204 @@ pub fn app(app: &App) -> Analysis {
     // `schedule`-able task
     let mut tq_capacity = 0;
     let mut tq_priority = 1;
-    let mut tq_tasks = Idents::new();
+    let mut tq_tasks = Idents::default();
     for (_, task) in app.schedule_calls() {
         tq_capacity += capacities[task];
         tq_priority = cmp::max(tq_priority, app.tasks[task].args.priority);
         tq_tasks.insert(task.clone());
     }
 
+    // Async tasks put one entry in the timer queue while they `await` a `Delay`
+    let mut tq_wakers = Idents::default();
+    if cfg!(feature = "timer-queue") {
+        for (name, _) in app.tasks.iter().filter(|(_, task)| task.is_async) {
+            tq_capacity += 1;
+            tq_wakers.insert(name.clone());
+        }
+    }
+
+    // The schedule table keeps one entry in the timer queue: the release of the next slot
+    let mut tq_table = Idents::default();
+    for (_, task) in app.table() {
+        tq_priority = cmp::max(tq_priority, app.tasks[task].args.priority);
+        tq_table.insert(task.clone());
+    }
+    if !tq_table.is_empty() {
+        tq_capacity += 1;
+    }
+
+    // Each task with a `wcet` budget keeps at most one entry in the timer queue: the end of the
+    // budget of its running instance. The timer queue monitors the budgets so it must be able to
+    // preempt all the tasks
+    let mut tq_budgets = Idents::default();
+    for (task, _) in app.budgets() {
+        tq_capacity += 1;
+        tq_budgets.insert(task.clone());
+    }
+    if !tq_budgets.is_empty() {
+        let max = app
+            .exceptions
+            .values()
+            .map(|e| e.args.priority)
+            .chain(app.interrupts.values().map(|i| i.args.priority))
+            .chain(app.tasks.values().map(|t| t.args.priority))
+            .max()
+            .unwrap_or(0);
+        tq_priority = cmp::max(tq_priority, max + 1);
+    }
+
+    // Each task with a `heartbeat` keeps one entry in the timer queue: the next time its
+    // heartbeat is checked
+    let mut tq_heartbeats = Idents::default();
+    for (task, _) in app.heartbeats() {
+        tq_capacity += 1;
+        tq_heartbeats.insert(task.clone());
+    }
+
     // Compute dispatchers capacities
     // Determine which tasks are dispatched by which dispatcher
     // Compute the timer queue priority which matches the priority of the highest priority
     // dispatcher
-    let mut dispatchers = Dispatchers::new();
-    let mut free_interrupts = app.free_interrupts.iter();
+    let mut dispatchers = Dispatchers::default();
+    // Interrupts bound to a priority level (`#[priority = ..]`) dispatch that level; the remaining
+    // levels get the unbound interrupts
+    let bound_interrupts = app
+        .free_interrupts
+        .iter()
+        .filter_map(|(name, fi)| {
+            fi.priority
+                .as_ref()
+                .map(|level| (level.value() as u8, (name, fi)))
+        })
+        .collect::<Map<_, _>>();
+    let mut free_interrupts = app
+        .free_interrupts
+        .iter()
+        .filter(|(_, fi)| fi.priority.is_none());
     let mut tasks = app.tasks.iter().collect::<Vec<_>>();
     tasks.sort_by(|l, r| l.1.args.priority.cmp(&r.1.args.priority));
     for (name, task) in tasks {
         let dispatcher = dispatchers.entry(task.args.priority).or_insert_with(|| {
-            let (name, fi) = free_interrupts
-                .next()
+            if task.args.priority == 0 {
+                return Dispatcher {
+                    attrs: vec![],
+                    capacity: 0,
+                    interrupt: None,
+                    policy: Policy::Fifo,
+                    tasks: vec![],
+                };
+            }
+
+            let (name, fi) = bound_interrupts
+                .get(&task.args.priority)
+                .cloned()
+                .or_else(|| free_interrupts.next())
                 .expect("BUG: not enough free_interrupts");
 
             Dispatcher {
                 attrs: fi.attrs.clone(),
                 capacity: 0,
-                interrupt: name.clone(),
+                interrupt: Some(name.clone()),
+                policy: Policy::Fifo,
                 tasks: vec![],
             }
         });
 
         dispatcher.capacity += capacities[name];
         dispatcher.tasks.push(name.clone());
+
+        if task.args.order.is_some() {
+            dispatcher.policy = Policy::SubPriority;
+        } else if task.args.deadline.is_some() {
+            dispatcher.policy = Policy::Deadline;
+        }
+    }
+
+    // Select the dispatching policy of each priority level
+    for (level, dispatcher) in &mut dispatchers {
+        if app
+            .args
+            .round_robin
+            .iter()
+            .any(|lit| lit.value() == u64::from(*level))
+        {
+            dispatcher.policy = Policy::RoundRobin;
+
+            // take turns in a predictable order
+            dispatcher.tasks.sort_by_key(|task| task.to_string());
+        } else if dispatcher.policy == Policy::SubPriority {
+            dispatcher
+                .tasks
+                .sort_by_key(|task| app.tasks[task].args.order.as_ref().map(|lit| lit.value()));
+        }
     }
 
     // All messages sent from `init` need to be `Send`
     for task in app.init.args.spawn.iter().chain(&app.init.args.schedule) {
         needs_send.insert(task.clone());
     }
+    for topic in &app.init.args.publish {
+        for task in app.subscribers(topic) {
+            needs_send.insert(task.clone());
+        }
+    }
+
+    // Each subscriber gets its own copy of the published message
+    let mut needs_clone = Set::default();
+    for (_, topic) in app.publish_calls() {
+        if app.subscribers(topic).len() > 1 {
+            needs_clone.insert(topic.clone());
+        }
+    }
+
+    // Compute the sizes of the memory pools
+    // Each instance of a task that receives a `Box` holds on to its block while it's queued and
+    // while it runs. (Async tasks keep their free queue slot until they complete so their capacity
+    // already accounts for the running instance). Resources hold on to their blocks at all times.
+    // Blocks held by local variables are not accounted for
+    let mut pools = Map::default();
+    for (pool, def) in &app.pools {
+        let mut demand = 0;
+        let mut opaque = vec![];
+        let mut count = |ty: &Type, instances: usize| match blocks(app, pool, ty) {
+            Ok(blocks) => demand += instances * blocks,
+            Err(span) => opaque.push(span),
+        };
+
+        for (name, task) in &app.tasks {
+            let instances = usize::from(capacities[name]) + if task.is_async { 0 } else { 1 };
+
+            for input in &task.inputs {
+                count(&input.ty, instances);
+            }
+        }
+
+        for res in app.resources.values() {
+            count(&res.ty, 1);
+        }
+
+        let capacity = def
+            .args
+            .capacity
+            .as_ref()
+            .map(|lit| lit.value() as usize)
+            .unwrap_or(cmp::max(demand, 1));
+
+        pools.insert(
+            pool.clone(),
+            PoolSize {
+                capacity,
+                demand,
+                opaque,
+            },
+        );
+    }
 
     // Ceiling analysis of free queues (consumer end point) -- first pass
     // Ceiling analysis of ready queues (producer end point)
     // Also compute more Send-ness requirements
-    let mut free_queues: HashMap<_, _> = app.tasks.keys().map(|task| (task.clone(), 0)).collect();
-    let mut ready_queues: HashMap<_, _> = dispatchers.keys().map(|level| (*level, 0)).collect();
+    let mut free_queues: Map<_, _> = app.tasks.keys().map(|task| (task.clone(), 0)).collect();
+    let mut ready_queues: Map<_, _> = dispatchers.keys().map(|level| (*level, 0)).collect();
     for (priority, task) in app.spawn_calls() {
         if let Some(priority) = priority {
             // Users of `spawn` contend for the to-be-spawned task FREE_QUEUE
@@ -173,10 +398,47 @@ pub fn app(app: &App) -> Analysis {
             // spawns from `init` are excluded from the ceiling analysis
         }
     }
+    // Publishers contend for the FREE_QUEUEs and READY_QUEUEs of the subscribers, like `spawn`
+    for (priority, topic) in app.publish_calls() {
+        if let Some(priority) = priority {
+            for task in app.subscribers(topic) {
+                let c = free_queues.get_mut(task).expect("BUG: free_queue.get_mut");
+                *c = cmp::max(*c, priority);
+
+                let c = ready_queues
+                    .get_mut(&app.tasks[task].args.priority)
+                    .expect("BUG: ready_queues.get_mut");
+                *c = cmp::max(*c, priority);
+
+                if app.tasks[task].args.priority != priority {
+                    needs_send.insert(task.clone());
+                }
+            }
+        }
+    }
+
+    // The timer queue releases the tasks of the schedule table: it takes a slot from their
+    // FREE_QUEUE and pushes them onto their READY_QUEUE
+    for task in &tq_table {
+        let c = free_queues.get_mut(task).expect("BUG: free_queue.get_mut");
+        *c = cmp::max(*c, tq_priority);
+
+        let c = ready_queues
+            .get_mut(&app.tasks[task].args.priority)
+            .expect("BUG: ready_queues.get_mut");
+        *c = cmp::max(*c, tq_priority);
+    }
+
+    // NOTE the levels dispatched earliest deadline first share a scheduler that's only accessed
+    // from within global critical sections; the ceilings of their ready queues are not used
 
     // Ceiling analysis of free queues (consumer end point) -- second pass
     // Ceiling analysis of the timer queue
     let mut tq_ceiling = tq_priority;
+    for task in &tq_wakers {
+        // Async tasks contend for the timer queue when they register a `Delay`
+        tq_ceiling = cmp::max(tq_ceiling, app.tasks[task].args.priority);
+    }
     for (priority, task) in app.schedule_calls() {
         if let Some(priority) = priority {
             // Users of `schedule` contend for the to-be-spawned task FREE_QUEUE (consumer end point)
@@ -194,22 +456,146 @@ pub fn app(app: &App) -> Analysis {
         capacities,
         dispatchers,
         free_queues,
+        needs_clone,
         needs_send,
         needs_sync,
         ownerships,
+        pools,
         ready_queues,
         timer_queue: TimerQueue {
+            budgets: tq_budgets,
             capacity: tq_capacity,
             ceiling: tq_ceiling,
+            heartbeats: tq_heartbeats,
             priority: tq_priority,
+            table: tq_table,
             tasks: tq_tasks,
+            wakers: tq_wakers,
         },
     }
 }
 
 pub struct TimerQueue {
+    /// Tasks whose `wcet` budget is monitored
+    pub budgets: Idents,
     pub capacity: u8,
     pub ceiling: u8,
+    /// Tasks whose `heartbeat` is monitored
+    pub heartbeats: Idents,
     pub priority: u8,
+    /// Tasks released by the schedule table
+    pub table: Idents,
     pub tasks: Idents,
+    /// Async tasks that can be woken up by the timer queue
+    pub wakers: Idents,
+}
+
+impl TimerQueue {
+    /// Whether the application needs a timer queue
+    pub fn is_used(&self) -> bool {
+        !self.tasks.is_empty()
+            || !self.wakers.is_empty()
+            || !self.table.is_empty()
+            || !self.budgets.is_empty()
+            || !self.heartbeats.is_empty()
+    }
+}
+
+/// Counts the blocks of `pool` that a value of type `ty` holds on to
+///
+/// Only `Box<pool>`, and tuples, arrays and `Option`s of them, can be counted; the span of any other
+/// type that refers to the pool is returned as an error. Topic aliases are resolved
+fn blocks(app: &App, pool: &Ident, ty: &Type) -> Result<usize, Span> {
+    match ty {
+        Type::Paren(paren) => blocks(app, pool, &paren.elem),
+        Type::Group(group) => blocks(app, pool, &group.elem),
+        Type::Tuple(tuple) => tuple
+            .elems
+            .iter()
+            .map(|elem| blocks(app, pool, elem))
+            .sum(),
+        Type::Array(array) => {
+            let blocks = blocks(app, pool, &array.elem)?;
+            if blocks == 0 {
+                return Ok(0);
+            }
+
+            match &array.len {
+                Expr::Lit(lit) => match &lit.lit {
+                    Lit::Int(len) => Ok(blocks * len.value() as usize),
+                    _ => Err(array.len.span()),
+                },
+                _ => Err(array.len.span()),
+            }
+        }
+        Type::Path(path) if path.qself.is_none() => {
+            if let Some(topic) = single_ident(&path.path).and_then(|ident| app.topics.get(ident)) {
+                return blocks(app, pool, &topic.ty);
+            }
+
+            let last = path.path.segments.iter().last().expect("UNREACHABLE");
+            let args = match &last.arguments {
+                PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match &args.args[0] {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            match args {
+                Some(Type::Path(arg))
+                    if last.ident == "Box"
+                        && arg.qself.is_none()
+                        && single_ident(&arg.path) == Some(pool) =>
+                {
+                    Ok(1)
+                }
+                Some(arg) if last.ident == "Option" => blocks(app, pool, arg),
+                _ => refers(app, pool, ty),
+            }
+        }
+        _ => refers(app, pool, ty),
+    }
+}
+
+/// The identifier of a path made of a single segment without arguments
+fn single_ident(path: &Path) -> Option<&Ident> {
+    if path.leading_colon.is_none() && path.segments.len() == 1 {
+        let segment = &path.segments[0];
+        if segment.arguments.is_empty() {
+            return Some(&segment.ident);
+        }
+    }
+
+    None
+}
+
+/// `Ok(0)` if `ty` doesn't refer to `pool`, directly or through a topic alias
+fn refers(app: &App, pool: &Ident, ty: &Type) -> Result<usize, Span> {
+    let tokens = ty.into_token_stream();
+    let refers = mentions(tokens.clone(), pool) != 0
+        || app.topics.iter().any(|(name, topic)| {
+            mentions(tokens.clone(), name) != 0
+                && mentions(topic.ty.clone().into_token_stream(), pool) != 0
+        });
+
+    if refers {
+        Err(ty.span())
+    } else {
+        Ok(0)
+    }
+}
+
+/// Counts the occurrences of `ident` in `tokens`
+fn mentions(tokens: TokenStream, ident: &Ident) -> usize {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            TokenTree::Group(group) => mentions(group.stream(), ident),
+            TokenTree::Ident(ref i) if i == ident => 1,
+            _ => 0,
+        })
+        .sum()
 }
@@ -1,11 +1,16 @@
-use std::{collections::HashSet, iter};
+use std::{cmp, iter, ptr, u8};
 
 use proc_macro2::Span;
-use syn::parse;
+use syn::Ident;
 
-use syntax::App;
+use analyze::Analysis;
+use syntax::{App, Errors, Map, Set, Topic};
+
+// NOTE all the checks run even if some of them fail so that all the errors in the specification are
+// reported at once
+pub fn app(app: &App) -> Result<(), Errors> {
+    let mut errors = Errors::default();
 
-pub fn app(app: &App) -> parse::Result<()> {
     // Check that all referenced resources have been declared
     for res in app
         .idle
@@ -13,25 +18,33 @@ pub fn app(app: &App) -> parse::Result<()> {
         .map(|idle| -> Box<Iterator<Item = _>> { Box::new(idle.args.resources.iter()) })
         .unwrap_or_else(|| Box::new(iter::empty()))
         .chain(&app.init.args.resources)
+        .chain(app.post_init.iter().flat_map(|pi| &pi.args.resources))
         .chain(app.exceptions.values().flat_map(|e| &e.args.resources))
         .chain(app.interrupts.values().flat_map(|i| &i.args.resources))
         .chain(app.tasks.values().flat_map(|t| &t.args.resources))
     {
         if !app.resources.contains_key(res) {
-            return Err(parse::Error::new(
-                res.span(),
+            undeclared(
+                &mut errors,
+                res,
                 "this resource has NOT been declared",
-            ));
+                app.resources.keys(),
+            );
         }
     }
 
     // Check that late resources have not been assigned to `init`
+    // (device peripherals are initialized before `init` runs)
     for res in &app.init.args.resources {
-        if app.resources.get(res).unwrap().expr.is_none() {
-            return Err(parse::Error::new(
-                res.span(),
-                "late resources can NOT be assigned to `init`",
-            ));
+        if let Some(name) = app.resources.keys().find(|name| *name == res) {
+            let res_ = &app.resources[name];
+            if res_.expr.is_none() && !res_.peripheral {
+                errors.push(res.span(), "late resources can NOT be assigned to `init`");
+                errors.note(
+                    name.span(),
+                    format!("`{}` is declared here as a late resource", name),
+                );
+            }
         }
     }
 
@@ -39,13 +52,35 @@ pub fn app(app: &App) -> parse::Result<()> {
     for res in app
         .resources
         .iter()
-        .filter_map(|(name, res)| if res.expr.is_none() { Some(name) } else { None })
+        .filter_map(|(name, res)| {
+            if res.expr.is_none() && !res.peripheral {
+                Some(name)
+            } else {
+                None
+            }
+        })
     {
         if app.init.assigns.iter().all(|assign| assign.left != *res) {
-            return Err(parse::Error::new(
+            errors.push(
                 res.span(),
                 "late resources MUST be initialized at the end of `init`",
-            ));
+            );
+        }
+    }
+
+    // Check that device peripherals are not initialized in `#[init]`
+    for assign in &app.init.assigns {
+        if app
+            .resources
+            .get(&assign.left)
+            .map(|res| res.peripheral)
+            .unwrap_or(false)
+        {
+            errors.push(
+                assign.left.span(),
+                "device peripherals are initialized by the runtime; they can NOT be assigned in \
+                 `init`",
+            );
         }
     }
 
@@ -59,6 +94,11 @@ pub fn app(app: &App) -> parse::Result<()> {
         .unwrap_or_else(|| Box::new(iter::empty()))
         .chain(&app.init.args.schedule)
         .chain(&app.init.args.spawn)
+        .chain(
+            app.post_init
+                .iter()
+                .flat_map(|pi| pi.args.schedule.iter().chain(&pi.args.spawn)),
+        )
         .chain(
             app.exceptions
                 .values()
@@ -75,41 +115,720 @@ pub fn app(app: &App) -> parse::Result<()> {
                 .flat_map(|t| t.args.schedule.iter().chain(&t.args.spawn)),
         ) {
         if !app.tasks.contains_key(task) {
-            return Err(parse::Error::new(
-                task.span(),
+            undeclared(
+                &mut errors,
+                task,
                 "this task has NOT been declared",
-            ));
+                app.tasks.keys(),
+            );
         }
     }
 
-    // Check that there are enough free interrupts to dispatch all tasks
+    // Check that all the topics that are published to or subscribed to have been declared
+    for topic in app
+        .publish_callers()
+        .flat_map(|(_, publish)| publish)
+        .chain(app.tasks.values().flat_map(|t| &t.args.subscribe))
+    {
+        if !app.topics.contains_key(topic) {
+            undeclared(
+                &mut errors,
+                topic,
+                "this topic has NOT been declared",
+                app.topics.keys(),
+            );
+        }
+    }
+
+    // Check that subscribers take the message as their only input
+    for (name, task) in &app.tasks {
+        if !task.args.subscribe.is_empty() && task.inputs.len() != 1 {
+            errors.push(
+                name.span(),
+                "tasks that subscribe to a topic must take exactly one input: the message",
+            );
+        }
+    }
+
+    for name in app.topics.keys() {
+        // the `publish` error reports the subscribers whose queues are full using a `u32` mask
+        let nsubscribers = app.subscribers(name).len();
+        if nsubscribers > 32 {
+            errors.push(
+                name.span(),
+                format!(
+                    "a topic can have at most 32 subscribers; this one has {}",
+                    nsubscribers
+                ),
+            );
+        }
+
+        // topics, pools and the modules generated for the tasks share the type namespace
+        if let Some(other) = app
+            .tasks
+            .keys()
+            .chain(app.exceptions.keys())
+            .chain(app.interrupts.keys())
+            .chain(app.pools.keys())
+            .find(|other| *other == name)
+        {
+            errors.push(
+                name.span(),
+                "this name is used by both a topic and a task or pool",
+            );
+            errors.note(other.span(), format!("`{}` is declared here", other));
+        }
+
+        // each topic gets a `publish` method named after it
+        let method = Topic::method(name);
+        if let Some(other) = app
+            .topics
+            .keys()
+            .find(|other| *other != name && Topic::method(other) == method)
+        {
+            errors.push(
+                name.span(),
+                format!(
+                    "topics `{}` and `{}` would both be published with `publish.{}`",
+                    name, other, method
+                ),
+            );
+        }
+    }
+
+    // Check that only interrupt handlers are controlled
+    for task in app.controls() {
+        if !app.interrupts.contains_key(task) {
+            undeclared(
+                &mut errors,
+                task,
+                "only `#[interrupt]` handlers can be listed in `controls`",
+                app.interrupts.keys(),
+            );
+        }
+    }
+
+    // Check that only interrupt handlers are pended; the interrupts listed in the `extern` block
+    // are reserved for the dispatchers
+    for (_, pend) in app.pend_callers() {
+        for task in pend {
+            if !app.interrupts.contains_key(task) {
+                undeclared(
+                    &mut errors,
+                    task,
+                    "only `#[interrupt]` handlers can be listed in `pend`",
+                    app.interrupts.keys(),
+                );
+            }
+        }
+    }
+
+    // Check that software tasks don't take the names of the `init`, `post_init` and `idle`
+    // contexts; code generation tells those contexts apart by name
+    for name in app.tasks.keys() {
+        for context in &["init", "post_init", "idle"] {
+            if name == context {
+                errors.push(
+                    name.span(),
+                    format!(
+                        "`{}` is reserved for the `#[{0}]` context; pick another name for this task",
+                        context
+                    ),
+                );
+            }
+        }
+    }
+
+    // Check that exceptions don't use the `enabled` argument; they can't be masked
+    for exception in app.exceptions.values() {
+        if let Some(enabled) = exception.args.enabled.as_ref() {
+            errors.push(
+                enabled.span,
+                "exceptions can NOT be disabled; `enabled` is only accepted by `#[interrupt]`",
+            );
+        }
+    }
+
+    // Check that there are enough free interrupts to dispatch all tasks (dead tasks are not
+    // dispatched and the background tasks, priority 0, are run from the idle loop)
+    let live = app.live_tasks();
     let ndispatchers = app
         .tasks
-        .values()
-        .map(|t| t.args.priority)
-        .collect::<HashSet<_>>()
+        .iter()
+        .filter(|(name, t)| live.contains(*name) && t.args.priority != 0)
+        .map(|(_, t)| t.args.priority)
+        .collect::<Set<_>>()
         .len();
     if ndispatchers > app.free_interrupts.len() {
-        return Err(parse::Error::new(
+        errors.push(
             Span::call_site(),
-            &*format!(
+            format!(
                 "{} free interrupt{} (`extern {{ .. }}`) {} required to dispatch all soft tasks",
                 ndispatchers,
                 if ndispatchers > 1 { "s" } else { "" },
                 if ndispatchers > 1 { "are" } else { "is" },
             ),
-        ));
+        );
+    }
+
+    // Check that async tasks don't specify a capacity; only one instance of an async task can be
+    // in flight at any time
+    for (name, task) in &app.tasks {
+        if task.is_async && task.args.capacity.is_some() {
+            errors.push(
+                name.span(),
+                "async tasks can't specify a `capacity`; their capacity is always 1",
+            );
+        }
+    }
+
+    // Check that the priority levels dispatched earliest deadline first contain only tasks with a
+    // relative `deadline` and that the preemption levels (priorities) are consistent with the
+    // relative deadlines, which is what SRP requires to keep its guarantees under EDF
+    for (name, task) in &app.tasks {
+        let level = task.args.priority;
+
+        if let Some(deadline) = task.args.deadline.as_ref() {
+            if task.is_async {
+                errors.push(
+                    deadline.span(),
+                    "async tasks can't specify a `deadline`; only their first poll is dispatched \
+                     by deadline",
+                );
+            }
+
+            if task.args.order.is_some() {
+                errors.push(
+                    deadline.span(),
+                    "`deadline` and `order` can't be used together",
+                );
+            }
+
+            if app
+                .args
+                .round_robin
+                .iter()
+                .any(|lit| lit.value() == u64::from(level))
+            {
+                errors.push(
+                    deadline.span(),
+                    "`deadline` can't be used on tasks dispatched in round-robin fashion",
+                );
+            }
+        }
+
+        for (other_name, other) in &app.tasks {
+            if other_name == name {
+                continue;
+            }
+
+            match (task.args.deadline.as_ref(), other.args.deadline.as_ref()) {
+                (Some(deadline), Some(other_deadline)) => {
+                    if level > other.args.priority && deadline.value() > other_deadline.value() {
+                        errors.push(
+                            deadline.span(),
+                            format!(
+                                "this task runs at a higher priority than task `{}` but has a \
+                                 longer relative deadline; priorities must not increase with the \
+                                 relative deadline",
+                                other_name
+                            ),
+                        );
+                    }
+                }
+                (None, Some(_)) if other.args.priority == level => {
+                    errors.push(
+                        name.span(),
+                        format!(
+                            "this task must specify a `deadline` because other tasks with the \
+                             same priority do (e.g. `{}`)",
+                            other_name
+                        ),
+                    );
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Check that the slots of the schedule table fit in the major frame and don't overlap
+    let table = app.table();
+    match (app.args.frame.as_ref(), table.first()) {
+        (None, Some((_, task))) => {
+            errors.push(
+                task.span(),
+                "tasks with `slots` need a major frame; specify its length using the `frame` \
+                 argument of `#[app]`",
+            );
+        }
+        (Some(frame), None) => {
+            errors.push(frame.span(), "no task has `slots` in the schedule table");
+        }
+        (Some(frame), Some(_)) => {
+            for (slot, _) in &table {
+                if slot.end.value() > frame.value() {
+                    errors.push(
+                        slot.end.span(),
+                        format!(
+                            "this slot doesn't fit in the major frame of {} cycles",
+                            frame.value()
+                        ),
+                    );
+                }
+            }
+        }
+        (None, None) => {}
+    }
+
+    if let (None, Some(mode)) = (app.args.frame.as_ref(), app.args.modes.first()) {
+        errors.push(
+            mode.span(),
+            "operating modes need a schedule table; specify the length of its major frame using \
+             the `frame` argument of `#[app]`",
+        );
+    }
+
+    // Slots only need to be disjoint within each mode; a slot shared by several modes is reported
+    // only once
+    let tables = app.mode_tables();
+    let mut overlapping = vec![];
+    for (_, table) in &tables {
+        for pair in table.windows(2) {
+            let (prev, prev_task) = pair[0];
+            let (slot, _) = pair[1];
+
+            if slot.start.value() < prev.end.value()
+                && !overlapping.iter().any(|s| ptr::eq(*s, slot))
+            {
+                overlapping.push(slot);
+                errors.push(
+                    slot.start.span(),
+                    format!(
+                        "this slot overlaps the slot `{}..{}` of task `{}`",
+                        prev.start.value(),
+                        prev.end.value(),
+                        prev_task
+                    ),
+                );
+            }
+        }
+    }
+
+    // The timer queue entry of the schedule table indexes its slots, and the frame boundary that
+    // follows them, with an `u8`
+    let slots = tables.iter().map(|(_, table)| table.len()).sum::<usize>();
+    if let (Some(frame), true) = (app.args.frame.as_ref(), slots > 255) {
+        errors.push(
+            frame.span(),
+            format!(
+                "the schedule table can't have more than 255 slots but it has {}",
+                slots
+            ),
+        );
+    }
+
+    for (name, task) in &app.tasks {
+        for mode in &task.args.modes {
+            if !app.args.modes.contains(mode) {
+                undeclared(
+                    &mut errors,
+                    mode,
+                    "this mode has NOT been declared",
+                    app.args.modes.iter(),
+                );
+            }
+        }
+
+        if !task.args.modes.is_empty() && task.args.slots.is_empty() {
+            errors.push(
+                name.span(),
+                "only time-triggered tasks (tasks with `slots`) can specify `modes`",
+            );
+        }
+    }
+
+    // Check that the tasks released by the schedule table are only released by it
+    for (name, task) in &app.tasks {
+        if task.args.slots.is_empty() {
+            continue;
+        }
+
+        if !task.inputs.is_empty() {
+            errors.push(name.span(), "time-triggered tasks can't take inputs");
+        }
+
+        if task.is_async {
+            errors.push(name.span(), "time-triggered tasks can't be async");
+        }
+
+        if task.args.capacity.is_some() {
+            errors.push(
+                name.span(),
+                "time-triggered tasks can't specify a `capacity`; their capacity is always 1",
+            );
+        }
+
+        if !task.args.subscribe.is_empty() {
+            errors.push(
+                name.span(),
+                "time-triggered tasks can't subscribe to topics",
+            );
+        }
+    }
+    for task in app
+        .spawn_callers()
+        .flat_map(|(_, spawn)| spawn)
+        .chain(app.schedule_callers().flat_map(|(_, schedule)| schedule))
+    {
+        if app
+            .tasks
+            .get(task)
+            .map(|t| !t.args.slots.is_empty())
+            .unwrap_or(false)
+        {
+            errors.push(
+                task.span(),
+                "this task is released by the schedule table; it can't be spawned or scheduled",
+            );
+        }
+    }
+
+    // Check that the execution budgets have a handler and that the timer queue, which monitors
+    // them, can run at a higher priority than all tasks
+    let budgets = app.budgets();
+    for (name, wcet) in &budgets {
+        if app.tasks.get(*name).map(|t| t.is_async).unwrap_or(false) {
+            errors.push(
+                wcet.span(),
+                "async tasks can't specify a `wcet`; they yield while they `await`",
+            );
+        }
+
+        if app.overrun.is_none() {
+            errors.push(
+                wcet.span(),
+                "tasks with a `wcet` budget need an `#[overrun]` handler",
+            );
+        }
+    }
+    if let (Some(overrun), true) = (app.overrun.as_ref(), budgets.is_empty()) {
+        errors.push(overrun.name.span(), "no task specifies a `wcet` budget");
+    }
+    if !budgets.is_empty() {
+        if let Some(name) = app
+            .exceptions
+            .iter()
+            .map(|(name, e)| (name, e.args.priority))
+            .chain(app.interrupts.iter().map(|(name, i)| (name, i.args.priority)))
+            .chain(app.tasks.iter().map(|(name, t)| (name, t.args.priority)))
+            .find(|(_, priority)| *priority == u8::MAX)
+            .map(|(name, _)| name)
+        {
+            errors.push(
+                name.span(),
+                "this task runs at priority 255 so the overrun monitor can't preempt it",
+            );
+        }
+    }
+
+    // The heartbeat of a task is its completion; async tasks complete once per spawn but yield in
+    // between so they can't be monitored this way
+    let heartbeats = app.heartbeats();
+    for (name, heartbeat) in &heartbeats {
+        if app.tasks[*name].is_async {
+            errors.push(
+                heartbeat.span(),
+                "async tasks can't specify a `heartbeat`; they yield while they `await`",
+            );
+        }
+    }
+    if let (Some(stalled), true) = (app.stalled.as_ref(), heartbeats.is_empty()) {
+        errors.push(stalled.name.span(), "no task specifies a `heartbeat`");
+    }
+
+    // `spawn` is the only API that enforces the minimum inter-arrival time; the other ways to
+    // release a sporadic task would bypass it
+    for (name, task) in &app.tasks {
+        if let Some(min) = task.args.min_inter_arrival.as_ref() {
+            if !app.spawn_callers().any(|(_, spawn)| spawn.contains(name)) {
+                errors.push(
+                    min.span(),
+                    "this task is never spawned; `min_inter_arrival` only applies to `spawn`",
+                );
+            }
+
+            if app
+                .schedule_callers()
+                .any(|(_, schedule)| schedule.contains(name))
+            {
+                errors.push(
+                    min.span(),
+                    "tasks that specify a `min_inter_arrival` time can't be scheduled; \
+                     only `spawn` enforces it",
+                );
+            }
+
+            if !task.args.subscribe.is_empty() {
+                errors.push(
+                    min.span(),
+                    "tasks that specify a `min_inter_arrival` time can't subscribe to topics; \
+                     only `spawn` enforces it",
+                );
+            }
+        }
+    }
+
+    // Check that every context fits in the crash record, which identifies the running context with
+    // a non-zero `u8` (the timer queue handler is conservatively counted in)
+    #[cfg(feature = "crash-report")]
+    {
+        let ncontexts = 2
+            + app.post_init.iter().count()
+            + app.idle.iter().count()
+            + app.exceptions.len()
+            + app.interrupts.len()
+            + app.tasks.len();
+        if ncontexts > usize::from(u8::MAX) {
+            errors.push(
+                Span::call_site(),
+                format!(
+                    "{} contexts exceed the limit of the `crash-report` feature ({})",
+                    ncontexts,
+                    u8::MAX,
+                ),
+            );
+        }
+    }
+
+    // Check that the round-robin levels dispatch software tasks
+    for level in &app.args.round_robin {
+        if app
+            .tasks
+            .values()
+            .all(|t| u64::from(t.args.priority) != level.value())
+        {
+            errors.push(level.span(), "no software task runs at this priority level");
+        }
+    }
+
+    // Check that either all or none of the tasks at a priority level specify an `order` and that
+    // no two tasks at the same level have the same `order`
+    for (name, task) in &app.tasks {
+        let level = task.args.priority;
+
+        if let Some(order) = task.args.order.as_ref() {
+            if app
+                .args
+                .round_robin
+                .iter()
+                .any(|lit| lit.value() == u64::from(level))
+            {
+                errors.push(
+                    order.span(),
+                    "`order` can't be used on tasks dispatched in round-robin fashion",
+                );
+            }
+        }
+
+        for (other_name, other) in &app.tasks {
+            if other_name == name || other.args.priority != level {
+                continue;
+            }
+
+            match (task.args.order.as_ref(), other.args.order.as_ref()) {
+                (Some(order), Some(other_order)) => {
+                    if order.value() == other_order.value() {
+                        errors.push(
+                            order.span(),
+                            format!(
+                                "task `{}` has the same `order` and priority as this one",
+                                other_name
+                            ),
+                        );
+                    }
+                }
+                (None, Some(_)) => {
+                    errors.push(
+                        name.span(),
+                        format!(
+                            "this task must specify an `order` because other tasks with the same \
+                             priority do (e.g. `{}`)",
+                            other_name
+                        ),
+                    );
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Check that the priority levels bound to free interrupts dispatch software tasks and that no
+    // two free interrupts are bound to the same level
+    for (name, fi) in &app.free_interrupts {
+        if let Some(level) = fi.priority.as_ref() {
+            if app
+                .tasks
+                .values()
+                .all(|t| u64::from(t.args.priority) != level.value())
+            {
+                errors.push(level.span(), "no software task runs at this priority level");
+            }
+
+            for (other_name, other) in &app.free_interrupts {
+                if other_name != name
+                    && other
+                        .priority
+                        .as_ref()
+                        .map(|other| other.value() == level.value())
+                        .unwrap_or(false)
+                {
+                    errors.push(
+                        level.span(),
+                        format!(
+                            "interrupt `{}` already dispatches the tasks at this priority level",
+                            other_name
+                        ),
+                    );
+                }
+            }
+        }
     }
 
     // Check that free interrupts are not being used
-    for int in app.interrupts.keys() {
-        if app.free_interrupts.contains_key(int) {
-            return Err(parse::Error::new(
+    for (name, interrupt) in &app.interrupts {
+        let int = interrupt.args.binds(name);
+        if let Some(free) = app.free_interrupts.keys().find(|free| *free == int) {
+            errors.push(
                 int.span(),
                 "free interrupts (`extern { .. }`) can't be used as interrupt handlers",
-            ));
+            );
+            errors.note(
+                free.span(),
+                format!("`{}` is declared here as a free interrupt", free),
+            );
+        }
+    }
+
+    // Check that each exception / interrupt is bound to at most one handler
+    let mut vectors: Map<&Ident, Vec<&Ident>> = Map::default();
+    for (name, args) in app
+        .exceptions
+        .iter()
+        .map(|(name, exception)| (name, &exception.args))
+        .chain(
+            app.interrupts
+                .iter()
+                .map(|(name, interrupt)| (name, &interrupt.args)),
+        )
+    {
+        vectors
+            .entry(args.binds(name))
+            .or_insert_with(Vec::new)
+            .push(args.binds.as_ref().unwrap_or(name));
+    }
+    for (vector, handlers) in vectors {
+        if handlers.len() > 1 {
+            for handler in handlers {
+                errors.push(
+                    handler.span(),
+                    format!("`{}` is bound to more than one handler", vector),
+                );
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks that the memory pools are large enough; this needs the results of the analysis
+pub fn pools(app: &App, analysis: &Analysis) -> Result<(), Errors> {
+    let mut errors = Errors::default();
+
+    for (name, pool) in &app.pools {
+        let size = &analysis.pools[name];
+
+        for span in &size.opaque {
+            errors.push(
+                *span,
+                format!(
+                    "the number of `Box<{}>` blocks this type holds can't be counted; only \
+                     `Box<{0}>` and tuples, arrays and `Option`s of it are supported",
+                    name
+                ),
+            );
+        }
+
+        if let Some(lit) = pool.args.capacity.as_ref() {
+            if size.capacity < size.demand {
+                errors.push(
+                    lit.span(),
+                    format!(
+                        "the message queues and the resources that hold `Box<{}>` can hold up to \
+                         {} blocks; the capacity of this pool must be at least {}",
+                        name, size.demand, size.demand
+                    ),
+                );
+            }
         }
     }
 
-    Ok(())
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// Reports a reference to an item that doesn't exist, suggesting the closest declared name, if any
+fn undeclared<'a, I>(errors: &mut Errors, name: &Ident, message: &str, candidates: I)
+where
+    I: Iterator<Item = &'a Ident>,
+{
+    let name_s = name.to_string();
+
+    // names that are too different are not worth suggesting
+    let max = cmp::max(1, name_s.len() / 3);
+    let suggestion = candidates
+        .map(|candidate| (distance(&name_s, &candidate.to_string()), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, candidate)| (*distance, candidate.to_string()));
+
+    if let Some((_, candidate)) = suggestion {
+        errors.push(
+            name.span(),
+            format!("{}; did you mean `{}`?", message, candidate),
+        );
+        errors.note(
+            candidate.span(),
+            format!("`{}` is declared here", candidate),
+        );
+    } else {
+        errors.push(name.span(), message);
+    }
+}
+
+// Levenshtein distance between `a` and `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = cmp::min(cmp::min(prev[j + 1] + 1, curr[j] + 1), prev[j] + cost);
+        }
+
+        prev.copy_from_slice(&curr);
+    }
+
+    prev[b.len()]
 }
@@ -1,29 +1,60 @@
+use std::cmp;
+
 use proc_macro::TokenStream;
-use std::{
-    collections::HashMap,
-    sync::atomic::{AtomicUsize, Ordering},
-    time::{SystemTime, UNIX_EPOCH},
-};
 
-use proc_macro2::Span;
-use quote::quote;
-use rand::{Rng, SeedableRng};
-use syn::{ArgCaptured, Ident, IntSuffix, LitInt};
+use proc_macro2::{Span, TokenTree};
+use quote::{quote, quote_spanned};
+use syn::{ArgCaptured, Attribute, Ident, Index, IntSuffix, LitInt};
 
-use analyze::{Analysis, Ownership};
-use syntax::{App, Idents, Static};
+use analyze::{Analysis, Ownership, Policy};
+use syntax::{App, Idents, Map, Set, Static, Topic};
 
 // NOTE to avoid polluting the user namespaces we map some identifiers to pseudo-hygienic names.
 // In some instances we also use the pseudo-hygienic names for safety, for example the user should
 // not modify the priority field of resources.
-type Aliases = HashMap<Ident, Ident>;
+type Aliases = Map<Ident, Ident>;
 
 pub struct Context {
+    // Task with a `min_inter_arrival` time -> Alias (`static`)
+    #[cfg(feature = "timer-queue")]
+    arrivals: Aliases,
+    // Alias (`fn`)
+    background: Ident,
+    // Alias (`static`)
+    background_level: Ident,
     // Alias
     #[cfg(feature = "timer-queue")]
     baseline: Ident,
+    // Task with a `wcet` budget -> Alias (`static mut`)
+    budgets: Aliases,
+    // Interrupt handler -> Alias (`static`)
+    controls: Aliases,
+    // Context (`init`, `idle`, task, ..) -> Id in the crash record
+    #[cfg(feature = "crash-report")]
+    crash_ids: Map<Ident, u8>,
+    // Alias (`static`)
+    #[cfg(feature = "crash-report")]
+    crash_contexts: Ident,
+    // Alias (`static`)
+    #[cfg(feature = "crash-report")]
+    crash_tasks: Ident,
+    // Alias (`fn`)
+    #[cfg(feature = "crash-report")]
+    crash_inspect: Ident,
+    // Alias (`static` scheduler of the levels dispatched earliest deadline first)
+    edf: Ident,
+    // Alias (`enum` of the tasks dispatched earliest deadline first)
+    edf_enum: Ident,
+    // Alias (`fn`)
+    edf_pend: Ident,
     // Dispatcher -> Alias (`enum`)
-    enums: HashMap<u8, Ident>,
+    enums: Map<u8, Ident>,
+    // Async task -> Alias (`static mut` pointer to the executor)
+    executors: Aliases,
+    // Async task -> Alias (`static`)
+    headers: Aliases,
+    // Task with a `heartbeat` -> Alias (`static`)
+    heartbeats: Aliases,
     // Task -> Alias (`static` / resource)
     free_queues: Aliases,
     // Alias (`fn`)
@@ -32,92 +63,289 @@ pub struct Context {
     init: Ident,
     // Task -> Alias (`static`)
     inputs: Aliases,
+    // Task with a `wcet` budget -> Alias (`fn` that runs the task under its budget)
+    monitors: Aliases,
+    // Task with a `wcet` budget -> Alias (timer queue `enum` variant)
+    overruns: Aliases,
+    // Alias (`fn`)
+    overrun: Ident,
+    // Pool -> Alias (`static mut`)
+    pool_nodes: Aliases,
+    // Pool -> Alias (`static`)
+    pool_stacks: Aliases,
+    // Alias (`fn`)
+    post_init: Ident,
+    // Alias (`fn`)
+    pre_init: Ident,
+    // Alias (`static`)
+    mode_requested: Ident,
     // Alias
     priority: Ident,
+    // Alias (`fn`)
+    stalled: Ident,
+    // Task with a `heartbeat` -> Alias (timer queue `enum` variant)
+    stalls: Aliases,
+    // Topic -> Alias (`fn`)
+    publish_fn: Aliases,
     // Dispatcher -> Alias (`static` / resource)
-    ready_queues: HashMap<u8, Ident>,
+    ready_queues: Map<u8, Ident>,
+    // Task -> Position of the task queue in the ready queues of its dispatcher (only for levels
+    // that are not dispatched in FIFO order)
+    ready_slots: Map<Ident, usize>,
+    // Dispatcher -> Alias (`static`)
+    #[cfg(feature = "introspect")]
+    ready_stats: Map<u8, Ident>,
     // For non-singletons this maps the resource name to its `static mut` variable name
     resources: Aliases,
+    // Resource -> Alias (`static mut` validity tag of a `#[retain]` resource)
+    retentions: Aliases,
     // Task -> Alias (`static`)
     #[cfg(feature = "timer-queue")]
     scheduleds: Aliases,
+    // Alias (`fn`)
+    sources: Ident,
+    // Alias (timer queue `enum` variant)
+    table: Ident,
+    // Alias (`static`)
+    table_release: Ident,
+    // Time-triggered task -> Alias (`static`)
+    table_slots: Aliases,
     // Task -> Alias (`fn`)
     spawn_fn: Aliases,
+    // Task -> Alias (`static`)
+    #[cfg(feature = "introspect")]
+    stats: Aliases,
     // Alias (`enum`)
     schedule_enum: Ident,
+    // Async task -> Alias (`fn`)
+    #[cfg(feature = "timer-queue")]
+    delay_fn: Aliases,
+    // Async task -> Alias (timer queue `enum` variant)
+    #[cfg(feature = "timer-queue")]
+    wakers: Aliases,
     // Task -> Alias (`fn`)
     schedule_fn: Aliases,
     tasks: Aliases,
     // Alias (`struct` / `static mut`)
     timer_queue: Ident,
+    // Alias (`static`)
+    #[cfg(feature = "introspect")]
+    timer_queue_stats: Ident,
+    // Alias (`static`)
+    #[cfg(feature = "introspect")]
+    symbols: Ident,
+    // Task (software or hardware) -> Alias (`static`)
+    #[cfg(feature = "wcet")]
+    times: Aliases,
+    // Pseudo-hygienic identifier generator
+    hygiene: Hygiene,
 }
 
-impl Default for Context {
-    fn default() -> Self {
+impl Context {
+    #[cfg_attr(not(feature = "crash-report"), allow(unused_variables))]
+    fn new(app: &App, analysis: &Analysis) -> Self {
+        let mut hygiene = Hygiene::new(app);
+
+        #[cfg(feature = "wcet")]
+        let times = app
+            .tasks
+            .keys()
+            .chain(app.interrupts.keys())
+            .chain(app.exceptions.keys())
+            .cloned()
+            .chain(if app.schedule_calls().next().is_some()
+                || (cfg!(feature = "timer-queue") && app.tasks.values().any(|t| t.is_async))
+                || !app.table().is_empty()
+                || !app.budgets().is_empty()
+            {
+                Some(Ident::new("SysTick", Span::call_site()))
+            } else {
+                None
+            })
+            .map(|name| {
+                let alias = hygiene.mk_ident(&format!("{}::WCET", name));
+                (name, alias)
+            })
+            .collect();
+
+        let mut budgets = Aliases::default();
+        let mut monitors = Aliases::default();
+        let mut overruns = Aliases::default();
+        for (task, _) in app.budgets() {
+            budgets.insert(task.clone(), hygiene.mk_ident(&format!("{}::BUDGET", task)));
+            monitors.insert(task.clone(), hygiene.mk_ident(&format!("{}::monitor", task)));
+            overruns.insert(task.clone(), hygiene.mk_ident(&format!("{}::OVERRUN", task)));
+        }
+
+        let mut heartbeats = Aliases::default();
+        let mut stalls = Aliases::default();
+        for (task, _) in app.heartbeats() {
+            heartbeats.insert(
+                task.clone(),
+                hygiene.mk_ident(&format!("{}::HEARTBEAT", task)),
+            );
+            stalls.insert(
+                task.clone(),
+                hygiene.mk_ident(&format!("{}::STALLED", task)),
+            );
+        }
+
+        let table_slots = app
+            .tasks
+            .iter()
+            .filter(|(_, task)| !task.args.slots.is_empty())
+            .map(|(name, _)| {
+                let alias = hygiene.mk_ident(&format!("{}::SLOTS", name));
+                (name.clone(), alias)
+            })
+            .collect();
+
+        #[cfg(feature = "timer-queue")]
+        let arrivals = app
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.args.min_inter_arrival.is_some())
+            .map(|(name, _)| {
+                let alias = hygiene.mk_ident(&format!("{}::ARRIVALS", name));
+                (name.clone(), alias)
+            })
+            .collect();
+
+        // NOTE id 0 means that no context is running
+        #[cfg(feature = "crash-report")]
+        let crash_ids = crash_contexts(app, analysis)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name, i as u8 + 1))
+            .collect();
+
         Context {
             #[cfg(feature = "timer-queue")]
-            baseline: mk_ident(),
-            enums: HashMap::new(),
-            free_queues: Aliases::new(),
-            idle: mk_ident(),
-            init: mk_ident(),
-            inputs: Aliases::new(),
-            priority: mk_ident(),
-            ready_queues: HashMap::new(),
-            resources: Aliases::new(),
+            arrivals,
+            #[cfg(feature = "timer-queue")]
+            baseline: hygiene.mk_ident("baseline"),
+            budgets,
+            background: hygiene.mk_ident("background"),
+            background_level: hygiene.mk_ident("P0::BACKGROUND"),
+            controls: Aliases::default(),
+            #[cfg(feature = "crash-report")]
+            crash_ids,
+            #[cfg(feature = "crash-report")]
+            crash_contexts: hygiene.mk_ident("CRASH::CONTEXTS"),
+            #[cfg(feature = "crash-report")]
+            crash_tasks: hygiene.mk_ident("CRASH::TASKS"),
+            #[cfg(feature = "crash-report")]
+            crash_inspect: hygiene.mk_ident("crash::inspect"),
+            edf: hygiene.mk_ident("EDF_QUEUE"),
+            edf_enum: hygiene.mk_ident("EDF::T"),
+            edf_pend: hygiene.mk_ident("edf::pend"),
+            enums: Map::default(),
+            executors: Aliases::default(),
+            headers: Aliases::default(),
+            heartbeats,
+            free_queues: Aliases::default(),
+            idle: hygiene.mk_ident("idle"),
+            init: hygiene.mk_ident("init"),
+            inputs: Aliases::default(),
+            monitors,
+            overruns,
+            overrun: hygiene.mk_ident("overrun"),
+            pool_nodes: Aliases::default(),
+            pool_stacks: Aliases::default(),
+            post_init: hygiene.mk_ident("post_init"),
+            pre_init: hygiene.mk_ident("pre_init"),
+            mode_requested: hygiene.mk_ident("MODE::REQUESTED"),
+            priority: hygiene.mk_ident("priority"),
+            stalled: hygiene.mk_ident("stalled"),
+            stalls,
+            publish_fn: Aliases::default(),
+            ready_queues: Map::default(),
+            ready_slots: Map::default(),
+            #[cfg(feature = "introspect")]
+            ready_stats: Map::default(),
+            resources: Aliases::default(),
+            retentions: Aliases::default(),
             #[cfg(feature = "timer-queue")]
-            scheduleds: Aliases::new(),
-            spawn_fn: Aliases::new(),
-            schedule_enum: mk_ident(),
-            schedule_fn: Aliases::new(),
-            tasks: Aliases::new(),
-            timer_queue: mk_ident(),
+            scheduleds: Aliases::default(),
+            sources: hygiene.mk_ident("sources"),
+            table: hygiene.mk_ident("TABLE"),
+            table_release: hygiene.mk_ident("TABLE::RELEASE"),
+            table_slots,
+            spawn_fn: Aliases::default(),
+            #[cfg(feature = "introspect")]
+            stats: Aliases::default(),
+            schedule_enum: hygiene.mk_ident("schedule_enum"),
+            #[cfg(feature = "timer-queue")]
+            delay_fn: Aliases::default(),
+            #[cfg(feature = "timer-queue")]
+            wakers: Aliases::default(),
+            schedule_fn: Aliases::default(),
+            tasks: Aliases::default(),
+            timer_queue: hygiene.mk_ident("timer_queue"),
+            #[cfg(feature = "introspect")]
+            timer_queue_stats: hygiene.mk_ident("TIMER_QUEUE::STATS"),
+            #[cfg(feature = "introspect")]
+            symbols: hygiene.mk_ident("RTFM_INTROSPECT"),
+            #[cfg(feature = "wcet")]
+            times,
+            hygiene,
         }
     }
 }
 
 pub fn app(app: &App, analysis: &Analysis) -> TokenStream {
-    let mut ctxt = Context::default();
+    let mut ctxt = Context::new(app, analysis);
 
     let device = &app.args.device;
 
     let resources = resources(&mut ctxt, &app, analysis);
 
+    let pools = pools(&mut ctxt, &app, analysis);
+
+    let controls = controls(&mut ctxt, &app);
+
+    let modes = modes(&ctxt, &app);
+
+    let budgets = budgets(&mut ctxt, &app);
+
+    let heartbeats = heartbeats(&mut ctxt, &app);
+
     let tasks = tasks(&mut ctxt, &app, analysis);
 
+    let dead_tasks = dead_tasks(&mut ctxt, &app, analysis);
+
     let (dispatchers_data, dispatchers) = dispatchers(&mut ctxt, &app, analysis);
 
     let init_fn = init(&mut ctxt, &app, analysis);
-    let init_arg = if cfg!(feature = "timer-queue") {
-        quote!(rtfm::Peripherals {
-            CBP: p.CBP,
-            CPUID: p.CPUID,
-            DCB: &mut p.DCB,
-            FPB: p.FPB,
-            FPU: p.FPU,
-            ITM: p.ITM,
-            MPU: p.MPU,
-            SCB: &mut p.SCB,
-            TPIU: p.TPIU,
-        })
+    let dcb = if cfg!(any(feature = "timer-queue", feature = "wcet")) {
+        quote!(DCB: &mut p.DCB,)
     } else {
-        quote!(rtfm::Peripherals {
-            CBP: p.CBP,
-            CPUID: p.CPUID,
-            DCB: p.DCB,
-            DWT: p.DWT,
-            FPB: p.FPB,
-            FPU: p.FPU,
-            ITM: p.ITM,
-            MPU: p.MPU,
-            SCB: &mut p.SCB,
-            SYST: p.SYST,
-            TPIU: p.TPIU,
-        })
+        quote!(DCB: p.DCB, DWT: p.DWT,)
+    };
+    let syst = if cfg!(feature = "timer-queue") {
+        quote!()
+    } else {
+        quote!(SYST: p.SYST,)
     };
+    let init_arg = quote!(rtfm::Peripherals {
+        CBP: p.CBP,
+        CPUID: p.CPUID,
+        #dcb
+        FPB: p.FPB,
+        FPU: p.FPU,
+        ITM: p.ITM,
+        MPU: p.MPU,
+        SCB: &mut p.SCB,
+        #syst
+        TPIU: p.TPIU,
+    });
 
     let post_init = post_init(&ctxt, &app, analysis);
 
+    let pre_init_fn = user_pre_init(&ctxt, &app);
+
+    let (post_init_fn, post_init_expr) = user_post_init(&mut ctxt, &app, analysis);
+
     let (idle_fn, idle_expr) = idle(&mut ctxt, &app, analysis);
 
     let exceptions = exceptions(&mut ctxt, app, analysis);
@@ -126,6 +354,10 @@ pub fn app(app: &App, analysis: &Analysis) -> TokenStream {
 
     let spawn = spawn(&mut ctxt, app, analysis);
 
+    let pend = pend(app);
+
+    let publish = publish(&ctxt, app, analysis);
+
     let schedule = match () {
         #[cfg(feature = "timer-queue")]
         () => schedule(&ctxt, app),
@@ -135,22 +367,118 @@ pub fn app(app: &App, analysis: &Analysis) -> TokenStream {
 
     let timer_queue = timer_queue(&ctxt, app, analysis);
 
-    let pre_init = pre_init(&ctxt, analysis);
+    let arrivals = match () {
+        #[cfg(feature = "timer-queue")]
+        () => arrivals(&ctxt, app),
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(),
+    };
+
+    let slots = match () {
+        #[cfg(feature = "timer-queue")]
+        () => slots(&ctxt),
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(),
+    };
+
+    let introspect = match () {
+        #[cfg(feature = "introspect")]
+        () => introspect(&ctxt, app, analysis),
+        #[cfg(not(feature = "introspect"))]
+        () => quote!(),
+    };
+
+    let wcet = match () {
+        #[cfg(feature = "wcet")]
+        () => wcet(&ctxt),
+        #[cfg(not(feature = "wcet"))]
+        () => quote!(),
+    };
+
+    let crash = match () {
+        #[cfg(feature = "crash-report")]
+        () => crash(&ctxt, app, analysis),
+        #[cfg(not(feature = "crash-report"))]
+        () => quote!(),
+    };
+
+    let sources = sources(&ctxt, app, analysis);
+
+    let pre_init = pre_init(&ctxt, app, analysis);
+
+    let executors = executors(&mut ctxt, app);
+
+    let timers = match () {
+        #[cfg(feature = "timer-queue")]
+        () => timers(&ctxt, app),
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(),
+    };
 
     let assertions = assertions(app, analysis);
 
+    let warnings = warnings(app, analysis);
+
+    let (cold_track, init_track, post_init_track, idle_track) = match () {
+        #[cfg(feature = "crash-report")]
+        () => (
+            quote!(rtfm::crash::set(0);),
+            enter(&ctxt, "init"),
+            if app.post_init.is_some() {
+                enter(&ctxt, "post_init")
+            } else {
+                quote!()
+            },
+            if app.idle.is_some() {
+                enter(&ctxt, "idle")
+            } else {
+                quote!(rtfm::crash::set(0);)
+            },
+        ),
+        #[cfg(not(feature = "crash-report"))]
+        () => (quote!(), quote!(), quote!(), quote!()),
+    };
+
     let init = &ctxt.init;
     quote!(
         #resources
 
+        #pools
+
+        #controls
+
+        #modes
+
+        #budgets
+
+        #heartbeats
+
         #spawn
 
+        #pend
+
+        #publish
+
         #timer_queue
 
         #schedule
 
+        #timers
+
         #dispatchers_data
 
+        #arrivals
+
+        #slots
+
+        #introspect
+
+        #wcet
+
+        #crash
+
+        #sources
+
         #(#exceptions)*
 
         #root_interrupts
@@ -167,26 +495,46 @@ pub fn app(app: &App, analysis: &Analysis) -> TokenStream {
 
         #(#tasks)*
 
+        #dead_tasks
+
+        #pre_init_fn
+
         #init_fn
 
+        #post_init_fn
+
         #idle_fn
 
         #[allow(unsafe_code)]
         #[rtfm::export::entry]
         #[doc(hidden)]
         fn main() -> ! {
+            #warnings
+
             #assertions
 
             rtfm::export::interrupt::disable();
 
+            #cold_track
+
             #pre_init
 
+            #executors
+
+            #init_track
+
             #init(#init_arg);
 
             #post_init
 
             unsafe { rtfm::export::interrupt::enable() }
 
+            #post_init_track
+
+            #post_init_expr
+
+            #idle_track
+
             #idle_expr
         }
     )
@@ -208,7 +556,7 @@ fn resources(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2:
                 pub static #mut_ #name: #ty = #expr;
             ));
 
-            let alias = mk_ident();
+            let alias = ctxt.hygiene.mk_ident(&format!("resources::{}", name));
             if let Some(Ownership::Shared { ceiling }) = analysis.ownerships.get(name) {
                 items.push(mk_resource(
                     ctxt,
@@ -223,27 +571,44 @@ fn resources(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2:
 
             ctxt.resources.insert(name.clone(), alias);
         } else {
-            let alias = mk_ident();
+            let alias = ctxt.hygiene.mk_ident(&format!("resources::{}", name));
             let symbol = format!("{}::{}", name, alias);
 
-            items.push(
-                expr.as_ref()
-                    .map(|expr| {
-                        quote!(
-                            #(#attrs)*
-                            #[export_name = #symbol]
-                            static mut #alias: #ty = #expr;
-                        )
-                    })
-                    .unwrap_or_else(|| {
-                        quote!(
-                            #(#attrs)*
-                            #[export_name = #symbol]
-                            static mut #alias: rtfm::export::MaybeUninit<#ty> =
-                                rtfm::export::MaybeUninit::uninitialized();
-                        )
-                    }),
-            );
+            if res.retain {
+                // NOTE the initializer is never loaded into memory; the value is restored, or
+                // re-initialized, before `init` runs (see `pre_init`)
+                let tag = ctxt.hygiene.mk_ident(&format!("{}::RETENTION", name));
+                items.push(quote!(
+                    #(#attrs)*
+                    #[export_name = #symbol]
+                    #[link_section = ".uninit.rtfm.retain"]
+                    static mut #alias: #ty = #expr;
+
+                    #[link_section = ".uninit.rtfm.retain"]
+                    static mut #tag: rtfm::export::Retention = rtfm::export::Retention::new();
+                ));
+
+                ctxt.retentions.insert(name.clone(), tag);
+            } else {
+                items.push(
+                    expr.as_ref()
+                        .map(|expr| {
+                            quote!(
+                                #(#attrs)*
+                                #[export_name = #symbol]
+                                static mut #alias: #ty = #expr;
+                            )
+                        })
+                        .unwrap_or_else(|| {
+                            quote!(
+                                #(#attrs)*
+                                #[export_name = #symbol]
+                                static mut #alias: rtfm::export::MaybeUninit<#ty> =
+                                    rtfm::export::MaybeUninit::uninitialized();
+                            )
+                        }),
+                );
+            }
 
             if let Some(Ownership::Shared { ceiling }) = analysis.ownerships.get(name) {
                 if res.mutability.is_some() {
@@ -280,1168 +645,3518 @@ fn resources(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2:
     quote!(#(#items)*)
 }
 
-fn init(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
-    let attrs = &app.init.attrs;
-    let locals = mk_locals(&app.init.statics, true);
-    let stmts = &app.init.stmts;
-    let assigns = app
-        .init
-        .assigns
-        .iter()
-        .map(|assign| {
-            if app
-                .resources
-                .get(&assign.left)
-                .map(|r| r.expr.is_none())
-                .unwrap_or(false)
-            {
-                let alias = &ctxt.resources[&assign.left];
-                let expr = &assign.right;
-                quote!(unsafe { #alias.set(#expr); })
-            } else {
-                let left = &assign.left;
-                let right = &assign.right;
-                quote!(#left = #right;)
-            }
-        })
-        .collect::<Vec<_>>();
-
-    let prelude = prelude(
-        ctxt,
-        Kind::Init,
-        &app.init.args.resources,
-        &app.init.args.spawn,
-        &app.init.args.schedule,
-        app,
-        255,
-        analysis,
-    );
+fn pools(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut items = vec![];
 
-    let module = module(
-        ctxt,
-        Kind::Init,
-        !app.init.args.schedule.is_empty(),
-        !app.init.args.spawn.is_empty(),
-    );
+    for (name, pool) in &app.pools {
+        let nodes = ctxt.hygiene.mk_ident(&format!("{}::NODES", name));
+        let stack = ctxt.hygiene.mk_ident(&format!("{}::STACK", name));
 
-    #[cfg(feature = "timer-queue")]
-    let baseline = &ctxt.baseline;
-    let baseline_let = match () {
-        #[cfg(feature = "timer-queue")]
-        () => quote!(let #baseline = rtfm::Instant::artificial(0);),
+        let attrs = &pool.attrs;
+        let ty = &pool.ty;
+        let expr = &pool.expr;
+        let capacity = analysis.pools[name].capacity;
+        let exprs = (0..capacity).map(|_| quote!(rtfm::pool::Node::new(#expr)));
+        let nodes_symbol = format!("{}::NODES", name);
 
-        #[cfg(not(feature = "timer-queue"))]
-        () => quote!(),
-    };
+        items.push(quote!(
+            #(#attrs)*
+            #[allow(non_camel_case_types)]
+            pub struct #name {
+                _0: (),
+            }
 
-    let start_let = match () {
-        #[cfg(feature = "timer-queue")]
-        () => quote!(
-            #[allow(unused_variables)]
-            let start = #baseline;
-        ),
-        #[cfg(not(feature = "timer-queue"))]
-        () => quote!(),
-    };
+            unsafe impl rtfm::pool::Pool for #name {
+                type Data = #ty;
 
-    let device = &app.args.device;
-    let init = &ctxt.init;
-    let name = format!("init::{}", init);
-    quote!(
-        #module
+                #[inline(always)]
+                fn stack() -> &'static rtfm::pool::Stack<#ty> {
+                    &#stack
+                }
+            }
 
-        #(#attrs)*
-        #[export_name = #name]
-        fn #init(core: rtfm::Peripherals) {
-            #(#locals)*
+            #[allow(dead_code)]
+            impl #name {
+                /// Claims a block from this pool; returns `None` if all the blocks are in use
+                #[inline]
+                pub fn alloc() -> Option<rtfm::pool::Box<#name>> {
+                    <#name as rtfm::pool::Pool>::alloc()
+                }
+            }
 
-            #baseline_let
+            static #stack: rtfm::pool::Stack<#ty> = rtfm::pool::Stack::new();
 
-            #prelude
+            #[export_name = #nodes_symbol]
+            static mut #nodes: [rtfm::pool::Node<#ty>; #capacity] = [#(#exprs,)*];
+        ));
 
-            let device = unsafe { #device::Peripherals::steal() };
+        ctxt.pool_nodes.insert(name.clone(), nodes);
+        ctxt.pool_stacks.insert(name.clone(), stack);
+    }
 
-            #start_let
+    quote!(#(#items)*)
+}
 
-            #(#stmts)*
+fn controls(ctxt: &mut Context, app: &App) -> proc_macro2::TokenStream {
+    let mut items = vec![];
 
-            #(#assigns)*
+    for name in app.controls() {
+        if ctxt.controls.contains_key(name) {
+            continue;
         }
-    )
-}
-
-fn post_init(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
-    let mut exprs = vec![];
 
-    // TODO turn the assertions that check that the priority is not larger than what's supported by
-    // the device into compile errors
-    let device = &app.args.device;
-    let nvic_prio_bits = quote!(#device::NVIC_PRIO_BITS);
-    for (name, interrupt) in &app.interrupts {
-        let priority = interrupt.args.priority;
-        exprs.push(quote!(p.NVIC.enable(#device::Interrupt::#name)));
-        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
-        exprs.push(quote!(p.NVIC.set_priority(
-            #device::Interrupt::#name,
-            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
-        )));
-    }
+        let alias = ctxt.hygiene.mk_ident(&format!("{}::CONTROL", name));
+        let enabled = app.interrupts[name].args.is_enabled();
 
-    for (name, exception) in &app.exceptions {
-        let priority = exception.args.priority;
-        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
-        exprs.push(quote!(p.SCB.set_priority(
-            rtfm::export::SystemHandler::#name,
-            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
-        )));
-    }
+        items.push(quote!(
+            static #alias: rtfm::export::ControlState = rtfm::export::ControlState::new(#enabled);
+        ));
 
-    if !analysis.timer_queue.tasks.is_empty() {
-        let priority = analysis.timer_queue.priority;
-        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
-        exprs.push(quote!(p.SCB.set_priority(
-            rtfm::export::SystemHandler::SysTick,
-            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
-        )));
+        ctxt.controls.insert(name.clone(), alias);
     }
 
-    for (priority, dispatcher) in &analysis.dispatchers {
-        let name = &dispatcher.interrupt;
-        exprs.push(quote!(p.NVIC.enable(#device::Interrupt::#name)));
-        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
-        exprs.push(quote!(p.NVIC.set_priority(
-            #device::Interrupt::#name,
-            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
-        )));
-    }
+    quote!(#(#items)*)
+}
 
-    if app.idle.is_none() {
-        // Set SLEEPONEXIT bit to enter sleep mode when returning from ISR
-        exprs.push(quote!(p.SCB.scr.modify(|r| r | 1 << 1)));
+fn modes(ctxt: &Context, app: &App) -> proc_macro2::TokenStream {
+    if app.args.modes.is_empty() {
+        return quote!();
     }
 
-    // Enable and start the system timer
-    if !analysis.timer_queue.tasks.is_empty() {
-        let tq = &ctxt.timer_queue;
-        exprs.push(quote!(#tq.get_mut().syst.set_clock_source(rtfm::export::SystClkSource::Core)));
-        exprs.push(quote!(#tq.get_mut().syst.enable_counter()));
-    }
+    let modes = &app.args.modes;
+    let requested = &ctxt.mode_requested;
+    quote!(
+        /// Operating modes
+        #[allow(dead_code)]
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        pub enum Mode {
+            #(#modes,)*
+        }
 
-    // Enable cycle counter
-    if cfg!(feature = "timer-queue") {
-        exprs.push(quote!(p.DCB.enable_trace()));
-        exprs.push(quote!(p.DWT.enable_cycle_counter()));
-    }
+        /// Switches between the operating modes
+        #[derive(Clone, Copy)]
+        pub struct ModeSwitch {
+            #[doc(hidden)]
+            pub _0: (),
+        }
 
-    if exprs.is_empty() {
+        impl ModeSwitch {
+            /// Requests a switch to the given `mode`
+            ///
+            /// The current mode completes its major frame; the tasks of the new mode are released
+            /// from the start of the next one. If several switches are requested within the same
+            /// frame the last one wins
+            #[allow(dead_code)]
+            pub fn switch(&self, mode: Mode) {
+                #requested.store(mode as u8, core::sync::atomic::Ordering::Relaxed)
+            }
+        }
+
+        static #requested: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+    )
+}
+
+// Any context can switch the operating mode; it's a single store that needs no locking
+fn mode_switch(app: &App) -> proc_macro2::TokenStream {
+    if app.args.modes.is_empty() {
         quote!()
     } else {
-        quote!(unsafe {
-            #(#exprs;)*
-        })
+        quote!(
+            #[allow(unused_variables)]
+            let mode = ModeSwitch { _0: () };
+        )
     }
 }
 
-/// This function creates creates a module for `init` / `idle` / a `task` (see `kind` argument)
-fn module(ctxt: &mut Context, kind: Kind, schedule: bool, spawn: bool) -> proc_macro2::TokenStream {
+fn budgets(ctxt: &mut Context, app: &App) -> proc_macro2::TokenStream {
+    let overrun = match app.overrun.as_ref() {
+        Some(overrun) => overrun,
+        None => return quote!(),
+    };
+
     let mut items = vec![];
 
-    let name = kind.ident();
-    let priority = &ctxt.priority;
+    let budgets = app.budgets();
+    let tasks = budgets.iter().map(|(task, _)| task);
+    items.push(quote!(
+        /// Tasks that have an execution budget
+        #[allow(dead_code)]
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        pub enum Task {
+            #(#tasks,)*
+        }
+    ));
 
-    if schedule {
+    let tq = &ctxt.timer_queue;
+    let enum_ = &ctxt.schedule_enum;
+    let stats_enqueue = match () {
+        #[cfg(feature = "introspect")]
+        () => {
+            let stats = &ctxt.timer_queue_stats;
+            quote!(#stats.enqueue();)
+        }
+        #[cfg(not(feature = "introspect"))]
+        () => quote!(),
+    };
+    for (task, wcet) in &budgets {
+        let budget = &ctxt.budgets[*task];
+        let monitor = &ctxt.monitors[*task];
+        let variant = &ctxt.overruns[*task];
+
+        // NOTE the timer queue runs at a higher priority than all tasks so a critical section is
+        // as cheap as a lock on it
         items.push(quote!(
-            /// Tasks that can be scheduled from this context
-            #[derive(Clone, Copy)]
-            pub struct Schedule<'a> {
-                #[doc(hidden)]
-                pub #priority: &'a core::cell::Cell<u8>,
+            static mut #budget: rtfm::export::Budget = rtfm::export::Budget::new();
+
+            #[allow(unsafe_code)]
+            #[inline(always)]
+            fn #monitor<F>(f: F)
+            where
+                F: FnOnce(),
+            {
+                rtfm::export::interrupt::free(|_| unsafe {
+                    let instant = rtfm::Instant::now() + rtfm::U32Ext::cycles(#wcet);
+                    if #budget.start(instant) {
+                        #tq.get_mut().enqueue_unchecked(rtfm::export::NotReady {
+                            instant,
+                            index: 0,
+                            task: #enum_::#variant,
+                        });
+                        #stats_enqueue
+                    }
+                });
+
+                f();
+
+                rtfm::export::interrupt::free(|_| unsafe { #budget.stop() });
             }
         ));
     }
 
-    if spawn {
-        if kind.is_idle() {
-            items.push(quote!(
-                /// Tasks that can be spawned from this context
-                #[derive(Clone, Copy)]
-                pub struct Spawn<'a> {
-                    #[doc(hidden)]
-                    pub #priority: &'a core::cell::Cell<u8>,
-                }
-            ));
-        } else {
-            let baseline_field = match () {
-                #[cfg(feature = "timer-queue")]
-                () => {
-                    let baseline = &ctxt.baseline;
-                    quote!(
-                        #[doc(hidden)]
-                        pub #baseline: rtfm::Instant,
-                    )
-                }
-                #[cfg(not(feature = "timer-queue"))]
-                () => quote!(),
-            };
+    let attrs = &overrun.attrs;
+    let locals = mk_locals(&overrun.statics, false);
+    let stmts = &overrun.stmts;
+    let mode = mode_switch(app);
+    let alias = &ctxt.overrun;
+    let task = ctxt.hygiene.mk_ident("overrun::task");
+    let name = format!("overrun::{}", alias);
+    items.push(quote!(
+        #(#attrs)*
+        #[export_name = #name]
+        fn #alias(#task: Task) {
+            #(#locals)*
 
-            items.push(quote!(
-                /// Tasks that can be spawned from this context
-                #[derive(Clone, Copy)]
-                pub struct Spawn<'a> {
-                    #baseline_field
-                    #[doc(hidden)]
-                    pub #priority: &'a core::cell::Cell<u8>,
-                }
-            ));
+            #[allow(unused_variables)]
+            let task = #task;
+
+            #mode
+
+            #(#stmts)*
         }
-    }
+    ));
 
-    if !items.is_empty() {
-        let doc = match kind {
-            Kind::Exception(_) => "Exception handler",
-            Kind::Idle => "Idle loop",
-            Kind::Init => "Initialization function",
-            Kind::Interrupt(_) => "Interrupt handler",
-            Kind::Task(_) => "Software task",
-        };
+    quote!(#(#items)*)
+}
 
-        quote!(
-            #[doc = #doc]
-            pub mod #name {
-                #(#items)*
-            }
-        )
-    } else {
-        quote!()
+fn heartbeats(ctxt: &mut Context, app: &App) -> proc_macro2::TokenStream {
+    let heartbeats = app.heartbeats();
+    if heartbeats.is_empty() {
+        return quote!();
     }
-}
 
-/// The prelude injects `resources`, `spawn`, `schedule` and `start` / `scheduled` (all values) into
-/// a function scope
-fn prelude(
-    ctxt: &mut Context,
-    kind: Kind,
-    resources: &Idents,
-    spawn: &Idents,
-    schedule: &Idents,
-    app: &App,
-    logical_prio: u8,
-    analysis: &Analysis,
-) -> proc_macro2::TokenStream {
     let mut items = vec![];
+    let mut alive = vec![];
+    for (task, period) in &heartbeats {
+        let heartbeat = &ctxt.heartbeats[*task];
 
-    let lt = if kind.runs_once() {
-        quote!('static)
-    } else {
-        quote!('a)
-    };
+        items.push(quote!(
+            static #heartbeat: rtfm::export::Heartbeat = rtfm::export::Heartbeat::new(#period);
+        ));
 
-    let module = kind.ident();
+        alive.push(quote!(super::#heartbeat.is_alive()));
+    }
 
-    let priority = &ctxt.priority;
-    if !resources.is_empty() {
-        let mut defs = vec![];
-        let mut exprs = vec![];
+    items.push(quote!(
+        /// Heartbeats of the software tasks
+        pub mod heartbeats {
+            /// Returns `true` if every task with a `heartbeat` has completed an instance within
+            /// its heartbeat
+            pub fn healthy() -> bool {
+                #(#alive)&&*
+            }
+        }
+    ));
 
-        // NOTE This field is just to avoid unused type parameter errors around `'a`
-        defs.push(quote!(#[allow(dead_code)] #priority: &'a core::cell::Cell<u8>));
-        exprs.push(quote!(#priority));
+    if let Some(stalled) = app.stalled.as_ref() {
+        let tasks = heartbeats.iter().map(|(task, _)| task);
+        items.push(quote!(
+            /// Tasks that have a heartbeat
+            #[allow(dead_code)]
+            #[allow(non_camel_case_types)]
+            #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+            pub enum Heartbeat {
+                #(#tasks,)*
+            }
+        ));
 
-        let mut may_call_lock = false;
-        let mut needs_unsafe = false;
-        for name in resources {
-            let res = &app.resources[name];
-            let initialized = res.expr.is_some();
-            let singleton = res.singleton;
-            let mut_ = res.mutability;
-            let ty = &res.ty;
+        let attrs = &stalled.attrs;
+        let locals = mk_locals(&stalled.statics, false);
+        let stmts = &stalled.stmts;
+        let mode = mode_switch(app);
+        let alias = &ctxt.stalled;
+        let task = ctxt.hygiene.mk_ident("stalled::task");
+        let name = format!("stalled::{}", alias);
+        items.push(quote!(
+            #(#attrs)*
+            #[export_name = #name]
+            fn #alias(#task: Heartbeat) {
+                #(#locals)*
 
-            if kind.is_init() {
-                let mut force_mut = false;
-                if !analysis.ownerships.contains_key(name) {
-                    // owned by Init
-                    if singleton {
-                        needs_unsafe = true;
-                        defs.push(quote!(#name: #name));
-                        exprs.push(quote!(#name: <#name as owned_singleton::Singleton>::new()));
-                        continue;
-                    } else {
-                        defs.push(quote!(#name: &'static #mut_ #ty));
-                    }
-                } else {
-                    // owned by someone else
-                    if singleton {
-                        needs_unsafe = true;
-                        defs.push(quote!(#name: &'a mut #name));
-                        exprs
-                            .push(quote!(#name: &mut <#name as owned_singleton::Singleton>::new()));
-                        continue;
-                    } else {
-                        force_mut = true;
-                        defs.push(quote!(#name: &'a mut #ty));
-                    }
-                }
+                #[allow(unused_variables)]
+                let task = #task;
 
-                let alias = &ctxt.resources[name];
-                // Resources assigned to init are always const initialized
-                if force_mut {
-                    needs_unsafe = true;
-                    exprs.push(quote!(#name: &mut #alias));
-                } else {
-                    exprs.push(quote!(#name: &#mut_ #alias));
-                }
+                #mode
+
+                #(#stmts)*
+            }
+        ));
+    }
+
+    quote!(#(#items)*)
+}
+
+fn init(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let attrs = &app.init.attrs;
+    let locals = mk_locals(&app.init.statics, true);
+    let stmts = &app.init.stmts;
+    let assigns = app
+        .init
+        .assigns
+        .iter()
+        .map(|assign| {
+            if app
+                .resources
+                .get(&assign.left)
+                .map(|r| r.expr.is_none())
+                .unwrap_or(false)
+            {
+                let alias = &ctxt.resources[&assign.left];
+                let expr = &assign.right;
+                quote!(unsafe { #alias.set(#expr); })
             } else {
-                let ownership = &analysis.ownerships[name];
+                let left = &assign.left;
+                let right = &assign.right;
+                quote!(#left = #right;)
+            }
+        })
+        .collect::<Vec<_>>();
 
-                if ownership.needs_lock(logical_prio) {
-                    may_call_lock = true;
-                    if singleton {
-                        if mut_.is_none() {
-                            needs_unsafe = true;
-                            defs.push(quote!(#name: &'a #name));
-                            exprs
-                                .push(quote!(#name: &<#name as owned_singleton::Singleton>::new()));
-                            continue;
-                        } else {
-                            // Generate a resource proxy
-                            defs.push(quote!(#name: resources::#name<'a>));
-                            exprs.push(quote!(#name: resources::#name { #priority }));
-                            continue;
-                        }
-                    } else {
-                        if mut_.is_none() {
-                            defs.push(quote!(#name: &'a #ty));
-                        } else {
-                            // Generate a resource proxy
-                            defs.push(quote!(#name: resources::#name<'a>));
-                            exprs.push(quote!(#name: resources::#name { #priority }));
-                            continue;
-                        }
-                    }
-                } else {
-                    if singleton {
-                        if kind.runs_once() {
-                            needs_unsafe = true;
-                            defs.push(quote!(#name: #name));
-                            exprs.push(quote!(#name: <#name as owned_singleton::Singleton>::new()));
+    let prelude = prelude(
+        ctxt,
+        Kind::Init,
+        &app.init.args.resources,
+        &app.init.args.spawn,
+        &app.init.args.schedule,
+        &app.init.args.controls,
+        &app.init.args.pend,
+        &app.init.args.publish,
+        app,
+        255,
+        analysis,
+    );
+
+    let module = module(
+        ctxt,
+        Kind::Init,
+        !app.init.args.schedule.is_empty(),
+        !app.init.args.spawn.is_empty(),
+        !app.init.args.pend.is_empty(),
+        !app.init.args.publish.is_empty(),
+        &app.init.args.controls,
+        false,
+    );
+
+    #[cfg(feature = "timer-queue")]
+    let baseline = &ctxt.baseline;
+    let baseline_let = match () {
+        #[cfg(feature = "timer-queue")]
+        () => quote!(let #baseline = rtfm::Instant::artificial(0);),
+
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(),
+    };
+
+    let start_let = match () {
+        #[cfg(feature = "timer-queue")]
+        () => quote!(
+            #[allow(unused_variables)]
+            let start = #baseline;
+        ),
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(),
+    };
+
+    let crash_let = match () {
+        #[cfg(feature = "crash-report")]
+        () => {
+            let contexts = &ctxt.crash_contexts;
+            let tasks = &ctxt.crash_tasks;
+            quote!(
+                #[allow(unused_variables)]
+                let crash = unsafe { rtfm::crash::Report::take(&#contexts, &#tasks) };
+            )
+        }
+        #[cfg(not(feature = "crash-report"))]
+        () => quote!(),
+    };
+
+    // The device peripherals that are used as resources have already been moved out of
+    // `Peripherals`; mark them as moved so `init` can't access them through `device`
+    let moved = app
+        .resources
+        .iter()
+        .filter(|(_, res)| res.peripheral)
+        .map(|(name, _)| quote!(core::mem::drop(device.#name);))
+        .collect::<Vec<_>>();
+
+    let device = &app.args.device;
+    let init = &ctxt.init;
+    let name = format!("init::{}", init);
+    quote!(
+        #module
+
+        #(#attrs)*
+        #[export_name = #name]
+        fn #init(core: rtfm::Peripherals) {
+            #(#locals)*
+
+            #baseline_let
+
+            #prelude
+
+            let device = unsafe { #device::Peripherals::steal() };
+            #(#moved)*
+
+            #start_let
+
+            #crash_let
+
+            #(#stmts)*
+
+            #(#assigns)*
+        }
+    )
+}
+
+fn post_init(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut exprs = vec![];
+
+    // TODO turn the assertions that check that the priority is not larger than what's supported by
+    // the device into compile errors
+    let device = &app.args.device;
+    let nvic_prio_bits = quote!(#device::NVIC_PRIO_BITS);
+    for (handler, interrupt) in &app.interrupts {
+        let name = interrupt.args.binds(handler);
+        let priority = interrupt.args.priority;
+        if let Some(state) = ctxt.controls.get(handler) {
+            // `init` may have disabled the task through its `Control` handle
+            exprs.push(quote!(if #state.is_enabled() {
+                p.NVIC.enable(#device::Interrupt::#name)
+            }));
+        } else if interrupt.args.is_enabled() {
+            exprs.push(quote!(p.NVIC.enable(#device::Interrupt::#name)));
+        }
+        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
+        exprs.push(quote!(p.NVIC.set_priority(
+            #device::Interrupt::#name,
+            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
+        )));
+    }
+
+    for (name, exception) in &app.exceptions {
+        let name = exception.args.binds(name);
+        let priority = exception.args.priority;
+        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
+        exprs.push(quote!(p.SCB.set_priority(
+            rtfm::export::SystemHandler::#name,
+            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
+        )));
+    }
+
+    if analysis.timer_queue.is_used() {
+        let priority = analysis.timer_queue.priority;
+        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
+        exprs.push(quote!(p.SCB.set_priority(
+            rtfm::export::SystemHandler::SysTick,
+            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
+        )));
+    }
+
+    for (priority, dispatcher) in &analysis.dispatchers {
+        let name = match dispatcher.interrupt.as_ref() {
+            Some(name) => name,
+            // the background level is run from the idle loop
+            None => continue,
+        };
+        exprs.push(quote!(p.NVIC.enable(#device::Interrupt::#name)));
+        exprs.push(quote!(assert!(#priority <= (1 << #nvic_prio_bits))));
+        exprs.push(quote!(p.NVIC.set_priority(
+            #device::Interrupt::#name,
+            ((1 << #nvic_prio_bits) - #priority) << (8 - #nvic_prio_bits),
+        )));
+    }
+
+    if app.idle.is_none() && !analysis.dispatchers.contains_key(&0) {
+        // Set SLEEPONEXIT bit to enter sleep mode when returning from ISR
+        exprs.push(quote!(p.SCB.scr.modify(|r| r | 1 << 1)));
+    }
+
+    // Enable and start the system timer
+    if analysis.timer_queue.is_used() {
+        let tq = &ctxt.timer_queue;
+        exprs.push(quote!(#tq.get_mut().syst.set_clock_source(rtfm::export::SystClkSource::Core)));
+        exprs.push(quote!(#tq.get_mut().syst.enable_counter()));
+    }
+
+    // Arm the schedule table at the boundary of its first major frame, `Instant(0)` like
+    // `init.start`, so it starts in the mode requested by `init` (by default, the first one)
+    let entries = table_entries(app);
+    if !entries.is_empty() {
+        let tq = &ctxt.timer_queue;
+        let enum_ = &ctxt.schedule_enum;
+        let table = &ctxt.table;
+        let release = &ctxt.table_release;
+        let boundary = entries.len() as u8;
+
+        exprs.push(quote!(#release.set(rtfm::Instant::artificial(0))));
+        exprs.push(quote!(#tq.get_mut().enqueue_unchecked(rtfm::export::NotReady {
+            instant: *#release.get_ref(),
+            index: #boundary,
+            task: #enum_::#table,
+        })));
+
+        #[cfg(feature = "introspect")]
+        {
+            let stats = &ctxt.timer_queue_stats;
+            exprs.push(quote!(#stats.enqueue()));
+        }
+    }
+
+    // Arm the first check of each heartbeat one heartbeat after the start of the application
+    for (task, period) in app.heartbeats() {
+        let tq = &ctxt.timer_queue;
+        let enum_ = &ctxt.schedule_enum;
+        let variant = &ctxt.stalls[task];
+
+        exprs.push(
+            quote!(#tq.get_mut().enqueue_unchecked(rtfm::export::NotReady {
+                instant: rtfm::Instant::artificial(0) + rtfm::U32Ext::cycles(#period),
+                index: 0,
+                task: #enum_::#variant,
+            })),
+        );
+
+        #[cfg(feature = "introspect")]
+        {
+            let stats = &ctxt.timer_queue_stats;
+            exprs.push(quote!(#stats.enqueue()));
+        }
+    }
+
+    // Enable cycle counter
+    if cfg!(any(feature = "timer-queue", feature = "wcet")) {
+        exprs.push(quote!(p.DCB.enable_trace()));
+        exprs.push(quote!(p.DWT.enable_cycle_counter()));
+    }
+
+    if exprs.is_empty() {
+        quote!()
+    } else {
+        quote!(unsafe {
+            #(#exprs;)*
+        })
+    }
+}
+
+/// This function creates creates a module for `init` / `idle` / a `task` (see `kind` argument)
+fn module(
+    ctxt: &mut Context,
+    kind: Kind,
+    schedule: bool,
+    spawn: bool,
+    pend: bool,
+    publish: bool,
+    controls: &Idents,
+    timer: bool,
+) -> proc_macro2::TokenStream {
+    let mut items = vec![];
+
+    let name = kind.ident();
+    let priority = &ctxt.priority;
+
+    if timer {
+        items.push(quote!(
+            /// Timer of this async task
+            #[derive(Clone, Copy)]
+            pub struct Timer<'a> {
+                #[doc(hidden)]
+                pub #priority: &'a core::cell::Cell<u8>,
+            }
+        ));
+    }
+
+    if schedule {
+        items.push(quote!(
+            /// Tasks that can be scheduled from this context
+            #[derive(Clone, Copy)]
+            pub struct Schedule<'a> {
+                #[doc(hidden)]
+                pub #priority: &'a core::cell::Cell<u8>,
+            }
+        ));
+    }
+
+    // the messages sent from `idle` / `post_init` are timestamped with `Instant::now()`; the
+    // other contexts forward their baseline
+    let baseline_field = if kind.is_background() {
+        quote!()
+    } else {
+        match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let baseline = &ctxt.baseline;
+                quote!(
+                    #[doc(hidden)]
+                    pub #baseline: rtfm::Instant,
+                )
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        }
+    };
+
+    if spawn {
+        items.push(quote!(
+            /// Tasks that can be spawned from this context
+            #[derive(Clone, Copy)]
+            pub struct Spawn<'a> {
+                #baseline_field
+                #[doc(hidden)]
+                pub #priority: &'a core::cell::Cell<u8>,
+            }
+        ));
+    }
+
+    if publish {
+        items.push(quote!(
+            /// Topics that can be published to from this context
+            #[derive(Clone, Copy)]
+            pub struct Publish<'a> {
+                #baseline_field
+                #[doc(hidden)]
+                pub #priority: &'a core::cell::Cell<u8>,
+            }
+        ));
+    }
+
+    if pend {
+        items.push(quote!(
+            /// Interrupt handlers that can be pended from this context
+            #[derive(Clone, Copy)]
+            pub struct Pend {
+                #[doc(hidden)]
+                pub _0: (),
+            }
+        ));
+    }
+
+    if !controls.is_empty() {
+        items.push(quote!(
+            /// Interrupt handlers that can be controlled from this context
+            #[allow(non_snake_case)]
+            pub struct Controls {
+                #(pub #controls: rtfm::Control,)*
+            }
+        ));
+    }
+
+    if !items.is_empty() {
+        let doc = match kind {
+            Kind::Exception(_) => "Exception handler",
+            Kind::Idle => "Idle loop",
+            Kind::Init => "Initialization function",
+            Kind::PostInit => "Post-initialization function",
+            Kind::Interrupt(_) => "Interrupt handler",
+            Kind::Task(_) => "Software task",
+        };
+
+        quote!(
+            #[doc = #doc]
+            pub mod #name {
+                #(#items)*
+            }
+        )
+    } else {
+        quote!()
+    }
+}
+
+/// The prelude injects `resources`, `spawn`, `schedule`, `mode` and `start` / `scheduled` (all
+/// values) into a function scope
+fn prelude(
+    ctxt: &mut Context,
+    kind: Kind,
+    resources: &Idents,
+    spawn: &Idents,
+    schedule: &Idents,
+    controls: &Idents,
+    pend: &Idents,
+    publish: &Idents,
+    app: &App,
+    logical_prio: u8,
+    analysis: &Analysis,
+) -> proc_macro2::TokenStream {
+    let mut items = vec![];
+
+    let lt = if kind.runs_once() {
+        quote!('static)
+    } else {
+        quote!('a)
+    };
+
+    let module = kind.ident();
+
+    let is_async = match kind {
+        Kind::Task(ref name) => app.task(name).is_async,
+        _ => false,
+    };
+
+    // the `spawn`, `schedule` and `publish` handles of a task that never runs don't send anything
+    // (see `dead_tasks`)
+    let is_dead = match kind {
+        Kind::Task(ref name) => app.dead_tasks.contains_key(name),
+        _ => false,
+    };
+
+    // `idle` runs the background tasks (priority 0) through its `resources`; the borrow checker
+    // rejects the call while `idle` holds a reference to any of its resources
+    let runs_background = kind == Kind::Idle && analysis.dispatchers.contains_key(&0);
+
+    let priority = &ctxt.priority;
+    if !resources.is_empty() || runs_background {
+        let mut defs = vec![];
+        let mut exprs = vec![];
+
+        // NOTE This field is just to avoid unused type parameter errors around `'a`
+        defs.push(quote!(#[allow(dead_code)] #priority: &'a core::cell::Cell<u8>));
+        exprs.push(quote!(#priority));
+
+        let mut may_call_lock = false;
+        let mut needs_unsafe = false;
+        for name in resources {
+            let res = &app.resources[name];
+            let initialized = res.expr.is_some();
+            let singleton = res.singleton;
+            let mut_ = res.mutability;
+            let ty = &res.ty;
+
+            if kind.is_init() {
+                let mut force_mut = false;
+                if !analysis.ownerships.contains_key(name) {
+                    // owned by Init
+                    if singleton {
+                        needs_unsafe = true;
+                        defs.push(quote!(#name: #name));
+                        exprs.push(quote!(#name: <#name as owned_singleton::Singleton>::new()));
+                        continue;
+                    } else {
+                        defs.push(quote!(#name: &'static #mut_ #ty));
+                    }
+                } else {
+                    // owned by someone else
+                    if singleton {
+                        needs_unsafe = true;
+                        defs.push(quote!(#name: &'a mut #name));
+                        exprs
+                            .push(quote!(#name: &mut <#name as owned_singleton::Singleton>::new()));
+                        continue;
+                    } else {
+                        force_mut = true;
+                        defs.push(quote!(#name: &'a mut #ty));
+                    }
+                }
+
+                let alias = &ctxt.resources[name];
+                // Resources assigned to init are always const initialized, except for device
+                // peripherals which are moved into their `static` variables before `init` runs
+                if !initialized {
+                    needs_unsafe = true;
+                    exprs.push(quote!(#name: #alias.get_mut()));
+                } else if force_mut {
+                    needs_unsafe = true;
+                    exprs.push(quote!(#name: &mut #alias));
+                } else {
+                    // `static mut` resources that only `init` uses, e.g. a `#[retain]` boot counter
+                    needs_unsafe |= mut_.is_some();
+                    exprs.push(quote!(#name: &#mut_ #alias));
+                }
+            } else {
+                let ownership = &analysis.ownerships[name];
+
+                // async tasks must `claim` shared resources even if they don't need a lock
+                let is_shared = match *ownership {
+                    Ownership::Shared { .. } => true,
+                    Ownership::Owned { .. } => false,
+                };
+
+                if ownership.needs_lock(logical_prio) || (is_async && is_shared) {
+                    may_call_lock = true;
+                    if singleton {
+                        if mut_.is_none() {
+                            needs_unsafe = true;
+                            defs.push(quote!(#name: &'a #name));
+                            exprs
+                                .push(quote!(#name: &<#name as owned_singleton::Singleton>::new()));
+                            continue;
+                        } else {
+                            // Generate a resource proxy
+                            defs.push(quote!(#name: resources::#name<'a>));
+                            exprs.push(quote!(#name: resources::#name { #priority }));
+                            continue;
+                        }
+                    } else {
+                        if mut_.is_none() {
+                            defs.push(quote!(#name: &'a #ty));
+                        } else {
+                            // Generate a resource proxy
+                            defs.push(quote!(#name: resources::#name<'a>));
+                            exprs.push(quote!(#name: resources::#name { #priority }));
+                            continue;
+                        }
+                    }
+                } else {
+                    // the background tasks run while `idle` is running; `idle` can't hold on to
+                    // the resources it shares with them
+                    let shared_with_background = runs_background
+                        && app
+                            .tasks
+                            .values()
+                            .any(|t| t.args.priority == 0 && t.args.resources.contains(name));
+                    let lt = if shared_with_background {
+                        quote!('a)
+                    } else {
+                        lt.clone()
+                    };
+
+                    if singleton {
+                        if kind.runs_once() && !shared_with_background {
+                            needs_unsafe = true;
+                            defs.push(quote!(#name: #name));
+                            exprs.push(quote!(#name: <#name as owned_singleton::Singleton>::new()));
+                        } else {
+                            needs_unsafe = true;
+                            defs.push(quote!(#name: &'a mut #name));
+                            exprs.push(
+                                quote!(#name: &mut <#name as owned_singleton::Singleton>::new()),
+                            );
+                        }
+                        continue;
+                    } else {
+                        defs.push(quote!(#name: &#lt #mut_ #ty));
+                    }
+                }
+
+                let alias = &ctxt.resources[name];
+                needs_unsafe = true;
+                if initialized {
+                    exprs.push(quote!(#name: &#mut_ #alias));
+                } else {
+                    let method = if mut_.is_some() {
+                        quote!(get_mut)
+                    } else {
+                        quote!(get_ref)
+                    };
+                    exprs.push(quote!(#name: #alias.#method() ));
+                }
+            }
+        }
+
+        let unsafety = if needs_unsafe {
+            Some(quote!(unsafe))
+        } else {
+            None
+        };
+        let run_background = if runs_background {
+            let background = &ctxt.background;
+            let background_level = &ctxt.background_level;
+            quote!(
+                impl<'a> Resources<'a> {
+                    /// Runs the pending background tasks (`priority = 0`)
+                    #[allow(unsafe_code)]
+                    fn run_background(&mut self) {
+                        unsafe { #background_level.run(#background) }
+                    }
+                }
+            )
+        } else {
+            quote!()
+        };
+
+        items.push(quote!(
+            #[allow(unsafe_code)]
+            #[allow(unused_mut)]
+            let mut resources = {
+                #[allow(non_snake_case)]
+                struct Resources<'a> { #(#defs,)* }
+
+                #run_background
+
+                #unsafety { Resources { #(#exprs,)* } }
+            };
+        ));
+
+        if may_call_lock {
+            items.push(quote!(
+                use rtfm::Mutex;
+            ));
+        }
+    }
+
+    if !spawn.is_empty() {
+        // Populate `spawn_fn`
+        for task in spawn {
+            if is_dead || ctxt.spawn_fn.contains_key(task) {
+                continue;
+            }
+
+            let alias = ctxt.hygiene.mk_ident(&format!("{}::spawn", task));
+            ctxt.spawn_fn.insert(task.clone(), alias);
+        }
+
+        if kind.is_background() {
+            items.push(quote!(
+                let spawn = #module::Spawn { #priority };
+            ));
+        } else {
+            let baseline_expr = match () {
+                #[cfg(feature = "timer-queue")]
+                () => {
+                    let baseline = &ctxt.baseline;
+                    quote!(#baseline)
+                }
+                #[cfg(not(feature = "timer-queue"))]
+                () => quote!(),
+            };
+            items.push(quote!(
+                let spawn = #module::Spawn { #priority, #baseline_expr };
+            ));
+        }
+    }
+
+    if !publish.is_empty() {
+        // Populate `publish_fn`
+        for topic in publish {
+            if is_dead || ctxt.publish_fn.contains_key(topic) {
+                continue;
+            }
+
+            let alias = ctxt.hygiene.mk_ident(&format!("{}::publish", topic));
+            ctxt.publish_fn.insert(topic.clone(), alias);
+        }
+
+        if kind.is_background() {
+            items.push(quote!(
+                let publish = #module::Publish { #priority };
+            ));
+        } else {
+            let baseline_expr = match () {
+                #[cfg(feature = "timer-queue")]
+                () => {
+                    let baseline = &ctxt.baseline;
+                    quote!(#baseline)
+                }
+                #[cfg(not(feature = "timer-queue"))]
+                () => quote!(),
+            };
+            items.push(quote!(
+                let publish = #module::Publish { #priority, #baseline_expr };
+            ));
+        }
+    }
+
+    if !schedule.is_empty() {
+        // Populate `schedule_fn`
+        for task in schedule {
+            if is_dead || ctxt.schedule_fn.contains_key(task) {
+                continue;
+            }
+
+            let alias = ctxt.hygiene.mk_ident(&format!("{}::schedule", task));
+            ctxt.schedule_fn.insert(task.clone(), alias);
+        }
+
+        items.push(quote!(
+            use rtfm::U32Ext;
+
+            let schedule = #module::Schedule { #priority };
+        ));
+    }
+
+    if !pend.is_empty() {
+        items.push(quote!(
+            let pend = #module::Pend { _0: () };
+        ));
+    }
+
+    if !controls.is_empty() {
+        let device = &app.args.device;
+        let exprs = controls.iter().map(|name| {
+            let vector = app.interrupts[name].args.binds(name);
+            let state = &ctxt.controls[name];
+
+            quote!(#name: rtfm::Control::new(#device::Interrupt::#vector, &#state))
+        });
+
+        items.push(quote!(
+            #[allow(unsafe_code)]
+            let controls = unsafe { #module::Controls { #(#exprs,)* } };
+        ));
+    }
+
+    if cfg!(feature = "timer-queue") && is_async {
+        if schedule.is_empty() {
+            items.push(quote!(use rtfm::U32Ext;));
+        }
+
+        items.push(quote!(
+            let timer = #module::Timer { #priority };
+        ));
+    }
+
+    let mode = mode_switch(app);
+
+    if items.is_empty() {
+        mode
+    } else {
+        quote!(
+            let ref #priority = core::cell::Cell::new(#logical_prio);
+
+            #(#items)*
+
+            #mode
+        )
+    }
+}
+
+// `#[pre_init]` runs before RAM is initialized so it gets no context
+fn user_pre_init(ctxt: &Context, app: &App) -> proc_macro2::TokenStream {
+    if let Some(pre_init) = app.pre_init.as_ref() {
+        let attrs = &pre_init.attrs;
+        let stmts = &pre_init.stmts;
+        let alias = &ctxt.pre_init;
+
+        // the crash record outlives resets; a panic in `#[pre_init]` must not be attributed to the
+        // task that was running before the reset (the task queues are not initialized yet)
+        let cold_track = match () {
+            #[cfg(feature = "crash-report")]
+            () => quote!(rtfm::crash::set(0);),
+            #[cfg(not(feature = "crash-report"))]
+            () => quote!(),
+        };
+
+        quote!(
+            #(#attrs)*
+            #[rtfm::export::pre_init]
+            unsafe fn #alias() {
+                #cold_track
+
+                #(#stmts)*
+            }
+        )
+    } else {
+        quote!()
+    }
+}
+
+fn user_post_init(
+    ctxt: &mut Context,
+    app: &App,
+    analysis: &Analysis,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if let Some(post_init) = app.post_init.as_ref() {
+        let attrs = &post_init.attrs;
+        let locals = mk_locals(&post_init.statics, true);
+        let stmts = &post_init.stmts;
+
+        let prelude = prelude(
+            ctxt,
+            Kind::PostInit,
+            &post_init.args.resources,
+            &post_init.args.spawn,
+            &post_init.args.schedule,
+            &post_init.args.controls,
+            &post_init.args.pend,
+            &post_init.args.publish,
+            app,
+            0,
+            analysis,
+        );
+
+        let module = module(
+            ctxt,
+            Kind::PostInit,
+            !post_init.args.schedule.is_empty(),
+            !post_init.args.spawn.is_empty(),
+            !post_init.args.pend.is_empty(),
+            !post_init.args.publish.is_empty(),
+            &post_init.args.controls,
+            false,
+        );
+
+        let alias = &ctxt.post_init;
+
+        let name = format!("post_init::{}", alias);
+        (
+            quote!(
+                #module
+
+                #(#attrs)*
+                #[export_name = #name]
+                fn #alias() {
+                    #(#locals)*
+
+                    #prelude
+
+                    #(#stmts)*
+                }),
+            quote!(#alias();),
+        )
+    } else {
+        (quote!(), quote!())
+    }
+}
+
+fn idle(
+    ctxt: &mut Context,
+    app: &App,
+    analysis: &Analysis,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if let Some(idle) = app.idle.as_ref() {
+        let attrs = &idle.attrs;
+        let locals = mk_locals(&idle.statics, true);
+        let stmts = &idle.stmts;
+
+        let prelude = prelude(
+            ctxt,
+            Kind::Idle,
+            &idle.args.resources,
+            &idle.args.spawn,
+            &idle.args.schedule,
+            &idle.args.controls,
+            &idle.args.pend,
+            &idle.args.publish,
+            app,
+            0,
+            analysis,
+        );
+
+        let module = module(
+            ctxt,
+            Kind::Idle,
+            !idle.args.schedule.is_empty(),
+            !idle.args.spawn.is_empty(),
+            !idle.args.pend.is_empty(),
+            !idle.args.publish.is_empty(),
+            &idle.args.controls,
+            false,
+        );
+
+        let idle = &ctxt.idle;
+
+        let name = format!("idle::{}", idle);
+        (
+            quote!(
+                #module
+
+                #(#attrs)*
+                #[export_name = #name]
+                fn #idle() -> ! {
+                    #(#locals)*
+
+                    #prelude
+
+                    #(#stmts)*
+                }),
+            quote!(#idle()),
+        )
+    } else if analysis.dispatchers.contains_key(&0) {
+        let background = &ctxt.background;
+        let background_level = &ctxt.background_level;
+        (
+            quote!(),
+            quote!(unsafe { #background_level.idle(#background) }),
+        )
+    } else {
+        (
+            quote!(),
+            quote!(loop {
+                rtfm::export::wfi();
+            }),
+        )
+    }
+}
+
+fn exceptions(ctxt: &mut Context, app: &App, analysis: &Analysis) -> Vec<proc_macro2::TokenStream> {
+    app.exceptions
+        .iter()
+        .map(|(ident, exception)| {
+            let attrs = &exception.attrs;
+            let statics = &exception.statics;
+            let stmts = &exception.stmts;
+
+            let prelude = prelude(
+                ctxt,
+                Kind::Exception(ident.clone()),
+                &exception.args.resources,
+                &exception.args.spawn,
+                &exception.args.schedule,
+                &exception.args.controls,
+                &exception.args.pend,
+                &exception.args.publish,
+                app,
+                exception.args.priority,
+                analysis,
+            );
+
+            let module = module(
+                ctxt,
+                Kind::Exception(ident.clone()),
+                !exception.args.schedule.is_empty(),
+                !exception.args.spawn.is_empty(),
+                !exception.args.pend.is_empty(),
+                !exception.args.publish.is_empty(),
+                &exception.args.controls,
+                false,
+            );
+
+            #[cfg(feature = "timer-queue")]
+            let baseline = &ctxt.baseline;
+            let baseline_let = match () {
+                #[cfg(feature = "timer-queue")]
+                () => quote!(let #baseline = rtfm::Instant::now();),
+                #[cfg(not(feature = "timer-queue"))]
+                () => quote!(),
+            };
+
+            let start_let = match () {
+                #[cfg(feature = "timer-queue")]
+                () => quote!(
+                        #[allow(unused_variables)]
+                        let start = #baseline;
+                    ),
+                #[cfg(not(feature = "timer-queue"))]
+                () => quote!(),
+            };
+
+            let body = monitor(
+                ctxt,
+                ident,
+                measure(
+                    ctxt,
+                    ident,
+                    quote!(rtfm::export::run(move || {
+                        #(#stmts)*
+                    })),
+                ),
+            );
+
+            let vector = exception.args.binds(ident);
+            quote!(
+                #module
+
+                #[rtfm::export::exception]
+                #[doc(hidden)]
+                #(#attrs)*
+                fn #vector() {
+                    #(#statics)*
+
+                    #baseline_let
+
+                    #prelude
+
+                    #start_let
+
+                    #body
+                })
+        })
+        .collect()
+}
+
+fn interrupts(
+    ctxt: &mut Context,
+    app: &App,
+    analysis: &Analysis,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut root = vec![];
+    let mut scoped = vec![];
+
+    for (ident, interrupt) in &app.interrupts {
+        let attrs = &interrupt.attrs;
+        let statics = &interrupt.statics;
+        let stmts = &interrupt.stmts;
+
+        let prelude = prelude(
+            ctxt,
+            Kind::Interrupt(ident.clone()),
+            &interrupt.args.resources,
+            &interrupt.args.spawn,
+            &interrupt.args.schedule,
+            &interrupt.args.controls,
+            &interrupt.args.pend,
+            &interrupt.args.publish,
+            app,
+            interrupt.args.priority,
+            analysis,
+        );
+
+        root.push(module(
+            ctxt,
+            Kind::Interrupt(ident.clone()),
+            !interrupt.args.schedule.is_empty(),
+            !interrupt.args.spawn.is_empty(),
+            !interrupt.args.pend.is_empty(),
+            !interrupt.args.publish.is_empty(),
+            &interrupt.args.controls,
+            false,
+        ));
+
+        #[cfg(feature = "timer-queue")]
+        let baseline = &ctxt.baseline;
+        let baseline_let = match () {
+            #[cfg(feature = "timer-queue")]
+            () => quote!(let #baseline = rtfm::Instant::now();),
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        let start_let = match () {
+            #[cfg(feature = "timer-queue")]
+            () => quote!(
+                #[allow(unused_variables)]
+                let start = #baseline;
+            ),
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        let body = monitor(
+            ctxt,
+            ident,
+            measure(
+                ctxt,
+                ident,
+                quote!(rtfm::export::run(move || {
+                    #(#stmts)*
+                })),
+            ),
+        );
+
+        let vector = interrupt.args.binds(ident);
+        scoped.push(quote!(
+            #[interrupt]
+            #(#attrs)*
+            fn #vector() {
+                #(#statics)*
+
+                #baseline_let
+
+                #prelude
+
+                #start_let
+
+                #body
+            }));
+    }
+
+    (quote!(#(#root)*), quote!(#(#scoped)*))
+}
+
+fn tasks(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut items = vec![];
+    for (name, task) in &app.tasks {
+        #[cfg(feature = "timer-queue")]
+        let scheduleds_alias = ctxt.hygiene.mk_ident(&format!("{}::SCHEDULED_TIMES", name));
+        let free_alias = ctxt.hygiene.mk_ident(&format!("{}::FREE_QUEUE", name));
+        let inputs_alias = ctxt.hygiene.mk_ident(&format!("{}::INPUTS", name));
+        let task_alias = ctxt.hygiene.mk_ident(&format!("{}::task", name));
+        #[cfg(feature = "introspect")]
+        let stats_alias = ctxt.hygiene.mk_ident(&format!("{}::STATS", name));
+
+        let attrs = &task.attrs;
+        let inputs = &task.inputs;
+        let locals = mk_locals(&task.statics, false);
+        let stmts = &task.stmts;
+
+        let prelude = prelude(
+            ctxt,
+            Kind::Task(name.clone()),
+            &task.args.resources,
+            &task.args.spawn,
+            &task.args.schedule,
+            &task.args.controls,
+            &task.args.pend,
+            &task.args.publish,
+            app,
+            task.args.priority,
+            analysis,
+        );
+
+        let ty = tuple_ty(inputs);
+
+        let capacity_lit = mk_capacity_literal(analysis.capacities[name]);
+        let capacity_ty = mk_typenum_capacity(analysis.capacities[name], true);
+
+        let resource = mk_resource(
+            ctxt,
+            &free_alias,
+            quote!(rtfm::export::FreeQueue<#capacity_ty>),
+            *analysis.free_queues.get(name).unwrap_or(&0),
+            quote!(#free_alias.get_mut()),
+            app,
+            None,
+        );
+
+        let scheduleds_static = match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let scheduleds_symbol = format!("{}::SCHEDULED_TIMES", name);
+
+                quote!(
+                    #[export_name = #scheduleds_symbol]
+                    static mut #scheduleds_alias:
+                    rtfm::export::MaybeUninit<[rtfm::Instant; #capacity_lit]> =
+                        rtfm::export::MaybeUninit::uninitialized();
+                )
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        let stats_static = match () {
+            #[cfg(feature = "introspect")]
+            () => mk_stats(&stats_alias, &format!("{}::STATS", name), analysis.capacities[name]),
+            #[cfg(not(feature = "introspect"))]
+            () => quote!(),
+        };
+
+        let scheduled_let = match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let baseline = &ctxt.baseline;
+                quote!(let scheduled = #baseline;)
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        let baseline_arg = match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let baseline = &ctxt.baseline;
+                quote!(#baseline: rtfm::Instant,)
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+        let task_symbol = format!("{}::{}", name, task_alias);
+        let inputs_symbol = format!("{}::INPUTS", name);
+        let free_symbol = format!("{}::FREE_QUEUE", name);
+
+        let task_fn = if task.is_async {
+            let executor_alias = ctxt.hygiene.mk_ident(&format!("{}::EXECUTOR", name));
+            let header_alias = ctxt.hygiene.mk_ident(&format!("{}::HEADER", name));
+            let pend = pend_dispatcher(ctxt, app, analysis, task.args.priority);
+
+            #[cfg(feature = "timer-queue")]
+            {
+                let waker = ctxt.hygiene.mk_ident(&format!("{}::WAKER", name));
+                let delay_fn = ctxt.hygiene.mk_ident(&format!("{}::delay", name));
+                ctxt.wakers.insert(name.clone(), waker);
+                ctxt.delay_fn.insert(name.clone(), delay_fn);
+            }
+
+            // NOTE this crate uses the 2015 edition; `async` must carry the span (edition) of the
+            // user code
+            let async_move = quote_spanned!(name.span()=> async move);
+
+            let executor_symbol = format!("{}::EXECUTOR", name);
+            let task = quote!(
+                #[export_name = #executor_symbol]
+                static mut #executor_alias: *mut () = core::ptr::null_mut();
+
+                static #header_alias: rtfm::export::Header = {
+                    fn pend() {
+                        #pend
+                    }
+
+                    rtfm::export::Header::new(pend)
+                };
+
+                #(#attrs)*
+                #[export_name = #task_symbol]
+                fn #task_alias(
+                    #baseline_arg
+                    #(#inputs,)*
+                ) -> impl core::future::Future<Output = ()> {
+                    #async_move {
+                        #(#locals)*
+
+                        #prelude
+
+                        #scheduled_let
+
+                        #(#stmts)*
+                    }
+                }
+            );
+
+            ctxt.executors.insert(name.clone(), executor_alias);
+            ctxt.headers.insert(name.clone(), header_alias);
+
+            task
+        } else {
+            quote!(
+                #(#attrs)*
+                #[export_name = #task_symbol]
+                fn #task_alias(#baseline_arg #(#inputs,)*) {
+                    #(#locals)*
+
+                    #prelude
+
+                    #scheduled_let
+
+                    #(#stmts)*
+                }
+            )
+        };
+
+        items.push(quote!(
+            // FIXME(MaybeUninit) MaybeUninit won't be necessary when core::mem::MaybeUninit
+            // stabilizes because heapless constructors will work in const context
+            #[export_name = #free_symbol]
+            static mut #free_alias: rtfm::export::MaybeUninit<
+                    rtfm::export::FreeQueue<#capacity_ty>
+                > = rtfm::export::MaybeUninit::uninitialized();
+
+            #resource
+
+            #[export_name = #inputs_symbol]
+            static mut #inputs_alias: rtfm::export::MaybeUninit<[#ty; #capacity_lit]> =
+                rtfm::export::MaybeUninit::uninitialized();
+
+            #scheduleds_static
+
+            #stats_static
+
+            #task_fn
+        ));
+
+        items.push(module(
+            ctxt,
+            Kind::Task(name.clone()),
+            !task.args.schedule.is_empty(),
+            !task.args.spawn.is_empty(),
+            !task.args.pend.is_empty(),
+            !task.args.publish.is_empty(),
+            &task.args.controls,
+            cfg!(feature = "timer-queue") && task.is_async,
+        ));
+
+        #[cfg(feature = "timer-queue")]
+        ctxt.scheduleds.insert(name.clone(), scheduleds_alias);
+        #[cfg(feature = "introspect")]
+        ctxt.stats.insert(name.clone(), stats_alias);
+        ctxt.free_queues.insert(name.clone(), free_alias);
+        ctxt.inputs.insert(name.clone(), inputs_alias);
+        ctxt.tasks.insert(name.clone(), task_alias);
+    }
+
+    quote!(#(#items)*)
+}
+
+// The tasks that never run get no queues and no dispatcher arm but their bodies are still type
+// checked. Their `spawn`, `schedule` and `publish` handles hand the message back
+fn dead_tasks(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut items = vec![];
+    for (name, task) in &app.dead_tasks {
+        let task_alias = ctxt.hygiene.mk_ident(&format!("{}::task", name));
+
+        let attrs = &task.attrs;
+        let inputs = &task.inputs;
+        let locals = mk_locals(&task.statics, false);
+        let stmts = &task.stmts;
+
+        let prelude = prelude(
+            ctxt,
+            Kind::Task(name.clone()),
+            &task.args.resources,
+            &task.args.spawn,
+            &task.args.schedule,
+            &task.args.controls,
+            &task.args.pend,
+            &task.args.publish,
+            app,
+            task.args.priority,
+            analysis,
+        );
+
+        let scheduled_let = match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let baseline = &ctxt.baseline;
+                quote!(let scheduled = #baseline;)
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        let baseline_arg = match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let baseline = &ctxt.baseline;
+                quote!(#baseline: rtfm::Instant,)
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        if task.is_async {
+            let async_move = quote_spanned!(name.span()=> async move);
+
+            items.push(quote!(
+                #(#attrs)*
+                #[allow(dead_code)]
+                fn #task_alias(
+                    #baseline_arg
+                    #(#inputs,)*
+                ) -> impl core::future::Future<Output = ()> {
+                    #async_move {
+                        #(#locals)*
+
+                        #prelude
+
+                        #scheduled_let
+
+                        #(#stmts)*
+                    }
+                }
+            ));
+        } else {
+            items.push(quote!(
+                #(#attrs)*
+                #[allow(dead_code)]
+                fn #task_alias(#baseline_arg #(#inputs,)*) {
+                    #(#locals)*
+
+                    #prelude
+
+                    #scheduled_let
+
+                    #(#stmts)*
+                }
+            ));
+        }
+
+        items.push(module(
+            ctxt,
+            Kind::Task(name.clone()),
+            !task.args.schedule.is_empty(),
+            !task.args.spawn.is_empty(),
+            !task.args.pend.is_empty(),
+            !task.args.publish.is_empty(),
+            &task.args.controls,
+            cfg!(feature = "timer-queue") && task.is_async,
+        ));
+
+        if !task.args.spawn.is_empty() {
+            let methods = task.args.spawn.iter().map(|callee| {
+                let inputs = &app.task(callee).inputs;
+                let pats = tuple_pat(inputs);
+                let error = spawn_error(app, callee);
+                let err = if app.task(callee).args.min_inter_arrival.is_some() {
+                    quote!(rtfm::ReleaseError::Full((#pats)))
+                } else {
+                    quote!((#pats))
+                };
+
+                quote!(
+                    #[inline]
+                    pub fn #callee(&self, #(#inputs,)*) -> Result<(), #error> {
+                        Err(#err)
+                    }
+                )
+            });
+
+            items.push(quote!(
+                impl<'a> #name::Spawn<'a> {
+                    #(#methods)*
+                }
+            ));
+        }
+
+        if !task.args.schedule.is_empty() {
+            let methods = task.args.schedule.iter().map(|callee| {
+                let inputs = &app.task(callee).inputs;
+                let ty = tuple_ty(inputs);
+                let pats = tuple_pat(inputs);
+
+                quote!(
+                    #[inline]
+                    pub fn #callee(
+                        &self,
+                        instant: rtfm::Instant,
+                        #(#inputs,)*
+                    ) -> Result<(), #ty> {
+                        let _ = instant;
+
+                        Err((#pats))
+                    }
+                )
+            });
+
+            items.push(quote!(
+                impl<'a> #name::Schedule<'a> {
+                    #(#methods)*
+                }
+            ));
+        }
+
+        if !task.args.publish.is_empty() {
+            let methods = task.args.publish.iter().map(|topic| {
+                let method = Topic::method(topic);
+
+                quote!(
+                    #[inline]
+                    pub fn #method(
+                        &self,
+                        message: #topic,
+                    ) -> Result<(), rtfm::PublishError<#topic>> {
+                        Err(rtfm::PublishError::new(message, &[], 0))
+                    }
+                )
+            });
+
+            items.push(quote!(
+                impl<'a> #name::Publish<'a> {
+                    #(#methods)*
+                }
+            ));
+        }
+
+        if cfg!(feature = "timer-queue") && task.is_async {
+            items.push(quote!(
+                impl<'a> #name::Timer<'a> {
+                    /// Returns a future that completes `duration` after the current time
+                    #[inline]
+                    pub fn delay(
+                        &self,
+                        duration: rtfm::Duration,
+                    ) -> rtfm::Delay<impl FnMut(rtfm::Instant) + 'a> {
+                        self.at(rtfm::Instant::now() + duration)
+                    }
+
+                    /// Returns a future that completes at the given `instant`
+                    #[inline]
+                    pub fn at(
+                        &self,
+                        instant: rtfm::Instant,
+                    ) -> rtfm::Delay<impl FnMut(rtfm::Instant) + 'a> {
+                        rtfm::Delay::new(instant, |_| {})
+                    }
+
+                    /// Returns a future that completes with the output of `future`, or with `None`
+                    /// if `duration` elapses first
+                    #[inline]
+                    pub fn timeout<F>(
+                        &self,
+                        duration: rtfm::Duration,
+                        future: F,
+                    ) -> rtfm::Timeout<F, impl FnMut(rtfm::Instant) + 'a>
+                    where
+                        F: core::future::Future,
+                    {
+                        rtfm::Timeout::new(future, self.delay(duration))
+                    }
+                }
+            ));
+        }
+    }
+
+    quote!(#(#items)*)
+}
+
+fn dispatchers(
+    ctxt: &mut Context,
+    app: &App,
+    analysis: &Analysis,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let mut data = vec![];
+    let mut dispatchers = vec![];
+
+    // The levels dispatched earliest deadline first share a single scheduler that decides which of
+    // their dispatchers must run next. It's only accessed from within global critical sections so
+    // it needs no ceiling
+    let edf_levels = analysis
+        .dispatchers
+        .iter()
+        .filter(|(_, dispatcher)| dispatcher.policy == Policy::Deadline)
+        .collect::<Vec<_>>();
+    let edf_tasks = edf_levels
+        .iter()
+        .flat_map(|(_, dispatcher)| dispatcher.tasks.iter())
+        .collect::<Vec<_>>();
+    if !edf_levels.is_empty() {
+        let edf = &ctxt.edf;
+        let edf_enum = &ctxt.edf_enum;
+        let edf_pend = &ctxt.edf_pend;
+        let capacity = mk_typenum_capacity(
+            edf_levels
+                .iter()
+                .map(|(_, dispatcher)| dispatcher.capacity)
+                .sum(),
+            false,
+        );
+        let arms = edf_levels.iter().map(|(level, _)| {
+            let pend = pend_dispatcher(ctxt, app, analysis, **level);
+
+            quote!(#level => #pend,)
+        });
+
+        let tasks = &edf_tasks;
+        data.push(quote!(
+            #[allow(dead_code)]
+            #[allow(non_camel_case_types)]
+            enum #edf_enum { #(#tasks,)* }
+
+            #[export_name = "EDF_QUEUE"]
+            static mut #edf: rtfm::export::MaybeUninit<rtfm::export::Edf<#edf_enum, #capacity>> =
+                rtfm::export::MaybeUninit::uninitialized();
+
+            unsafe fn #edf_pend(level: u8) {
+                match level {
+                    #(#arms)*
+                    _ => core::hint::unreachable_unchecked(),
+                }
+            }
+        ));
+    }
+
+    for (level, dispatcher) in &analysis.dispatchers {
+        let ready_alias = ctxt.hygiene.mk_ident(&format!("P{}::READY_QUEUE", level));
+        let enum_alias = if dispatcher.policy == Policy::Deadline {
+            ctxt.edf_enum.clone()
+        } else {
+            ctxt.hygiene.mk_ident(&format!("P{}::T", level))
+        };
+        #[cfg(feature = "introspect")]
+        let stats_alias = ctxt.hygiene.mk_ident(&format!("P{}::STATS", level));
+        let tasks = &dispatcher.tasks;
+
+        if dispatcher.policy != Policy::Deadline {
+            let symbol = format!("P{}::READY_QUEUE", level);
+            let e = quote!(rtfm::export);
+            let ty = if dispatcher.policy == Policy::Fifo {
+                let capacity = mk_typenum_capacity(dispatcher.capacity, true);
+                quote!(#e::ReadyQueue<#enum_alias, #capacity>)
+            } else {
+                // one queue of indices per task
+                let tys = tasks.iter().enumerate().map(|(i, task)| {
+                    ctxt.ready_slots.insert(task.clone(), i);
+
+                    let capacity = mk_typenum_capacity(analysis.capacities[task], true);
+                    quote!(#e::TaskQueue<#capacity>)
+                });
+
+                quote!((#(#tys,)*))
+            };
+            let ceiling = *analysis.ready_queues.get(&level).unwrap_or(&0);
+            let resource = mk_resource(
+                ctxt,
+                &ready_alias,
+                ty.clone(),
+                ceiling,
+                quote!(#ready_alias.get_mut()),
+                app,
+                None,
+            );
+            data.push(quote!(
+                #[allow(dead_code)]
+                #[allow(non_camel_case_types)]
+                enum #enum_alias { #(#tasks,)* }
+
+                #[export_name = #symbol]
+                static mut #ready_alias: #e::MaybeUninit<#ty> = #e::MaybeUninit::uninitialized();
+
+                #resource
+            ));
+        }
+
+        let stats_dequeue = match () {
+            #[cfg(feature = "introspect")]
+            () => {
+                data.push(mk_stats(
+                    &stats_alias,
+                    &format!("P{}::STATS", level),
+                    dispatcher.capacity,
+                ));
+
+                quote!(#stats_alias.dequeue();)
+            }
+            #[cfg(not(feature = "introspect"))]
+            () => quote!(),
+        };
+
+        // Runs one instance of each task; `index` is the slot that holds its inputs
+        let runs = dispatcher
+            .tasks
+            .iter()
+            .map(|task| {
+                let inputs = &ctxt.inputs[task];
+                let free = &ctxt.free_queues[task];
+                let pats = tuple_pat(&app.tasks[task].inputs);
+                let alias = &ctxt.tasks[task];
+
+                let task_stats_dequeue = match () {
+                    #[cfg(feature = "introspect")]
+                    () => {
+                        let stats = &ctxt.stats[task];
+                        quote!(#stats.dequeue();)
+                    }
+                    #[cfg(not(feature = "introspect"))]
+                    () => quote!(),
+                };
+
+                let baseline_let;
+                let call;
+                let args;
+                match () {
+                    #[cfg(feature = "timer-queue")]
+                    () => {
+                        let scheduleds = &ctxt.scheduleds[task];
+                        baseline_let = quote!(
+                            let baseline =
+                                ptr::read(#scheduleds.get_ref().get_unchecked(usize::from(index)));
+                        );
+                        call = quote!(#alias(baseline, #pats));
+                        args = quote!((baseline, input));
+                    }
+                    #[cfg(not(feature = "timer-queue"))]
+                    () => {
+                        baseline_let = quote!();
+                        call = quote!(#alias(#pats));
+                        args = quote!(input);
+                    }
+                };
+
+                if app.tasks[task].is_async {
+                    // The free slot is released when the future completes
+                    let constructor = async_constructor(ctxt, app, task);
+                    let executor = &ctxt.executors[task];
+                    let poll = async_poll(ctxt, task);
+
+                    quote!({
+                        #stats_dequeue
+                        #baseline_let
+                        let input = ptr::read(#inputs.get_ref().get_unchecked(usize::from(index)));
+                        #task_stats_dequeue
+                        let executor = rtfm::export::executor_ref(#constructor, #executor);
+                        executor.spawn(index, (#constructor)(#args));
+                        #poll
+                    })
+                } else {
+                    let call = monitor(ctxt, task, measure(ctxt, task, call));
+                    let call = heartbeat(ctxt, task, call);
+                    let call = slot_end(ctxt, task, call);
+
+                    quote!({
+                        #stats_dequeue
+                        #baseline_let
+                        let input = ptr::read(#inputs.get_ref().get_unchecked(usize::from(index)));
+                        #free.get_mut().split().0.enqueue_unchecked(index);
+                        #task_stats_dequeue
+                        let (#pats) = input;
+                        #call
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let body = match dispatcher.policy {
+            Policy::Fifo => {
+                let arms = tasks
+                    .iter()
+                    .zip(&runs)
+                    .map(|(task, run)| quote!(#enum_alias::#task => #run))
+                    .collect::<Vec<_>>();
+
+                quote!(
+                    while let Some((task, index)) = #ready_alias.get_mut().split().1.dequeue() {
+                        match task {
+                            #(#arms)*
+                        }
+                    }
+                )
+            }
+            Policy::Deadline => {
+                let edf = &ctxt.edf;
+                let edf_pend = &ctxt.edf_pend;
+                let mut arms = tasks
+                    .iter()
+                    .zip(&runs)
+                    .map(|(task, run)| quote!(#enum_alias::#task => #run))
+                    .collect::<Vec<_>>();
+                if tasks.len() < edf_tasks.len() {
+                    // `start` only hands out the instances of the tasks of this level
+                    arms.push(quote!(_ => core::hint::unreachable_unchecked(),));
+                }
+
+                // Run the pending instances of this level for as long as they have the earliest
+                // deadline; on completion pend the level of the next instance, which may be lower
+                // than this one
+                quote!(
+                    while let Some((ready, previous)) =
+                        rtfm::export::interrupt::free(|_| #edf.get_mut().start(#level))
+                    {
+                        let index = ready.index;
+                        match ready.task {
+                            #(#arms)*
+                        }
+
+                        if let Some(level) =
+                            rtfm::export::interrupt::free(|_| #edf.get_mut().complete(previous))
+                        {
+                            if level != #level {
+                                #edf_pend(level);
+                            }
+                        }
+                    }
+                )
+            }
+            Policy::SubPriority => {
+                // After running a task start over from the first queue; a task with lower `order`
+                // may have been spawned in the meantime
+                let checks = runs.iter().enumerate().map(|(i, run)| {
+                    let i = Index::from(i);
+                    quote!(
+                        if let Some(index) = #ready_alias.get_mut().#i.split().1.dequeue() {
+                            #run
+                            continue;
+                        }
+                    )
+                });
+
+                quote!(
+                    loop {
+                        #(#checks)*
+
+                        break;
+                    }
+                )
+            }
+            Policy::RoundRobin => {
+                let cursor = ctxt.hygiene.mk_ident(&format!("P{}::CURSOR", level));
+                data.push(quote!(
+                    static mut #cursor: u8 = 0;
+                ));
+
+                let ntasks = tasks.len() as u8;
+                let arms = runs.iter().enumerate().map(|(i, run)| {
+                    let lit = mk_capacity_literal(i as u8);
+                    let i = Index::from(i);
+                    quote!(
+                        #lit => if let Some(index) = #ready_alias.get_mut().#i.split().1.dequeue() {
+                            #run
+                            true
+                        } else {
+                            false
+                        },
+                    )
+                });
+
+                // Visit the queues in turn, one message at a time, until all of them are empty
+                quote!(
+                    let mut empty = 0;
+                    while empty < #ntasks {
+                        let current = #cursor;
+                        #cursor = if current + 1 == #ntasks { 0 } else { current + 1 };
+
+                        let ran = match current {
+                            #(#arms)*
+                            _ => core::hint::unreachable_unchecked(),
+                        };
+
+                        if ran {
+                            empty = 0;
+                        } else {
+                            empty += 1;
+                        }
+                    }
+                )
+            }
+        };
+
+        // Resume the async tasks that have been woken up
+        let resumes = tasks
+            .iter()
+            .filter(|task| app.tasks[*task].is_async)
+            .map(|task| {
+                let constructor = async_constructor(ctxt, app, task);
+                let executor = &ctxt.executors[task];
+                let header = &ctxt.headers[task];
+                let poll = async_poll(ctxt, task);
+
+                quote!(
+                    if #header.is_woken() {
+                        let executor = rtfm::export::executor_ref(#constructor, #executor);
+                        #poll
+                    }
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(interrupt) = dispatcher.interrupt.as_ref() {
+            let attrs = &dispatcher.attrs;
+            dispatchers.push(quote!(
+                #(#attrs)*
+                #[interrupt]
+                unsafe fn #interrupt() {
+                    use core::ptr;
+
+                    rtfm::export::run(|| {
+                        #(#resumes)*
+
+                        #body
+                    });
+                }
+            ));
+        } else {
+            // The background level is dispatched from the idle loop (see `idle`)
+            let background = &ctxt.background;
+            let background_level = &ctxt.background_level;
+            data.push(quote!(
+                #[export_name = "P0::BACKGROUND"]
+                static #background_level: rtfm::export::Background =
+                    rtfm::export::Background::new();
+
+                unsafe fn #background() {
+                    use core::ptr;
+
+                    #(#resumes)*
+
+                    #body
+                }
+            ));
+        }
+
+        if dispatcher.policy != Policy::Deadline {
+            ctxt.ready_queues.insert(*level, ready_alias);
+        }
+        #[cfg(feature = "introspect")]
+        ctxt.ready_stats.insert(*level, stats_alias);
+        ctxt.enums.insert(*level, enum_alias);
+    }
+
+    (quote!(#(#data)*), quote!(#(#dispatchers)*))
+}
+
+fn spawn(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut items = vec![];
+
+    // Generate `spawn` functions
+    let priority = &ctxt.priority;
+    #[cfg(feature = "timer-queue")]
+    let baseline = &ctxt.baseline;
+    for (task, alias) in &ctxt.spawn_fn {
+        let free = &ctxt.free_queues[task];
+        let (enqueue, pend) = make_ready(ctxt, app, analysis, task);
+        let inputs = &ctxt.inputs[task];
+        let free_dequeue = free_dequeue(ctxt, task);
+        let args = &app.tasks[task].inputs;
+        let pats = tuple_pat(args);
+
+        let scheduleds_write = match () {
+            #[cfg(feature = "timer-queue")]
+            () => {
+                let scheduleds = &ctxt.scheduleds[task];
+                quote!(
+                    ptr::write(
+                        #scheduleds.get_mut().get_unchecked_mut(usize::from(index)),
+                        #baseline,
+                    );
+                )
+            }
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
+        };
+
+        let release = quote!(
+            ptr::write(#inputs.get_mut().get_unchecked_mut(usize::from(index)), (#pats));
+            #scheduleds_write
+
+            #enqueue
+
+            #pend
+
+            Ok(())
+        );
+
+        // Releases that come too soon after the previous one are rejected with their own error.
+        // The check happens with the free queue locked, which serializes the spawners of the task,
+        // and the release is recorded only once it holds a slot of the queue
+        let body = match () {
+            #[cfg(feature = "timer-queue")]
+            () => ctxt.arrivals.get(task).map(|arrivals| {
+                quote!(
+                    let admitted = (#free { #priority }).claim(|f| {
+                        if #arrivals.admit(#baseline) {
+                            let index = #free_dequeue;
+                            if index.is_some() {
+                                #arrivals.record(#baseline);
+                            }
+                            Some(index)
                         } else {
-                            needs_unsafe = true;
-                            defs.push(quote!(#name: &'a mut #name));
-                            exprs.push(
-                                quote!(#name: &mut <#name as owned_singleton::Singleton>::new()),
-                            );
+                            None
                         }
-                        continue;
-                    } else {
-                        defs.push(quote!(#name: &#lt #mut_ #ty));
-                    }
-                }
+                    });
 
-                let alias = &ctxt.resources[name];
-                needs_unsafe = true;
-                if initialized {
-                    exprs.push(quote!(#name: &#mut_ #alias));
+                    match admitted {
+                        Some(Some(index)) => {
+                            #release
+                        }
+                        Some(None) => Err(rtfm::ReleaseError::Full((#pats))),
+                        None => Err(rtfm::ReleaseError::TooSoon((#pats))),
+                    }
+                )
+            }),
+            #[cfg(not(feature = "timer-queue"))]
+            () => None,
+        }
+        .unwrap_or_else(|| {
+            quote!(
+                if let Some(index) = (#free { #priority }).claim(|f| #free_dequeue) {
+                    #release
                 } else {
-                    let method = if mut_.is_some() {
-                        quote!(get_mut)
-                    } else {
-                        quote!(get_ref)
-                    };
-                    exprs.push(quote!(#name: #alias.#method() ));
+                    Err((#pats))
                 }
-            }
-        }
+            )
+        });
+        let error = spawn_error(app, task);
 
-        let unsafety = if needs_unsafe {
-            Some(quote!(unsafe))
-        } else {
-            None
+        let baseline_arg = match () {
+            #[cfg(feature = "timer-queue")]
+            () => quote!(#baseline: rtfm::Instant,),
+            #[cfg(not(feature = "timer-queue"))]
+            () => quote!(),
         };
-        items.push(quote!(
-            #[allow(unsafe_code)]
-            #[allow(unused_mut)]
-            let mut resources = {
-                #[allow(non_snake_case)]
-                struct Resources<'a> { #(#defs,)* }
 
-                #unsafety { Resources { #(#exprs,)* } }
-            };
-        ));
+        items.push(quote!(
+            #[inline(always)]
+            unsafe fn #alias(
+                #baseline_arg
+                #priority: &core::cell::Cell<u8>,
+                #(#args,)*
+            ) -> Result<(), #error> {
+                use core::ptr;
 
-        if may_call_lock {
-            items.push(quote!(
                 use rtfm::Mutex;
-            ));
-        }
-    }
 
-    if !spawn.is_empty() {
-        // Populate `spawn_fn`
-        for task in spawn {
-            if ctxt.spawn_fn.contains_key(task) {
-                continue;
+                #body
             }
+        ))
+    }
 
-            ctxt.spawn_fn.insert(task.clone(), mk_ident());
+    // Generate `spawn` structs; these call the `spawn` functions generated above
+    for (name, spawn) in app.spawn_callers() {
+        if spawn.is_empty() {
+            continue;
         }
 
-        if kind.is_idle() {
-            items.push(quote!(
-                let spawn = #module::Spawn { #priority };
-            ));
-        } else {
-            let baseline_expr = match () {
+        // NOTE software tasks can't take these names (see `check::app`)
+        #[cfg(feature = "timer-queue")]
+        let is_idle = name.to_string() == "idle" || name.to_string() == "post_init";
+
+        let mut methods = vec![];
+        for task in spawn {
+            let alias = &ctxt.spawn_fn[task];
+            let inputs = &app.tasks[task].inputs;
+            let pats = tuple_pat(inputs);
+
+            let instant = match () {
                 #[cfg(feature = "timer-queue")]
                 () => {
-                    let baseline = &ctxt.baseline;
-                    quote!(#baseline)
+                    if is_idle {
+                        quote!(rtfm::Instant::now(),)
+                    } else {
+                        quote!(self.#baseline,)
+                    }
                 }
                 #[cfg(not(feature = "timer-queue"))]
                 () => quote!(),
             };
-            items.push(quote!(
-                let spawn = #module::Spawn { #priority, #baseline_expr };
+            let error = spawn_error(app, task);
+            methods.push(quote!(
+                #[allow(unsafe_code)]
+                #[inline]
+                pub fn #task(&self, #(#inputs,)*) -> Result<(), #error> {
+                    unsafe { #alias(#instant &self.#priority, #pats) }
+                }
             ));
         }
-    }
 
-    if !schedule.is_empty() {
-        // Populate `schedule_fn`
-        for task in schedule {
-            if ctxt.schedule_fn.contains_key(task) {
-                continue;
+        items.push(quote!(
+            impl<'a> #name::Spawn<'a> {
+                #(#methods)*
             }
+        ));
+    }
 
-            ctxt.schedule_fn.insert(task.clone(), mk_ident());
-        }
+    quote!(#(#items)*)
+}
 
-        items.push(quote!(
-            use rtfm::U32Ext;
+fn publish(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut items = vec![];
 
-            let schedule = #module::Schedule { #priority };
+    // Type aliases
+    for (name, topic) in &app.topics {
+        let attrs = &topic.attrs;
+        let ty = &topic.ty;
+
+        items.push(quote!(
+            #(#attrs)*
+            type #name = #ty;
         ));
     }
 
-    if items.is_empty() {
-        quote!()
-    } else {
-        quote!(
-            let ref #priority = core::cell::Cell::new(#logical_prio);
+    // Generate `publish` functions
+    let priority = &ctxt.priority;
+    #[cfg(feature = "timer-queue")]
+    let baseline = &ctxt.baseline;
+    let baseline_arg = match () {
+        #[cfg(feature = "timer-queue")]
+        () => quote!(#baseline: rtfm::Instant,),
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(),
+    };
+    for (topic, alias) in &ctxt.publish_fn {
+        let subscribers = app.subscribers(topic);
 
-            #(#items)*
-        )
-    }
-}
+        if subscribers.is_empty() {
+            let baseline_expr = match () {
+                #[cfg(feature = "timer-queue")]
+                () => quote!(#baseline,),
+                #[cfg(not(feature = "timer-queue"))]
+                () => quote!(),
+            };
 
-fn idle(
-    ctxt: &mut Context,
-    app: &App,
-    analysis: &Analysis,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    if let Some(idle) = app.idle.as_ref() {
-        let attrs = &idle.attrs;
-        let locals = mk_locals(&idle.statics, true);
-        let stmts = &idle.stmts;
+            items.push(quote!(
+                #[inline(always)]
+                unsafe fn #alias(
+                    #baseline_arg
+                    #priority: &core::cell::Cell<u8>,
+                    message: #topic,
+                ) -> Result<(), rtfm::PublishError<#topic>> {
+                    let _ = (#baseline_expr #priority, message);
 
-        let prelude = prelude(
-            ctxt,
-            Kind::Idle,
-            &idle.args.resources,
-            &idle.args.spawn,
-            &idle.args.schedule,
-            app,
-            0,
-            analysis,
-        );
+                    Ok(())
+                }
+            ));
 
-        let module = module(
-            ctxt,
-            Kind::Idle,
-            !idle.args.schedule.is_empty(),
-            !idle.args.spawn.is_empty(),
+            continue;
+        }
+
+        let names = subscribers.iter().map(|task| task.to_string());
+        let frees = (0..subscribers.len())
+            .map(|i| Ident::new(&format!("f{}", i), Span::call_site()))
+            .collect::<Vec<_>>();
+        let indices = (0..subscribers.len())
+            .map(|i| Ident::new(&format!("i{}", i), Span::call_site()))
+            .collect::<Vec<_>>();
+
+        // Reserve a slot in the queue of every subscriber or in none of them
+        let checks = frees.iter();
+        let shifts = (0..subscribers.len()).map(|i| i as u32);
+        let dequeues = subscribers.iter().zip(&frees).map(|(task, f)| {
+            let free_dequeue = free_dequeue(ctxt, task);
+
+            quote!({
+                let f = &mut *#f;
+                #free_dequeue.unwrap()
+            })
+        });
+        let mut reserve = quote!(
+            let mut overflowed = 0;
+            #(
+                if !#checks.split().1.ready() {
+                    overflowed |= 1 << #shifts;
+                }
+            )*
+
+            if overflowed == 0 {
+                Ok((#(#dequeues,)*))
+            } else {
+                Err(overflowed)
+            }
         );
+        for (task, f) in subscribers.iter().zip(&frees).rev() {
+            let free = &ctxt.free_queues[*task];
 
-        let idle = &ctxt.idle;
+            reserve = quote!((#free { #priority }).claim(|#f| { #reserve }));
+        }
 
-        let name = format!("idle::{}", idle);
-        (
-            quote!(
-                #module
+        // The last subscriber gets the original message; the others get clones of it
+        let mut writes = vec![];
+        let mut enqueues = vec![];
+        let mut pends = vec![];
+        let n = subscribers.len();
+        for (i, (task, index)) in subscribers.iter().zip(&indices).enumerate() {
+            let inputs = &ctxt.inputs[*task];
+            let (enqueue, pend) = make_ready(ctxt, app, analysis, task);
+
+            let message = if i + 1 == n {
+                quote!(message)
+            } else {
+                quote!(message.clone())
+            };
 
-                #(#attrs)*
-                #[export_name = #name]
-                fn #idle() -> ! {
-                    #(#locals)*
+            let scheduleds_write = match () {
+                #[cfg(feature = "timer-queue")]
+                () => {
+                    let scheduleds = &ctxt.scheduleds[*task];
+                    quote!(
+                        ptr::write(
+                            #scheduleds.get_mut().get_unchecked_mut(usize::from(#index)),
+                            #baseline,
+                        );
+                    )
+                }
+                #[cfg(not(feature = "timer-queue"))]
+                () => quote!(),
+            };
 
-                    #prelude
+            writes.push(quote!(
+                ptr::write(#inputs.get_mut().get_unchecked_mut(usize::from(#index)), #message);
+                #scheduleds_write
+            ));
 
-                    #(#stmts)*
-                }),
-            quote!(#idle()),
-        )
-    } else {
-        (
-            quote!(),
-            quote!(loop {
-                rtfm::export::wfi();
-            }),
-        )
-    }
-}
+            enqueues.push(quote!({
+                let index = #index;
+                #enqueue
+            }));
 
-fn exceptions(ctxt: &mut Context, app: &App, analysis: &Analysis) -> Vec<proc_macro2::TokenStream> {
-    app.exceptions
-        .iter()
-        .map(|(ident, exception)| {
-            let attrs = &exception.attrs;
-            let statics = &exception.statics;
-            let stmts = &exception.stmts;
+            pends.push(pend);
+        }
 
-            let prelude = prelude(
-                ctxt,
-                Kind::Exception(ident.clone()),
-                &exception.args.resources,
-                &exception.args.spawn,
-                &exception.args.schedule,
-                app,
-                exception.args.priority,
-                analysis,
-            );
+        items.push(quote!(
+            #[inline(always)]
+            unsafe fn #alias(
+                #baseline_arg
+                #priority: &core::cell::Cell<u8>,
+                message: #topic,
+            ) -> Result<(), rtfm::PublishError<#topic>> {
+                use core::ptr;
+
+                use rtfm::Mutex;
+
+                match #reserve {
+                    Ok((#(#indices,)*)) => {
+                        #(#writes)*
+
+                        #(#enqueues)*
+
+                        #(#pends)*
+
+                        Ok(())
+                    }
+                    Err(overflowed) => {
+                        Err(rtfm::PublishError::new(message, &[#(#names),*], overflowed))
+                    }
+                }
+            }
+        ));
+    }
 
-            let module = module(
-                ctxt,
-                Kind::Exception(ident.clone()),
-                !exception.args.schedule.is_empty(),
-                !exception.args.spawn.is_empty(),
-            );
+    // Generate `publish` structs; these call the `publish` functions generated above
+    for (name, publish) in app.publish_callers() {
+        if publish.is_empty() {
+            continue;
+        }
 
-            #[cfg(feature = "timer-queue")]
-            let baseline = &ctxt.baseline;
-            let baseline_let = match () {
-                #[cfg(feature = "timer-queue")]
-                () => quote!(let #baseline = rtfm::Instant::now();),
-                #[cfg(not(feature = "timer-queue"))]
-                () => quote!(),
-            };
+        // NOTE software tasks can't take these names (see `check::app`)
+        #[cfg(feature = "timer-queue")]
+        let is_idle = name.to_string() == "idle" || name.to_string() == "post_init";
 
-            let start_let = match () {
+        let mut methods = vec![];
+        for topic in publish {
+            let alias = &ctxt.publish_fn[topic];
+            let method = Topic::method(topic);
+
+            let instant = match () {
                 #[cfg(feature = "timer-queue")]
-                () => quote!(
-                        #[allow(unused_variables)]
-                        let start = #baseline;
-                    ),
+                () => {
+                    if is_idle {
+                        quote!(rtfm::Instant::now(),)
+                    } else {
+                        quote!(self.#baseline,)
+                    }
+                }
                 #[cfg(not(feature = "timer-queue"))]
                 () => quote!(),
             };
+            methods.push(quote!(
+                #[allow(unsafe_code)]
+                #[inline]
+                pub fn #method(&self, message: #topic) -> Result<(), rtfm::PublishError<#topic>> {
+                    unsafe { #alias(#instant &self.#priority, message) }
+                }
+            ));
+        }
 
-            quote!(
-                #module
-
-                #[rtfm::export::exception]
-                #[doc(hidden)]
-                #(#attrs)*
-                fn #ident() {
-                    #(#statics)*
+        items.push(quote!(
+            impl<'a> #name::Publish<'a> {
+                #(#methods)*
+            }
+        ));
+    }
 
-                    #baseline_let
+    quote!(#(#items)*)
+}
 
-                    #prelude
+fn pend(app: &App) -> proc_macro2::TokenStream {
+    let mut items = vec![];
 
-                    #start_let
+    // Generate `pend` structs; only the interrupts bound to handlers can be pended through these
+    let device = &app.args.device;
+    for (name, pend) in app.pend_callers() {
+        if pend.is_empty() {
+            continue;
+        }
 
-                    rtfm::export::run(move || {
-                        #(#stmts)*
-                    })
-                })
-        })
-        .collect()
-}
+        let methods = pend.iter().map(|task| {
+            let vector = app.interrupts[task].args.binds(task);
 
-fn interrupts(
-    ctxt: &mut Context,
-    app: &App,
-    analysis: &Analysis,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let mut root = vec![];
-    let mut scoped = vec![];
+            quote!(
+                #[allow(non_snake_case)]
+                #[inline]
+                pub fn #task(&self) {
+                    rtfm::export::pend(#device::Interrupt::#vector)
+                }
+            )
+        });
 
-    for (ident, interrupt) in &app.interrupts {
-        let attrs = &interrupt.attrs;
-        let statics = &interrupt.statics;
-        let stmts = &interrupt.stmts;
+        items.push(quote!(
+            impl #name::Pend {
+                #(#methods)*
+            }
+        ));
+    }
 
-        let prelude = prelude(
-            ctxt,
-            Kind::Interrupt(ident.clone()),
-            &interrupt.args.resources,
-            &interrupt.args.spawn,
-            &interrupt.args.schedule,
-            app,
-            interrupt.args.priority,
-            analysis,
-        );
+    quote!(#(#items)*)
+}
 
-        root.push(module(
-            ctxt,
-            Kind::Interrupt(ident.clone()),
-            !interrupt.args.schedule.is_empty(),
-            !interrupt.args.spawn.is_empty(),
-        ));
+#[cfg(feature = "timer-queue")]
+fn schedule(ctxt: &Context, app: &App) -> proc_macro2::TokenStream {
+    let mut items = vec![];
 
-        #[cfg(feature = "timer-queue")]
-        let baseline = &ctxt.baseline;
-        let baseline_let = match () {
-            #[cfg(feature = "timer-queue")]
-            () => quote!(let #baseline = rtfm::Instant::now();),
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
+    // Generate `schedule` functions
+    let priority = &ctxt.priority;
+    let timer_queue = &ctxt.timer_queue;
+    for (task, alias) in &ctxt.schedule_fn {
+        let free = &ctxt.free_queues[task];
+        let enum_ = &ctxt.schedule_enum;
+        let inputs = &ctxt.inputs[task];
+        let scheduleds = &ctxt.scheduleds[task];
+        let args = &app.tasks[task].inputs;
+        let ty = tuple_ty(args);
+        let pats = tuple_pat(args);
+        let free_dequeue = free_dequeue(ctxt, task);
+        let timer_queue_enqueue = match () {
+            #[cfg(feature = "introspect")]
+            () => {
+                let stats = &ctxt.timer_queue_stats;
+                quote!({
+                    tq.enqueue_unchecked(nr);
+                    #stats.enqueue();
+                })
+            }
+            #[cfg(not(feature = "introspect"))]
+            () => quote!(tq.enqueue_unchecked(nr)),
         };
 
-        let start_let = match () {
-            #[cfg(feature = "timer-queue")]
-            () => quote!(
-                #[allow(unused_variables)]
-                let start = #baseline;
-            ),
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
-        };
+        items.push(quote!(
+            #[inline(always)]
+            unsafe fn #alias(
+                #priority: &core::cell::Cell<u8>,
+                instant: rtfm::Instant,
+                #(#args,)*
+            ) -> Result<(), #ty> {
+                use core::ptr;
 
-        scoped.push(quote!(
-            #[interrupt]
-            #(#attrs)*
-            fn #ident() {
-                #(#statics)*
+                use rtfm::Mutex;
 
-                #baseline_let
+                if let Some(index) = (#free { #priority }).claim(|f| #free_dequeue) {
+                    ptr::write(#inputs.get_mut().get_unchecked_mut(usize::from(index)), (#pats));
+                    ptr::write(
+                        #scheduleds.get_mut().get_unchecked_mut(usize::from(index)),
+                        instant,
+                    );
 
-                #prelude
+                    let nr = rtfm::export::NotReady {
+                        instant,
+                        index,
+                        task: #enum_::#task,
+                    };
 
-                #start_let
+                    ({#timer_queue { #priority }}).claim(|tq| #timer_queue_enqueue);
 
-                rtfm::export::run(move || {
-                    #(#stmts)*
-                })
-            }));
+                    Ok(())
+                } else {
+                    Err((#pats))
+                }
+            }
+        ))
     }
 
-    (quote!(#(#root)*), quote!(#(#scoped)*))
-}
+    // Generate `Schedule` structs; these call the `schedule` functions generated above
+    for (name, schedule) in app.schedule_callers() {
+        if schedule.is_empty() {
+            continue;
+        }
 
-fn tasks(ctxt: &mut Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
-    let mut items = vec![];
-    for (name, task) in &app.tasks {
-        #[cfg(feature = "timer-queue")]
-        let scheduleds_alias = mk_ident();
-        let free_alias = mk_ident();
-        let inputs_alias = mk_ident();
-        let task_alias = mk_ident();
+        debug_assert!(!schedule.is_empty());
 
-        let attrs = &task.attrs;
-        let inputs = &task.inputs;
-        let locals = mk_locals(&task.statics, false);
-        let stmts = &task.stmts;
+        let mut methods = vec![];
+        for task in schedule {
+            let alias = &ctxt.schedule_fn[task];
+            let inputs = &app.tasks[task].inputs;
+            let ty = tuple_ty(inputs);
+            let pats = tuple_pat(inputs);
 
-        let prelude = prelude(
-            ctxt,
-            Kind::Task(name.clone()),
-            &task.args.resources,
-            &task.args.spawn,
-            &task.args.schedule,
-            app,
-            task.args.priority,
-            analysis,
-        );
+            methods.push(quote!(
+                #[inline]
+                pub fn #task(
+                    &self,
+                    instant: rtfm::Instant,
+                    #(#inputs,)*
+                ) -> Result<(), #ty> {
+                    unsafe { #alias(&self.#priority, instant, #pats) }
+                }
+            ));
+        }
 
-        let ty = tuple_ty(inputs);
+        items.push(quote!(
+            impl<'a> #name::Schedule<'a> {
+                #(#methods)*
+            }
+        ));
+    }
 
-        let capacity_lit = mk_capacity_literal(analysis.capacities[name]);
-        let capacity_ty = mk_typenum_capacity(analysis.capacities[name], true);
+    quote!(#(#items)*)
+}
 
-        let resource = mk_resource(
-            ctxt,
-            &free_alias,
-            quote!(rtfm::export::FreeQueue<#capacity_ty>),
-            *analysis.free_queues.get(name).unwrap_or(&0),
-            quote!(#free_alias.get_mut()),
-            app,
-            None,
-        );
+// Generates the `Timer` API of the async tasks
+#[cfg(feature = "timer-queue")]
+fn timers(ctxt: &Context, app: &App) -> proc_macro2::TokenStream {
+    let mut items = vec![];
 
-        let scheduleds_static = match () {
-            #[cfg(feature = "timer-queue")]
-            () => {
-                let scheduleds_symbol = format!("{}::SCHEDULED_TIMES::{}", name, scheduleds_alias);
+    let priority = &ctxt.priority;
+    let timer_queue = &ctxt.timer_queue;
+    let enum_ = &ctxt.schedule_enum;
+    let timer_queue_enqueue = match () {
+        #[cfg(feature = "introspect")]
+        () => {
+            let stats = &ctxt.timer_queue_stats;
+            quote!({
+                tq.enqueue_unchecked(nr);
+                #stats.enqueue();
+            })
+        }
+        #[cfg(not(feature = "introspect"))]
+        () => quote!(tq.enqueue_unchecked(nr)),
+    };
+    for (task, alias) in &ctxt.delay_fn {
+        let waker = &ctxt.wakers[task];
+        let header = &ctxt.headers[task];
+
+        // NOTE a task has at most one pending timer queue entry; that's what `Header::arm` tracks.
+        // A `Delay` that was dropped may have left the entry at a later instant than the one
+        // requested; in that case the entry is moved
+        items.push(quote!(
+            #[allow(dead_code)]
+            #[inline(always)]
+            unsafe fn #alias(#priority: &core::cell::Cell<u8>, instant: rtfm::Instant) {
+                use rtfm::Mutex;
 
-                quote!(
-                    #[export_name = #scheduleds_symbol]
-                    static mut #scheduleds_alias:
-                    rtfm::export::MaybeUninit<[rtfm::Instant; #capacity_lit]> =
-                        rtfm::export::MaybeUninit::uninitialized();
-                )
-            }
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
-        };
+                ({#timer_queue { #priority }}).claim(|tq| match #header.arm(instant) {
+                    rtfm::export::Arm::Enqueue => {
+                        let nr = rtfm::export::NotReady {
+                            instant,
+                            index: 0,
+                            task: #enum_::#waker,
+                        };
 
-        let scheduled_let = match () {
-            #[cfg(feature = "timer-queue")]
-            () => {
-                let baseline = &ctxt.baseline;
-                quote!(let scheduled = #baseline;)
+                        #timer_queue_enqueue
+                    }
+                    rtfm::export::Arm::Advance => tq.advance(instant, |task| match task {
+                        #enum_::#waker => true,
+                        #[allow(unreachable_patterns)]
+                        _ => false,
+                    }),
+                    rtfm::export::Arm::Keep => {}
+                });
             }
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
-        };
+        ));
 
-        let baseline_arg = match () {
-            #[cfg(feature = "timer-queue")]
-            () => {
-                let baseline = &ctxt.baseline;
-                quote!(#baseline: rtfm::Instant,)
-            }
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
-        };
-        let task_symbol = format!("{}::{}", name, task_alias);
-        let inputs_symbol = format!("{}::INPUTS::{}", name, inputs_alias);
-        let free_symbol = format!("{}::FREE_QUEUE::{}", name, free_alias);
+        debug_assert!(app.tasks[task].is_async);
         items.push(quote!(
-            // FIXME(MaybeUninit) MaybeUninit won't be necessary when core::mem::MaybeUninit
-            // stabilizes because heapless constructors will work in const context
-            #[export_name = #free_symbol]
-            static mut #free_alias: rtfm::export::MaybeUninit<
-                    rtfm::export::FreeQueue<#capacity_ty>
-                > = rtfm::export::MaybeUninit::uninitialized();
-
-            #resource
+            #[allow(dead_code)]
+            impl<'a> #task::Timer<'a> {
+                /// Returns a future that completes `duration` after the current time
+                #[inline]
+                pub fn delay(
+                    &self,
+                    duration: rtfm::Duration,
+                ) -> rtfm::Delay<impl FnMut(rtfm::Instant) + 'a> {
+                    self.at(rtfm::Instant::now() + duration)
+                }
 
-            #[export_name = #inputs_symbol]
-            static mut #inputs_alias: rtfm::export::MaybeUninit<[#ty; #capacity_lit]> =
-                rtfm::export::MaybeUninit::uninitialized();
+                /// Returns a future that completes at the given `instant`
+                #[inline]
+                pub fn at(
+                    &self,
+                    instant: rtfm::Instant,
+                ) -> rtfm::Delay<impl FnMut(rtfm::Instant) + 'a> {
+                    let priority = self.#priority;
+                    rtfm::Delay::new(instant, move |instant| unsafe { #alias(priority, instant) })
+                }
 
-            #scheduleds_static
+                /// Returns a future that completes with the output of `future`, or with `None` if
+                /// `duration` elapses first
+                #[inline]
+                pub fn timeout<F>(
+                    &self,
+                    duration: rtfm::Duration,
+                    future: F,
+                ) -> rtfm::Timeout<F, impl FnMut(rtfm::Instant) + 'a>
+                where
+                    F: core::future::Future,
+                {
+                    rtfm::Timeout::new(future, self.delay(duration))
+                }
+            }
+        ));
+    }
 
-            #(#attrs)*
-            #[export_name = #task_symbol]
-            fn #task_alias(#baseline_arg #(#inputs,)*) {
-                #(#locals)*
+    quote!(#(#items)*)
+}
 
-                #prelude
+// Flattens the schedule tables of all the modes into the slots indexed by the timer queue entry of
+// the table: `(mode, task, start, end)`. The index that follows the last slot is the frame boundary
+fn table_entries(app: &App) -> Vec<(usize, &Ident, u64, u64)> {
+    app.mode_tables()
+        .into_iter()
+        .enumerate()
+        .flat_map(|(mode, (_, table))| {
+            table
+                .into_iter()
+                .map(move |(slot, task)| (mode, task, slot.start.value(), slot.end.value()))
+        })
+        .collect()
+}
 
-                #scheduled_let
+fn timer_queue(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let tasks = &analysis.timer_queue.tasks;
 
-                #(#stmts)*
-            }
-        ));
+    if !analysis.timer_queue.is_used() {
+        return quote!();
+    }
 
-        items.push(module(
-            ctxt,
-            Kind::Task(name.clone()),
-            !task.args.schedule.is_empty(),
-            !task.args.spawn.is_empty(),
-        ));
+    let mut items = vec![];
 
+    let enum_ = &ctxt.schedule_enum;
+    let wakers: Vec<&Ident> = match () {
         #[cfg(feature = "timer-queue")]
-        ctxt.scheduleds.insert(name.clone(), scheduleds_alias);
-        ctxt.free_queues.insert(name.clone(), free_alias);
-        ctxt.inputs.insert(name.clone(), inputs_alias);
-        ctxt.tasks.insert(name.clone(), task_alias);
+        () => analysis
+            .timer_queue
+            .wakers
+            .iter()
+            .map(|task| &ctxt.wakers[task])
+            .collect::<Vec<_>>(),
+        #[cfg(not(feature = "timer-queue"))]
+        () => vec![],
+    };
+    let table = &ctxt.table;
+    let mut variants = wakers.clone();
+    if !analysis.timer_queue.table.is_empty() {
+        variants.push(table);
     }
+    let overruns = app
+        .budgets()
+        .into_iter()
+        .map(|(task, _)| (task, &ctxt.overruns[task]))
+        .collect::<Vec<_>>();
+    variants.extend(overruns.iter().map(|(_, variant)| *variant));
+    let stalls = app
+        .heartbeats()
+        .into_iter()
+        .map(|(task, _)| (task, &ctxt.stalls[task]))
+        .collect::<Vec<_>>();
+    variants.extend(stalls.iter().map(|(_, variant)| *variant));
+    items.push(quote!(
+        #[allow(dead_code)]
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy)]
+        enum #enum_ { #(#tasks,)* #(#variants,)* }
+    ));
 
-    quote!(#(#items)*)
-}
+    let cap = mk_typenum_capacity(analysis.timer_queue.capacity, false);
+    let tq = &ctxt.timer_queue;
+    let symbol = "TIMER_QUEUE";
+    items.push(quote!(
+        #[export_name = #symbol]
+        static mut #tq:
+            rtfm::export::MaybeUninit<rtfm::export::TimerQueue<#enum_, #cap>> =
+                rtfm::export::MaybeUninit::uninitialized();
+    ));
 
-fn dispatchers(
-    ctxt: &mut Context,
-    app: &App,
-    analysis: &Analysis,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let mut data = vec![];
-    let mut dispatchers = vec![];
+    items.push(mk_resource(
+        ctxt,
+        tq,
+        quote!(rtfm::export::TimerQueue<#enum_, #cap>),
+        analysis.timer_queue.ceiling,
+        quote!(#tq.get_mut()),
+        app,
+        None,
+    ));
 
-    for (level, dispatcher) in &analysis.dispatchers {
-        let ready_alias = mk_ident();
-        let enum_alias = mk_ident();
-        let tasks = &dispatcher.tasks;
-        let capacity = mk_typenum_capacity(dispatcher.capacity, true);
+    let stats_dequeue = match () {
+        #[cfg(feature = "introspect")]
+        () => {
+            let stats = &ctxt.timer_queue_stats;
+            items.push(mk_stats(
+                stats,
+                "TIMER_QUEUE::STATS",
+                analysis.timer_queue.capacity,
+            ));
 
-        let symbol = format!("P{}::READY_QUEUE::{}", level, ready_alias);
-        let e = quote!(rtfm::export);
-        let ty = quote!(#e::ReadyQueue<#enum_alias, #capacity>);
-        let ceiling = *analysis.ready_queues.get(&level).unwrap_or(&0);
-        let resource = mk_resource(
-            ctxt,
-            &ready_alias,
-            ty.clone(),
-            ceiling,
-            quote!(#ready_alias.get_mut()),
-            app,
-            None,
-        );
-        data.push(quote!(
-            #[allow(dead_code)]
-            #[allow(non_camel_case_types)]
-            enum #enum_alias { #(#tasks,)* }
+            quote!(#stats.dequeue();)
+        }
+        #[cfg(not(feature = "introspect"))]
+        () => quote!(),
+    };
+    let stats_enqueue = match () {
+        #[cfg(feature = "introspect")]
+        () => {
+            let stats = &ctxt.timer_queue_stats;
+            quote!(#stats.enqueue();)
+        }
+        #[cfg(not(feature = "introspect"))]
+        () => quote!(),
+    };
 
-            #[export_name = #symbol]
-            static mut #ready_alias: #e::MaybeUninit<#ty> = #e::MaybeUninit::uninitialized();
+    let priority = &ctxt.priority;
+    let mut arms = tasks
+        .iter()
+        .map(|task| {
+            let (enqueue, pend) = make_ready(ctxt, app, analysis, task);
 
-            #resource
-        ));
+            quote!(
+                #enum_::#task => {
+                    #enqueue
 
-        let interrupt = &dispatcher.interrupt;
+                    #pend
+                }
+            )
+        })
+        .chain(wakers.iter().zip(&analysis.timer_queue.wakers).map(|(waker, task)| {
+            let header = &ctxt.headers[task];
 
-        let arms = dispatcher
-            .tasks
-            .iter()
-            .map(|task| {
-                let inputs = &ctxt.inputs[task];
-                let free = &ctxt.free_queues[task];
-                let pats = tuple_pat(&app.tasks[task].inputs);
-                let alias = &ctxt.tasks[task];
+            quote!(
+                #enum_::#waker => {
+                    (#tq { #priority }).claim(|_| #header.disarm());
+                    #header.wake();
+                }
+            )
+        }))
+        .collect::<Vec<_>>();
 
-                let baseline_let;
-                let call;
-                match () {
-                    #[cfg(feature = "timer-queue")]
-                    () => {
-                        let scheduleds = &ctxt.scheduleds[task];
-                        baseline_let = quote!(
-                            let baseline =
-                                ptr::read(#scheduleds.get_ref().get_unchecked(usize::from(index)));
-                        );
-                        call = quote!(#alias(baseline, #pats));
-                    }
-                    #[cfg(not(feature = "timer-queue"))]
-                    () => {
-                        baseline_let = quote!();
-                        call = quote!(#alias(#pats));
+    // The entry of the schedule table releases the task of slot `index` and then re-arms itself
+    // for the next slot. After the last slot it re-arms itself for the end of the major frame where
+    // it picks the table of the requested mode
+    let entries = table_entries(app);
+    if !entries.is_empty() {
+        let release = &ctxt.table_release;
+        let frame = app.args.frame.as_ref().map(|lit| lit.value()).unwrap_or(0);
+        items.push(quote!(
+            static mut #release: rtfm::export::MaybeUninit<rtfm::Instant> =
+                rtfm::export::MaybeUninit::uninitialized();
+        ));
+
+        let boundary = entries.len() as u8;
+        let mut slots = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (mode, task, start, end))| {
+                let slots = &ctxt.table_slots[*task];
+                let free = &ctxt.free_queues[*task];
+                let (enqueue, pend) = make_ready(ctxt, app, analysis, task);
+                let free_dequeue = free_dequeue(ctxt, task);
+                let scheduled = scheduled(ctxt, task);
+
+                let len = (end - start) as u32;
+                let (next, delta) = match entries.get(i + 1) {
+                    Some((next_mode, _, next_start, _)) if next_mode == mode => {
+                        ((i + 1) as u8, (next_start - start) as u32)
                     }
+                    _ => (boundary, (frame - start) as u32),
                 };
 
-                quote!(#enum_alias::#task => {
-                    #baseline_let
-                    let input = ptr::read(#inputs.get_ref().get_unchecked(usize::from(index)));
-                    #free.get_mut().split().0.enqueue_unchecked(index);
-                    let (#pats) = input;
-                    #call
-                })
-            })
-            .collect::<Vec<_>>();
+                let i = i as u8;
+                quote!(
+                    #i => {
+                        let release = *#release.get_ref();
 
-        let attrs = &dispatcher.attrs;
-        dispatchers.push(quote!(
-            #(#attrs)*
-            #[interrupt]
-            unsafe fn #interrupt() {
-                use core::ptr;
+                        // if the previous release of the task hasn't completed yet this one is
+                        // dropped and counted as a miss
+                        if let Some(index) = (#free { #priority }).claim(|f| #free_dequeue) {
+                            core::ptr::write(#scheduled, release);
+                            #slots.release(release + rtfm::U32Ext::cycles(#len));
 
-                rtfm::export::run(|| {
-                    while let Some((task, index)) = #ready_alias.get_mut().split().1.dequeue() {
-                        match task {
-                            #(#arms)*
+                            #enqueue
+
+                            #pend
+                        } else {
+                            #slots.miss();
                         }
+
+                        (#next, release + rtfm::U32Ext::cycles(#delta))
                     }
-                });
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // the first slot of each mode; a mode without slots waits for the next frame boundary
+        let mut firsts = (0..cmp::max(app.args.modes.len(), 1)).map(|mode| {
+            let (next, delta) = entries
+                .iter()
+                .position(|(mode_, _, _, _)| *mode_ == mode)
+                .map(|i| (i as u8, entries[i].2 as u32))
+                .unwrap_or((boundary, frame as u32));
+
+            quote!((#next, release + rtfm::U32Ext::cycles(#delta)))
+        });
+        let first = if app.args.modes.is_empty() {
+            firsts.next().expect("UNREACHABLE")
+        } else {
+            let requested = &ctxt.mode_requested;
+            let modes = (0..app.args.modes.len()).map(|mode| mode as u8);
+            quote!(match #requested.load(core::sync::atomic::Ordering::Relaxed) {
+                #(#modes => #firsts,)*
+                _ => core::hint::unreachable_unchecked(),
+            })
+        };
+        slots.push(quote!(
+            #boundary => {
+                let release = *#release.get_ref();
+
+                #first
             }
         ));
 
-        ctxt.ready_queues.insert(*level, ready_alias);
-        ctxt.enums.insert(*level, enum_alias);
+        arms.push(quote!(
+            #enum_::#table => {
+                let (next, instant) = match index {
+                    #(#slots)*
+                    _ => core::hint::unreachable_unchecked(),
+                };
+                #release.set(instant);
+
+                let nr = rtfm::export::NotReady {
+                    instant,
+                    index: next,
+                    task: #enum_::#table,
+                };
+                (#tq { #priority }).claim(|tq| {
+                    tq.enqueue_unchecked(nr);
+                    #stats_enqueue
+                });
+            }
+        ));
     }
 
-    (quote!(#(#data)*), quote!(#(#dispatchers)*))
-}
+    // The entry of a budget reports an overrun if the instance that armed it is still running; if
+    // a newer instance is running the entry is re-armed for the end of its budget
+    let overrun = &ctxt.overrun;
+    for (task, variant) in &overruns {
+        let budget = &ctxt.budgets[*task];
+        arms.push(quote!(
+            #enum_::#variant => match #budget.check() {
+                rtfm::export::BudgetCheck::Idle => {}
+                rtfm::export::BudgetCheck::Overrun => #overrun(Task::#task),
+                rtfm::export::BudgetCheck::Rearm(instant) => {
+                    let nr = rtfm::export::NotReady {
+                        instant,
+                        index: 0,
+                        task: #enum_::#variant,
+                    };
+                    (#tq { #priority }).claim(|tq| {
+                        tq.enqueue_unchecked(nr);
+                        #stats_enqueue
+                    });
+                }
+            }
+        ));
+    }
 
-fn spawn(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
-    let mut items = vec![];
+    // The entry of a heartbeat reports a stall if the task hasn't completed an instance within its
+    // heartbeat and re-arms itself for the next check
+    let stalled = &ctxt.stalled;
+    for (task, variant) in &stalls {
+        let heartbeat = &ctxt.heartbeats[*task];
+        let report = if app.stalled.is_some() {
+            quote!(if stalled {
+                #stalled(Heartbeat::#task);
+            })
+        } else {
+            quote!()
+        };
 
-    // Generate `spawn` functions
-    let device = &app.args.device;
-    let priority = &ctxt.priority;
-    #[cfg(feature = "timer-queue")]
-    let baseline = &ctxt.baseline;
-    for (task, alias) in &ctxt.spawn_fn {
-        let free = &ctxt.free_queues[task];
-        let level = app.tasks[task].args.priority;
-        let ready = &ctxt.ready_queues[&level];
-        let enum_ = &ctxt.enums[&level];
-        let dispatcher = &analysis.dispatchers[&level].interrupt;
-        let inputs = &ctxt.inputs[task];
-        let args = &app.tasks[task].inputs;
-        let ty = tuple_ty(args);
-        let pats = tuple_pat(args);
+        arms.push(quote!(
+            #enum_::#variant => {
+                #[allow(unused_variables)]
+                let (stalled, instant) = #heartbeat.check();
+                #report
 
-        let scheduleds_write = match () {
-            #[cfg(feature = "timer-queue")]
-            () => {
-                let scheduleds = &ctxt.scheduleds[task];
-                quote!(
-                    ptr::write(
-                        #scheduleds.get_mut().get_unchecked_mut(usize::from(index)),
-                        #baseline,
-                    );
-                )
+                let nr = rtfm::export::NotReady {
+                    instant,
+                    index: 0,
+                    task: #enum_::#variant,
+                };
+                (#tq { #priority }).claim(|tq| {
+                    tq.enqueue_unchecked(nr);
+                    #stats_enqueue
+                });
             }
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
-        };
+        ));
+    }
 
-        let baseline_arg = match () {
-            #[cfg(feature = "timer-queue")]
-            () => quote!(#baseline: rtfm::Instant,),
-            #[cfg(not(feature = "timer-queue"))]
-            () => quote!(),
-        };
+    let body = measure(
+        ctxt,
+        &Ident::new("SysTick", Span::call_site()),
+        quote!(rtfm::export::run(|| {
+            rtfm::export::sys_tick(#tq { #priority }, |task, index| {
+                #stats_dequeue
 
-        items.push(quote!(
-            #[inline(always)]
-            unsafe fn #alias(
-                #baseline_arg
-                #priority: &core::cell::Cell<u8>,
-                #(#args,)*
-            ) -> Result<(), #ty> {
-                use core::ptr;
+                match task {
+                    #(#arms)*
+                }
+            });
+        })),
+    );
 
-                use rtfm::Mutex;
+    let logical_prio = analysis.timer_queue.priority;
+    items.push(quote!(
+        #[rtfm::export::exception]
+        #[doc(hidden)]
+        unsafe fn SysTick() {
+            use rtfm::Mutex;
 
-                if let Some(index) = (#free { #priority }).claim(|f| f.split().1.dequeue()) {
-                    ptr::write(#inputs.get_mut().get_unchecked_mut(usize::from(index)), (#pats));
-                    #scheduleds_write
+            let ref #priority = core::cell::Cell::new(#logical_prio);
 
-                    #ready { #priority }.claim(|rq| {
-                        rq.split().0.enqueue_unchecked((#enum_::#task, index))
-                    });
+            #body
+        }
+    ));
+
+    quote!(#(#items)*)
+}
 
-                    rtfm::pend(#device::Interrupt::#dispatcher);
+#[cfg(feature = "introspect")]
+fn introspect(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut symbols = vec![];
 
-                    Ok(())
-                } else {
-                    Err((#pats))
-                }
+    let mut tasks = vec![];
+    for (name, task) in &app.tasks {
+        let stats = &ctxt.stats[name];
+        let priority = task.args.priority;
+        let symbol = format!("{}\0", name);
+        symbols.push(quote!(
+            rtfm::introspect::Symbol::new(
+                #symbol,
+                &#stats,
+                rtfm::introspect::Kind::Task,
+                #priority,
+            )
+        ));
+
+        let doc = format!("Pending instances of the software task `{}`", name);
+        tasks.push(quote!(
+            #[doc = #doc]
+            pub fn #name() -> &'static rtfm::introspect::Stats {
+                &super::super::#stats
             }
-        ))
+        ));
     }
 
-    // Generate `spawn` structs; these call the `spawn` functions generated above
-    for (name, spawn) in app.spawn_callers() {
-        if spawn.is_empty() {
-            continue;
-        }
+    let mut arms = vec![];
+    for (level, stats) in &ctxt.ready_stats {
+        let symbol = format!("P{}::READY_QUEUE\0", level);
+        symbols.push(quote!(
+            rtfm::introspect::Symbol::new(
+                #symbol,
+                &#stats,
+                rtfm::introspect::Kind::ReadyQueue,
+                #level,
+            )
+        ));
 
-        #[cfg(feature = "timer-queue")]
-        let is_idle = name.to_string() == "idle";
+        arms.push(quote!(#level => Some(&super::#stats),));
+    }
 
-        let mut methods = vec![];
-        for task in spawn {
-            let alias = &ctxt.spawn_fn[task];
-            let inputs = &app.tasks[task].inputs;
-            let ty = tuple_ty(inputs);
-            let pats = tuple_pat(inputs);
+    let timer_queue = if !analysis.timer_queue.is_used() {
+        quote!(None)
+    } else {
+        let stats = &ctxt.timer_queue_stats;
+        let priority = analysis.timer_queue.priority;
+        symbols.push(quote!(
+            rtfm::introspect::Symbol::new(
+                "TIMER_QUEUE\0",
+                &#stats,
+                rtfm::introspect::Kind::TimerQueue,
+                #priority,
+            )
+        ));
 
-            let instant = match () {
-                #[cfg(feature = "timer-queue")]
-                () => {
-                    if is_idle {
-                        quote!(rtfm::Instant::now(),)
-                    } else {
-                        quote!(self.#baseline,)
-                    }
-                }
-                #[cfg(not(feature = "timer-queue"))]
-                () => quote!(),
-            };
-            methods.push(quote!(
-                #[allow(unsafe_code)]
-                #[inline]
-                pub fn #task(&self, #(#inputs,)*) -> Result<(), #ty> {
-                    unsafe { #alias(#instant &self.#priority, #pats) }
+        quote!(Some(&super::#stats))
+    };
+
+    let len = LitInt::new(symbols.len() as u64, IntSuffix::None, Span::call_site());
+    let alias = &ctxt.symbols;
+    quote!(
+        #[export_name = "RTFM_INTROSPECT"]
+        #[link_section = ".rtfm_introspect"]
+        static #alias: [rtfm::introspect::Symbol; #len] = unsafe { [#(#symbols,)*] };
+
+        /// Run-time statistics of the task queues
+        pub mod introspect {
+            /// Software tasks
+            pub mod tasks {
+                #(#tasks)*
+            }
+
+            /// Returns the statistics of the ready queue of the dispatcher that runs at the given
+            /// `priority`
+            pub fn ready_queue(priority: u8) -> Option<&'static rtfm::introspect::Stats> {
+                match priority {
+                    #(#arms)*
+                    _ => None,
                 }
-            ));
+            }
+
+            /// Returns the statistics of the timer queue
+            pub fn timer_queue() -> Option<&'static rtfm::introspect::Stats> {
+                #timer_queue
+            }
+
+            /// Returns the symbol table
+            pub fn symbols() -> &'static [rtfm::introspect::Symbol] {
+                &super::#alias
+            }
         }
+    )
+}
+
+#[cfg(feature = "timer-queue")]
+fn arrivals(ctxt: &Context, app: &App) -> proc_macro2::TokenStream {
+    if ctxt.arrivals.is_empty() {
+        return quote!();
+    }
 
+    let mut arrivals = ctxt.arrivals.iter().collect::<Vec<_>>();
+    arrivals.sort_by_key(|(task, _)| task.to_string());
+
+    let mut items = vec![];
+    let mut fns = vec![];
+    for (task, alias) in arrivals {
+        let min = app.tasks[task].args.min_inter_arrival.as_ref().unwrap();
         items.push(quote!(
-            impl<'a> #name::Spawn<'a> {
-                #(#methods)*
+            static #alias: rtfm::Arrivals = rtfm::Arrivals::new(#min);
+        ));
+
+        let doc = format!("Releases of the `{}` task", task);
+        fns.push(quote!(
+            #[doc = #doc]
+            pub fn #task() -> &'static rtfm::Arrivals {
+                &super::#alias
             }
         ));
     }
 
-    quote!(#(#items)*)
+    quote!(
+        #(#items)*
+
+        /// Releases of the software tasks that have a minimum inter-arrival time
+        pub mod arrivals {
+            #(#fns)*
+        }
+    )
 }
 
 #[cfg(feature = "timer-queue")]
-fn schedule(ctxt: &Context, app: &App) -> proc_macro2::TokenStream {
-    let mut items = vec![];
+fn slots(ctxt: &Context) -> proc_macro2::TokenStream {
+    if ctxt.table_slots.is_empty() {
+        return quote!();
+    }
 
-    // Generate `schedule` functions
-    let priority = &ctxt.priority;
-    let timer_queue = &ctxt.timer_queue;
-    for (task, alias) in &ctxt.schedule_fn {
-        let free = &ctxt.free_queues[task];
-        let enum_ = &ctxt.schedule_enum;
-        let inputs = &ctxt.inputs[task];
-        let scheduleds = &ctxt.scheduleds[task];
-        let args = &app.tasks[task].inputs;
-        let ty = tuple_ty(args);
-        let pats = tuple_pat(args);
+    let mut slots = ctxt.table_slots.iter().collect::<Vec<_>>();
+    slots.sort_by_key(|(task, _)| task.to_string());
 
+    let mut items = vec![];
+    let mut fns = vec![];
+    for (task, alias) in slots {
         items.push(quote!(
-            #[inline(always)]
-            unsafe fn #alias(
-                #priority: &core::cell::Cell<u8>,
-                instant: rtfm::Instant,
-                #(#args,)*
-            ) -> Result<(), #ty> {
-                use core::ptr;
+            static #alias: rtfm::Slots = rtfm::Slots::new();
+        ));
 
-                use rtfm::Mutex;
+        let doc = format!("Releases of the `{}` task", task);
+        fns.push(quote!(
+            #[doc = #doc]
+            pub fn #task() -> &'static rtfm::Slots {
+                &super::#alias
+            }
+        ));
+    }
 
-                if let Some(index) = (#free { #priority }).claim(|f| f.split().1.dequeue()) {
-                    ptr::write(#inputs.get_mut().get_unchecked_mut(usize::from(index)), (#pats));
-                    ptr::write(
-                        #scheduleds.get_mut().get_unchecked_mut(usize::from(index)),
-                        instant,
-                    );
+    quote!(
+        #(#items)*
 
-                    let nr = rtfm::export::NotReady {
-                        instant,
-                        index,
-                        task: #enum_::#task,
-                    };
+        /// Releases of the time-triggered software tasks
+        pub mod slots {
+            #(#fns)*
+        }
+    )
+}
 
-                    ({#timer_queue { #priority }}).claim(|tq| tq.enqueue_unchecked(nr));
+#[cfg(feature = "wcet")]
+fn wcet(ctxt: &Context) -> proc_macro2::TokenStream {
+    let mut times = ctxt.times.iter().collect::<Vec<_>>();
+    times.sort_by_key(|(task, _)| task.to_string());
 
-                    Ok(())
-                } else {
-                    Err((#pats))
-                }
+    let mut items = vec![];
+    let mut fns = vec![];
+    let mut all = vec![];
+    for (task, alias) in times {
+        let symbol = format!("{}::WCET", task);
+        items.push(quote!(
+            #[export_name = #symbol]
+            static #alias: rtfm::wcet::Times = rtfm::wcet::Times::new();
+        ));
+
+        let doc = format!("Execution times of the `{}` task", task);
+        fns.push(quote!(
+            #[doc = #doc]
+            #[allow(non_snake_case)]
+            pub fn #task() -> &'static rtfm::wcet::Times {
+                &super::super::#alias
             }
-        ))
+        ));
+
+        let name = task.to_string();
+        all.push(quote!((#name, &super::#alias)));
     }
 
-    // Generate `Schedule` structs; these call the `schedule` functions generated above
-    for (name, schedule) in app.schedule_callers() {
-        if schedule.is_empty() {
-            continue;
+    let n = LitInt::new(all.len() as u64, IntSuffix::None, Span::call_site());
+    quote!(
+        #(#items)*
+
+        /// Execution time measurements
+        pub mod wcet {
+            /// Software and hardware tasks
+            pub mod tasks {
+                #(#fns)*
+            }
+
+            /// Returns the name and execution times of every task, sorted by name
+            pub fn all() -> [(&'static str, &'static rtfm::wcet::Times); #n] {
+                [#(#all,)*]
+            }
         }
+    )
+}
 
-        debug_assert!(!schedule.is_empty());
+/// Contexts that can be running when the device crashes, in id order, and their static priority
+#[cfg(feature = "crash-report")]
+fn crash_contexts(app: &App, analysis: &Analysis) -> Vec<(Ident, u8)> {
+    fn sorted<'a>(
+        items: impl Iterator<Item = (&'a Ident, u8)>,
+    ) -> impl Iterator<Item = (Ident, u8)> {
+        let mut items = items
+            .map(|(name, prio)| (name.clone(), prio))
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(name, _)| name.to_string());
+        items.into_iter()
+    }
 
-        let mut methods = vec![];
-        for task in schedule {
-            let alias = &ctxt.schedule_fn[task];
-            let inputs = &app.tasks[task].inputs;
-            let ty = tuple_ty(inputs);
-            let pats = tuple_pat(inputs);
+    let mut contexts = vec![(Ident::new("init", Span::call_site()), 0)];
+    if app.post_init.is_some() {
+        contexts.push((Ident::new("post_init", Span::call_site()), 0));
+    }
+    if app.idle.is_some() {
+        contexts.push((Ident::new("idle", Span::call_site()), 0));
+    }
 
-            methods.push(quote!(
-                #[inline]
-                pub fn #task(
-                    &self,
-                    instant: rtfm::Instant,
-                    #(#inputs,)*
-                ) -> Result<(), #ty> {
-                    unsafe { #alias(&self.#priority, instant, #pats) }
-                }
-            ));
-        }
+    contexts.extend(sorted(
+        app.exceptions
+            .iter()
+            .map(|(name, e)| (name, e.args.priority)),
+    ));
+    contexts.extend(sorted(
+        app.interrupts
+            .iter()
+            .map(|(name, i)| (name, i.args.priority)),
+    ));
+    contexts.extend(sorted(
+        app.tasks.iter().map(|(name, t)| (name, t.args.priority)),
+    ));
 
-        items.push(quote!(
-            impl<'a> #name::Schedule<'a> {
-                #(#methods)*
-            }
+    if analysis.timer_queue.is_used() {
+        contexts.push((
+            Ident::new("SysTick", Span::call_site()),
+            analysis.timer_queue.priority,
         ));
     }
 
-    quote!(#(#items)*)
+    contexts
 }
 
-fn timer_queue(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
-    let tasks = &analysis.timer_queue.tasks;
+/// Marks the context `name` as the running one
+#[cfg(feature = "crash-report")]
+fn enter(ctxt: &Context, name: &str) -> proc_macro2::TokenStream {
+    let id = ctxt.crash_ids[&Ident::new(name, Span::call_site())];
+    quote!(rtfm::crash::set(#id);)
+}
 
-    if tasks.is_empty() {
-        return quote!();
-    }
+#[cfg(feature = "crash-report")]
+fn crash(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let contexts = crash_contexts(app, analysis);
+    let names = contexts
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+    let priorities = contexts
+        .iter()
+        .map(|(_, priority)| priority)
+        .collect::<Vec<_>>();
+    let ncontexts = LitInt::new(contexts.len() as u64, IntSuffix::None, Span::call_site());
 
-    let mut items = vec![];
+    let mut tasks = app.tasks.keys().collect::<Vec<_>>();
+    tasks.sort_by_key(|task| task.to_string());
+    let ntasks = LitInt::new(tasks.len() as u64, IntSuffix::None, Span::call_site());
+    let occupancies = tasks
+        .iter()
+        .map(|task| {
+            let capacity = analysis.capacities[*task];
+            let free = &ctxt.free_queues[*task];
+            quote!(#capacity - #free.get_ref().len() as u8)
+        })
+        .collect::<Vec<_>>();
+    let tasks = tasks
+        .iter()
+        .map(|task| task.to_string())
+        .collect::<Vec<_>>();
 
-    let enum_ = &ctxt.schedule_enum;
-    items.push(quote!(
-        #[allow(dead_code)]
-        #[allow(non_camel_case_types)]
-        #[derive(Clone, Copy)]
-        enum #enum_ { #(#tasks,)* }
-    ));
+    let device = &app.args.device;
+    let crash_contexts = &ctxt.crash_contexts;
+    let crash_tasks = &ctxt.crash_tasks;
+    let crash_inspect = &ctxt.crash_inspect;
+    quote!(
+        static #crash_contexts: [&str; #ncontexts] = [#(#names,)*];
 
-    let cap = mk_typenum_capacity(analysis.timer_queue.capacity, false);
-    let tq = &ctxt.timer_queue;
-    let symbol = format!("TIMER_QUEUE::{}", tq);
-    items.push(quote!(
-        #[export_name = #symbol]
-        static mut #tq:
-            rtfm::export::MaybeUninit<rtfm::export::TimerQueue<#enum_, #cap>> =
-                rtfm::export::MaybeUninit::uninitialized();
-    ));
+        static #crash_tasks: [&str; #ntasks] = [#(#tasks,)*];
 
-    items.push(mk_resource(
-        ctxt,
-        tq,
-        quote!(rtfm::export::TimerQueue<#enum_, #cap>),
-        analysis.timer_queue.ceiling,
-        quote!(#tq.get_mut()),
-        app,
-        None,
-    ));
+        #[allow(unsafe_code)]
+        #[doc(hidden)]
+        #[export_name = "rtfm::crash::inspect"]
+        unsafe fn #crash_inspect(task: u8, queues: &mut [u8]) -> u8 {
+            let priorities: [u8; #ncontexts] = [#(#priorities,)*];
+            let occupancies: [u8; #ntasks] = [#(#occupancies,)*];
+
+            for (queue, occupancy) in queues.iter_mut().zip(occupancies.iter()) {
+                *queue = *occupancy;
+            }
+
+            rtfm::crash::priority(priorities[usize::from(task) - 1], #device::NVIC_PRIO_BITS)
+        }
+    )
+}
 
-    let priority = &ctxt.priority;
+/// Generates the function that masks / unmasks the interrupts of the tasks in a range of priorities
+/// (see `Mutex::sources`)
+fn sources(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
     let device = &app.args.device;
-    let arms = tasks
+
+    // Priority -> Interrupts (and the state of their `Control` handle, if any)
+    let mut levels: Map<u8, Vec<(&Ident, Option<&Ident>)>> = Map::default();
+    for (name, interrupt) in &app.interrupts {
+        let state = ctxt.controls.get(name);
+
+        // an interrupt that's never enabled can't preempt a critical section
+        if state.is_none() && !interrupt.args.is_enabled() {
+            continue;
+        }
+
+        levels
+            .entry(interrupt.args.priority)
+            .or_insert_with(Vec::new)
+            .push((interrupt.args.binds(name), state));
+    }
+    for (level, dispatcher) in &analysis.dispatchers {
+        if let Some(interrupt) = dispatcher.interrupt.as_ref() {
+            levels
+                .entry(*level)
+                .or_insert_with(Vec::new)
+                .push((interrupt, None));
+        }
+    }
+    let mut levels = levels.into_iter().collect::<Vec<_>>();
+    levels.sort_by_key(|(level, _)| *level);
+
+    let masks = levels
         .iter()
-        .map(|task| {
-            let level = app.tasks[task].args.priority;
-            let tenum = &ctxt.enums[&level];
-            let ready = &ctxt.ready_queues[&level];
-            let dispatcher = &analysis.dispatchers[&level].interrupt;
+        .map(|(level, interrupts)| {
+            let unmasks = interrupts.iter().map(|(name, state)| {
+                if let Some(state) = state {
+                    quote!(#state.unmask(#device::Interrupt::#name);)
+                } else {
+                    quote!(rtfm::export::unmask(#device::Interrupt::#name);)
+                }
+            });
+            let masks = interrupts.iter().map(|(name, state)| {
+                if let Some(state) = state {
+                    quote!(#state.mask(#device::Interrupt::#name);)
+                } else {
+                    quote!(rtfm::export::mask(#device::Interrupt::#name);)
+                }
+            });
 
             quote!(
-                #enum_::#task => {
-                    (#ready { #priority }).claim(|rq| {
-                        rq.split().0.enqueue_unchecked((#tenum::#task, index))
-                    });
-
-                    rtfm::pend(#device::Interrupt::#dispatcher);
+                if current < #level && #level <= ceiling {
+                    if enable {
+                        #(#unmasks)*
+                    } else {
+                        #(#masks)*
+                    }
                 }
             )
         })
         .collect::<Vec<_>>();
 
-    let logical_prio = analysis.timer_queue.priority;
-    items.push(quote!(
-        #[rtfm::export::exception]
-        #[doc(hidden)]
-        unsafe fn SysTick() {
-            use rtfm::Mutex;
+    // Exceptions can't be masked
+    let mut exceptions = app
+        .exceptions
+        .values()
+        .map(|exception| exception.args.priority)
+        .collect::<Vec<_>>();
+    if analysis.timer_queue.is_used() {
+        exceptions.push(analysis.timer_queue.priority);
+    }
+    exceptions.sort();
+    exceptions.dedup();
+    let exceptions = exceptions.iter().map(|level| {
+        quote!(
+            if current < #level && #level <= ceiling {
+                return false;
+            }
+        )
+    });
 
-            let ref #priority = core::cell::Cell::new(#logical_prio);
+    let sources = &ctxt.sources;
+    quote!(
+        #[allow(dead_code)]
+        #[allow(unused_variables)]
+        #[inline(always)]
+        unsafe fn #sources(current: u8, ceiling: u8, enable: bool) -> bool {
+            #(#exceptions)*
 
-            rtfm::export::run(|| {
-                rtfm::export::sys_tick(#tq { #priority }, |task, index| {
-                    match task {
-                        #(#arms)*
-                    }
-                });
-            })
+            #(#masks)*
+
+            true
         }
-    ));
+    )
+}
 
-    quote!(#(#items)*)
+// Allocates the executors of the async tasks on the stack of `main`
+fn executors(ctxt: &mut Context, app: &App) -> proc_macro2::TokenStream {
+    let mut stmts = vec![];
+
+    for (name, task) in &app.tasks {
+        if !task.is_async {
+            continue;
+        }
+
+        let local = ctxt.hygiene.mk_ident(&format!("{}::executor", name));
+        let constructor = async_constructor(ctxt, app, name);
+        let executor = &ctxt.executors[name];
+        let header = &ctxt.headers[name];
+
+        stmts.push(quote!(
+            let mut #local = rtfm::export::executor(#constructor, &#header);
+            unsafe { #executor = &mut #local as *mut _ as *mut (); }
+        ));
+    }
+
+    quote!(#(#stmts)*)
 }
 
-fn pre_init(ctxt: &Context, analysis: &Analysis) -> proc_macro2::TokenStream {
+fn pre_init(ctxt: &Context, app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
     let mut exprs = vec![];
 
+    // Move the device peripherals that are used as resources into their `static` variables
+    let peripherals = app
+        .resources
+        .iter()
+        .filter(|(_, res)| res.peripheral)
+        .map(|(name, _)| {
+            let alias = &ctxt.resources[name];
+            quote!(#alias.set(device.#name);)
+        })
+        .collect::<Vec<_>>();
+    if !peripherals.is_empty() {
+        let device = &app.args.device;
+        exprs.push(quote!(
+            let device = #device::Peripherals::steal();
+            #(#peripherals)*
+        ));
+    }
+
+    // Re-initialize the retained resources whose value didn't survive the reset; the key changes
+    // whenever the declaration of the resource changes
+    for (name, tag) in &ctxt.retentions {
+        let res = &app.resources[name];
+        let alias = &ctxt.resources[name];
+        let (ty, expr) = (&res.ty, &res.expr);
+        let key = fnv1a(
+            FNV_OFFSET_BASIS,
+            quote!(#name: #ty = #expr).to_string().as_bytes(),
+        );
+        let key = (key ^ (key >> 32)) as u32;
+        exprs.push(quote!(
+            if !#tag.is_valid::<#ty>(#key) {
+                core::ptr::write(&mut #alias, #expr);
+                #tag.set::<#ty>(#key);
+            }
+        ));
+    }
+
     // FIXME(MaybeUninit) Because we are using a fake MaybeUninit we need to set the Option tag to
     // Some; otherwise the get_ref and get_mut could result in UB. Also heapless collections can't
     // be constructed in const context; we have to initialize them at runtime (i.e. here).
@@ -1456,9 +4171,28 @@ fn pre_init(ctxt: &Context, analysis: &Analysis) -> proc_macro2::TokenStream {
         exprs.push(quote!(#inputs.set(core::mem::uninitialized());))
     }
 
-    // these are `MaybeUninit` `ReadyQueue`s
-    for queue in ctxt.ready_queues.values() {
-        exprs.push(quote!(#queue.set(rtfm::export::ReadyQueue::new());))
+    // this is a `MaybeUninit` `Edf`
+    if analysis
+        .dispatchers
+        .values()
+        .any(|dispatcher| dispatcher.policy == Policy::Deadline)
+    {
+        let edf = &ctxt.edf;
+        exprs.push(quote!(#edf.set(rtfm::export::Edf::new());))
+    }
+
+    // these are `MaybeUninit` `ReadyQueue`s or tuples of `TaskQueue`s
+    for (level, queue) in &ctxt.ready_queues {
+        let dispatcher = &analysis.dispatchers[level];
+        if dispatcher.policy == Policy::Fifo {
+            exprs.push(quote!(#queue.set(rtfm::export::ReadyQueue::new());))
+        } else {
+            let news = dispatcher
+                .tasks
+                .iter()
+                .map(|_| quote!(rtfm::export::TaskQueue::new()));
+            exprs.push(quote!(#queue.set((#(#news,)*));))
+        }
     }
 
     // these are `MaybeUninit` `FreeQueue`s
@@ -1469,11 +4203,21 @@ fn pre_init(ctxt: &Context, analysis: &Analysis) -> proc_macro2::TokenStream {
     // end-of-FIXME
 
     // Initialize the timer queue
-    if !analysis.timer_queue.tasks.is_empty() {
+    if analysis.timer_queue.is_used() {
         let tq = &ctxt.timer_queue;
         exprs.push(quote!(#tq.set(rtfm::export::TimerQueue::new(p.SYST));));
     }
 
+    // Populate the memory pools
+    for (pool, nodes) in &ctxt.pool_nodes {
+        let stack = &ctxt.pool_stacks[pool];
+        exprs.push(quote!(
+            for node in #nodes.iter_mut() {
+                #stack.push(core::ptr::NonNull::from(node));
+            }
+        ))
+    }
+
     // Populate the `FreeQueue`s
     for (task, alias) in &ctxt.free_queues {
         let capacity = analysis.capacities[task];
@@ -1484,6 +4228,13 @@ fn pre_init(ctxt: &Context, analysis: &Analysis) -> proc_macro2::TokenStream {
         ))
     }
 
+    // Keep the symbol table in the final binary
+    #[cfg(feature = "introspect")]
+    {
+        let symbols = &ctxt.symbols;
+        exprs.push(quote!(core::ptr::read_volatile(&#symbols as *const _ as *const u8);));
+    }
+
     // Set the cycle count to 0 and disable it while `init` executes
     if cfg!(feature = "timer-queue") {
         exprs.push(quote!(p.DWT.ctrl.modify(|r| r & !1);));
@@ -1498,9 +4249,132 @@ fn pre_init(ctxt: &Context, analysis: &Analysis) -> proc_macro2::TokenStream {
     )
 }
 
+// Stable procedural macros can't emit warnings so we report the unused parts of the specification
+// as uses of deprecated items; the warnings point to the unused items
+fn warnings(app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
+    let mut warnings = vec![];
+
+    let used = app
+        .resource_accesses()
+        .map(|(_, res)| res)
+        .chain(&app.init.args.resources)
+        .collect::<Set<_>>();
+    for (name, res) in &app.resources {
+        if !used.contains(name) && !allows_unused(&res.attrs) {
+            warnings.push((
+                name,
+                "unused_resource",
+                format!("resource `{}` is never used", name),
+            ));
+        }
+    }
+
+    for (name, task) in &app.dead_tasks {
+        if !allows_unused(&task.attrs) {
+            warnings.push((
+                name,
+                "unused_task",
+                format!(
+                    "task `{}` is never spawned or scheduled; it will never run",
+                    name
+                ),
+            ));
+        }
+    }
+
+    for (name, fi) in &app.free_interrupts {
+        if analysis
+            .dispatchers
+            .values()
+            .all(|dispatcher| dispatcher.interrupt.as_ref() != Some(name))
+            && !allows_unused(&fi.attrs)
+        {
+            warnings.push((
+                name,
+                "unused_interrupt",
+                format!("free interrupt `{}` doesn't dispatch any task", name),
+            ));
+        }
+    }
+
+    let published = app.publish_calls().map(|(_, topic)| topic).collect::<Set<_>>();
+    for (name, topic) in &app.topics {
+        if allows_unused(&topic.attrs) {
+            continue;
+        }
+
+        if !published.contains(name) {
+            warnings.push((
+                name,
+                "unused_topic",
+                format!("topic `{}` is never published to", name),
+            ));
+        } else if app.subscribers(name).is_empty() {
+            warnings.push((
+                name,
+                "unused_topic",
+                format!("topic `{}` has no subscribers", name),
+            ));
+        }
+    }
+
+    // There's no stable way for a procedural macro to emit a warning so each one is reported as
+    // the use of a deprecated item, spanned to the unused item; the note carries the actual problem
+    // and how to silence it
+    let stmts = warnings.into_iter().map(|(name, kind, problem)| {
+        let def = Ident::new(kind, Span::call_site());
+        let use_ = Ident::new(kind, name.span());
+        let note = format!(
+            "{}; add `#[allow(dead_code)]` to `{}` to silence this warning",
+            problem, name
+        );
+
+        quote!({
+            #[deprecated(note = #note)]
+            #[allow(non_camel_case_types)]
+            struct #def;
+
+            let _ = #use_;
+        })
+    });
+
+    quote!(#(#stmts)*)
+}
+
+// Whether the item has an `#[allow(dead_code)]` or `#[allow(unused)]` attribute
+fn allows_unused(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path.is_ident("allow")
+            && attr.tts.clone().into_iter().any(|tt| match tt {
+                TokenTree::Group(group) => group.stream().into_iter().any(|tt| match tt {
+                    TokenTree::Ident(ident) => ident == "dead_code" || ident == "unused",
+                    _ => false,
+                }),
+                _ => false,
+            })
+    })
+}
+
 fn assertions(app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
     let mut items = vec![];
 
+    // The overrun monitor runs one level above every task; the device must support that level
+    if !analysis.timer_queue.budgets.is_empty() {
+        let device = &app.args.device;
+        let priority = analysis.timer_queue.priority;
+        let message = format!(
+            "the overrun monitor runs at priority {}, one level above the highest priority task, \
+             but the device doesn't support priorities higher than `1 << NVIC_PRIO_BITS`",
+            priority
+        );
+        items.push(quote!({
+            const _: () = assert!(
+                (#priority as u16) <= (1u16 << #device::NVIC_PRIO_BITS),
+                #message
+            );
+        }));
+    }
+
     for ty in &analysis.needs_sync {
         items.push(quote!(rtfm::export::assert_sync::<#ty>()));
     }
@@ -1510,6 +4384,16 @@ fn assertions(app: &App, analysis: &Analysis) -> proc_macro2::TokenStream {
         items.push(quote!(rtfm::export::assert_send::<#ty>()));
     }
 
+    for topic in &analysis.needs_clone {
+        items.push(quote!(rtfm::export::assert_clone::<#topic>()));
+    }
+
+    // retained values are restored by reinterpreting RAM; only plain data survives that
+    for res in app.resources.values().filter(|res| res.retain) {
+        let ty = &res.ty;
+        items.push(quote!(rtfm::export::assert_copy::<#ty>()));
+    }
+
     quote!(#(#items;)*)
 }
 
@@ -1523,6 +4407,7 @@ fn mk_resource(
     module: Option<&mut Vec<proc_macro2::TokenStream>>,
 ) -> proc_macro2::TokenStream {
     let priority = &ctxt.priority;
+    let sources = &ctxt.sources;
     let device = &app.args.device;
 
     let mut items = vec![];
@@ -1563,12 +4448,295 @@ fn mk_resource(
             fn ptr(&self) -> *mut Self::Data {
                 unsafe { #ptr }
             }
+
+            #[inline(always)]
+            unsafe fn sources(&self, current: u8, ceiling: u8, enable: bool) -> bool {
+                #sources(current, ceiling, enable)
+            }
         }
     ));
 
     quote!(#(#items)*)
 }
 
+/// Pends the dispatcher of the priority `level`
+fn pend_dispatcher(
+    ctxt: &Context,
+    app: &App,
+    analysis: &Analysis,
+    level: u8,
+) -> proc_macro2::TokenStream {
+    if let Some(interrupt) = analysis.dispatchers[&level].interrupt.as_ref() {
+        let device = &app.args.device;
+        quote!(rtfm::export::pend(#device::Interrupt::#interrupt))
+    } else {
+        let background_level = &ctxt.background_level;
+        quote!(#background_level.pend())
+    }
+}
+
+/// Takes a slot from the free queue `f` of `task`
+#[cfg_attr(not(feature = "introspect"), allow(unused_variables))]
+fn free_dequeue(ctxt: &Context, task: &Ident) -> proc_macro2::TokenStream {
+    match () {
+        #[cfg(feature = "introspect")]
+        () => {
+            let stats = &ctxt.stats[task];
+            quote!({
+                let index = f.split().1.dequeue();
+                if index.is_some() {
+                    #stats.enqueue();
+                }
+                index
+            })
+        }
+        #[cfg(not(feature = "introspect"))]
+        () => quote!(f.split().1.dequeue()),
+    }
+}
+
+/// Pointer to the baseline of the instance `index` of `task`
+fn scheduled(ctxt: &Context, task: &Ident) -> proc_macro2::TokenStream {
+    match () {
+        #[cfg(feature = "timer-queue")]
+        () => {
+            let scheduleds = &ctxt.scheduleds[task];
+            quote!(#scheduleds.get_mut().get_unchecked_mut(usize::from(index)))
+        }
+        // `deadline` and `slots` are rejected by the parser when the `timer-queue` feature is
+        // disabled
+        #[cfg(not(feature = "timer-queue"))]
+        () => {
+            let _ = (ctxt, task);
+            unreachable!()
+        }
+    }
+}
+
+/// Makes instance `index` of `task` ready; returns the statements that enqueue it and that pend
+/// its dispatcher
+///
+/// For tasks dispatched earliest deadline first the scheduler decides which dispatcher to pend, if
+/// any, so both happen in the first statement
+fn make_ready(
+    ctxt: &Context,
+    app: &App,
+    analysis: &Analysis,
+    task: &Ident,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let priority = &ctxt.priority;
+    let level = app.tasks[task].args.priority;
+
+    if let Some(deadline) = app.tasks[task].args.deadline.as_ref() {
+        let edf = &ctxt.edf;
+        let edf_enum = &ctxt.edf_enum;
+        let edf_pend = &ctxt.edf_pend;
+        let scheduled = scheduled(ctxt, task);
+        let stats_enqueue = match () {
+            #[cfg(feature = "introspect")]
+            () => {
+                let stats = &ctxt.ready_stats[&level];
+                quote!(#stats.enqueue();)
+            }
+            #[cfg(not(feature = "introspect"))]
+            () => quote!(),
+        };
+
+        // the absolute deadline is relative to the baseline of the instance
+        (
+            quote!(
+                let deadline = core::ptr::read(#scheduled) + rtfm::U32Ext::cycles(#deadline);
+                if let Some(level) = rtfm::export::interrupt::free(|_| {
+                    #stats_enqueue
+                    #edf.get_mut().release(deadline, #level, index, #edf_enum::#task)
+                }) {
+                    #edf_pend(level);
+                }
+            ),
+            quote!(),
+        )
+    } else {
+        let ready = &ctxt.ready_queues[&level];
+        let ready_enqueue = ready_enqueue(ctxt, level, task);
+        let pend = pend_dispatcher(ctxt, app, analysis, level);
+
+        (
+            quote!((#ready { #priority }).claim(|rq| #ready_enqueue);),
+            quote!(#pend;),
+        )
+    }
+}
+
+/// Pushes `task` (and `index`) onto the ready queue `rq` of priority `level`
+fn ready_enqueue(ctxt: &Context, level: u8, task: &Ident) -> proc_macro2::TokenStream {
+    let enqueue = if let Some(slot) = ctxt.ready_slots.get(task) {
+        let slot = Index::from(*slot);
+        quote!(rq.#slot.split().0.enqueue_unchecked(index))
+    } else {
+        let enum_ = &ctxt.enums[&level];
+        quote!(rq.split().0.enqueue_unchecked((#enum_::#task, index)))
+    };
+
+    match () {
+        #[cfg(feature = "introspect")]
+        () => {
+            let stats = &ctxt.ready_stats[&level];
+            quote!({
+                #enqueue;
+                #stats.enqueue();
+            })
+        }
+        #[cfg(not(feature = "introspect"))]
+        () => enqueue,
+    }
+}
+
+/// Closure that creates the future of the async `task` from its inputs
+///
+/// Only its type is relevant: it's used to recover the type of the executor (see
+/// `rtfm::export::executor_ref`)
+fn async_constructor(ctxt: &Context, app: &App, task: &Ident) -> proc_macro2::TokenStream {
+    let alias = &ctxt.tasks[task];
+    let inputs = &app.tasks[task].inputs;
+    let ty = tuple_ty(inputs);
+    let pats = tuple_pat(inputs);
+
+    match () {
+        #[cfg(feature = "timer-queue")]
+        () => quote!(|(baseline, input): (rtfm::Instant, #ty)| {
+            let (#pats) = input;
+            #alias(baseline, #pats)
+        }),
+        #[cfg(not(feature = "timer-queue"))]
+        () => quote!(|input: #ty| {
+            let (#pats) = input;
+            #alias(#pats)
+        }),
+    }
+}
+
+/// Polls the future held by `executor`, which runs `task`, and releases its slot on completion
+fn async_poll(ctxt: &Context, task: &Ident) -> proc_macro2::TokenStream {
+    let free = &ctxt.free_queues[task];
+    let poll = measure(ctxt, task, quote!(done = executor.poll()));
+
+    quote!(
+        let mut done = None;
+        #poll;
+        if let Some(index) = done {
+            #free.get_mut().split().0.enqueue_unchecked(index);
+        }
+    )
+}
+
+/// Measures the execution time of `body`, which runs `task`
+#[cfg_attr(not(feature = "wcet"), allow(unused_variables))]
+fn measure(
+    ctxt: &Context,
+    task: &Ident,
+    body: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let body = track(ctxt, task, body);
+
+    match () {
+        #[cfg(feature = "wcet")]
+        () => {
+            let times = &ctxt.times[task];
+            quote!({
+                let measurement = rtfm::wcet::start();
+                #body;
+                rtfm::wcet::stop(measurement, &#times);
+            })
+        }
+        #[cfg(not(feature = "wcet"))]
+        () => body,
+    }
+}
+
+// Records `task` as the running context while `body` runs
+#[cfg_attr(not(feature = "crash-report"), allow(unused_variables))]
+fn track(ctxt: &Context, task: &Ident, body: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    match () {
+        #[cfg(feature = "crash-report")]
+        () => {
+            let id = ctxt.crash_ids[task];
+            quote!({
+                let previous = rtfm::crash::enter(#id);
+                #body;
+                rtfm::crash::set(previous);
+            })
+        }
+        #[cfg(not(feature = "crash-report"))]
+        () => body,
+    }
+}
+
+// The error type of `spawn.<task>`; sporadic tasks tell a release that came too soon apart from a
+// full task queue
+fn spawn_error(app: &App, task: &Ident) -> proc_macro2::TokenStream {
+    let task = app.task(task);
+    let ty = tuple_ty(&task.inputs);
+
+    if task.args.min_inter_arrival.is_some() {
+        quote!(rtfm::ReleaseError<#ty>)
+    } else {
+        ty
+    }
+}
+
+// Runs the task within its execution budget, if it has one
+fn monitor(
+    ctxt: &Context,
+    task: &Ident,
+    body: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if let Some(monitor) = ctxt.monitors.get(task) {
+        quote!(#monitor(|| #body))
+    } else {
+        body
+    }
+}
+
+// Records the completion of the task, if it has a heartbeat
+fn heartbeat(
+    ctxt: &Context,
+    task: &Ident,
+    body: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if let Some(heartbeat) = ctxt.heartbeats.get(task) {
+        quote!({
+            #body;
+            #heartbeat.beat();
+        })
+    } else {
+        body
+    }
+}
+
+// Checks the completion of the task against the end of its slot, if it's time-triggered
+fn slot_end(
+    ctxt: &Context,
+    task: &Ident,
+    body: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if let Some(slots) = ctxt.table_slots.get(task) {
+        quote!({
+            #body;
+            #slots.complete();
+        })
+    } else {
+        body
+    }
+}
+
+#[cfg(feature = "introspect")]
+fn mk_stats(alias: &Ident, symbol: &str, capacity: u8) -> proc_macro2::TokenStream {
+    quote!(
+        #[export_name = #symbol]
+        static #alias: rtfm::introspect::Stats = rtfm::introspect::Stats::new(#capacity);
+    )
+}
+
 fn mk_capacity_literal(capacity: u8) -> LitInt {
     LitInt::new(u64::from(capacity), IntSuffix::None, Span::call_site())
 }
@@ -1587,46 +4755,93 @@ fn mk_typenum_capacity(capacity: u8, power_of_two: bool) -> proc_macro2::TokenSt
     quote!(rtfm::export::consts::#ident)
 }
 
-fn mk_ident() -> Ident {
-    static CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Generator of pseudo-hygienic identifiers
+///
+/// Identifiers are derived from a hash of the application contents (the names of its resources and
+/// tasks) and a per-identifier key so the expansion of `#[app]` is the same on every build
+struct Hygiene {
+    seed: u64,
+    used: Set<String>,
+}
+
+impl Hygiene {
+    fn new(app: &App) -> Self {
+        let device = &app.args.device;
+
+        let mut names = app
+            .resources
+            .keys()
+            .map(|name| format!("static {}", name))
+            .chain(app.tasks.keys().map(|name| format!("task {}", name)))
+            .chain(app.interrupts.keys().map(|name| format!("interrupt {}", name)))
+            .chain(app.exceptions.keys().map(|name| format!("exception {}", name)))
+            .chain(app.free_interrupts.keys().map(|name| format!("extern {}", name)))
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let mut seed = fnv1a(FNV_OFFSET_BASIS, quote!(#device).to_string().as_bytes());
+        for name in names {
+            seed = fnv1a(seed, name.as_bytes());
+        }
+
+        Hygiene {
+            seed,
+            used: Set::default(),
+        }
+    }
 
-    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    fn mk_ident(&mut self, key: &str) -> Ident {
+        let mut state = fnv1a(self.seed, key.as_bytes());
 
-    let secs = elapsed.as_secs();
-    let nanos = elapsed.subsec_nanos();
+        loop {
+            let ident = (0..16)
+                .map(|i| {
+                    state = xorshift(state);
 
-    let count = CALL_COUNT.fetch_add(1, Ordering::SeqCst) as u32;
-    let mut seed: [u8; 16] = [0; 16];
+                    if i == 0 || state & 1 == 0 {
+                        (b'a' + ((state >> 8) % 25) as u8) as char
+                    } else {
+                        (b'0' + ((state >> 8) % 10) as u8) as char
+                    }
+                })
+                .collect::<String>();
 
-    for (i, v) in seed.iter_mut().take(8).enumerate() {
-        *v = ((secs >> (i * 8)) & 0xFF) as u8
+            // on the (unlikely) event of a collision we keep drawing from the same sequence
+            if self.used.insert(ident.clone()) {
+                return Ident::new(&ident, Span::call_site());
+            }
+        }
     }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
 
-    for (i, v) in seed.iter_mut().skip(8).take(4).enumerate() {
-        *v = ((nanos >> (i * 8)) & 0xFF) as u8
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
     }
 
-    for (i, v) in seed.iter_mut().skip(12).enumerate() {
-        *v = ((count >> (i * 8)) & 0xFF) as u8
+    // separator; avoids `("ab", "c")` and `("a", "bc")` hashing to the same value
+    (hash ^ 0xff).wrapping_mul(FNV_PRIME)
+}
+
+fn xorshift(mut x: u64) -> u64 {
+    // the all-zeros state is a fixed point
+    if x == 0 {
+        x = FNV_OFFSET_BASIS;
     }
 
-    let mut rng = rand::rngs::SmallRng::from_seed(seed);
-    Ident::new(
-        &(0..16)
-            .map(|i| {
-                if i == 0 || rng.gen() {
-                    ('a' as u8 + rng.gen::<u8>() % 25) as char
-                } else {
-                    ('0' as u8 + rng.gen::<u8>() % 10) as char
-                }
-            })
-            .collect::<String>(),
-        Span::call_site(),
-    )
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    x
 }
 
 // `once = true` means that these locals will be called from a function that will run *once*
-fn mk_locals(locals: &HashMap<Ident, Static>, once: bool) -> proc_macro2::TokenStream {
+fn mk_locals(locals: &Map<Ident, Static>, once: bool) -> proc_macro2::TokenStream {
     let lt = if once { Some(quote!('static)) } else { None };
 
     let locals = locals
@@ -1680,6 +4895,7 @@ enum Kind {
     Idle,
     Init,
     Interrupt(Ident),
+    PostInit,
     Task(Ident),
 }
 
@@ -1688,12 +4904,17 @@ impl Kind {
         match self {
             Kind::Init => Ident::new("init", Span::call_site()),
             Kind::Idle => Ident::new("idle", Span::call_site()),
+            Kind::PostInit => Ident::new("post_init", Span::call_site()),
             Kind::Task(name) | Kind::Interrupt(name) | Kind::Exception(name) => name.clone(),
         }
     }
 
-    fn is_idle(&self) -> bool {
-        *self == Kind::Idle
+    /// `idle` and `post_init` run at priority 0 with interrupts enabled; they have no baseline
+    fn is_background(&self) -> bool {
+        match *self {
+            Kind::Idle | Kind::PostInit => true,
+            _ => false,
+        }
     }
 
     fn is_init(&self) -> bool {
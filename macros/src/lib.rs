@@ -4,7 +4,6 @@
 extern crate proc_macro;
 extern crate proc_macro2;
 extern crate quote;
-extern crate rand;
 extern crate syn;
 
 use proc_macro::TokenStream;
@@ -28,6 +27,20 @@ mod syntax;
 ///
 /// [`svd2rust`]: https://crates.io/crates/svd2rust
 ///
+/// And the following optional arguments:
+///
+/// - `frame = <integer>`. The length of the *major frame* of the schedule table, in clock cycles.
+/// The value must be in the range `1..=2147483647`. See [`#[task]`](#e-task) for details.
+/// *IMPORTANT*: This argument is accepted only if the `timer-queue` feature has been enabled.
+///
+/// - `modes = [Mode_A, Mode_B, ..]`. The operating modes of the application; each mode releases its
+/// own subset of the tasks of the schedule table. The first mode is the initial one. This argument
+/// generates a `Mode` enum with one variant per mode. See [`#[task]`](#e-task) for details.
+/// *IMPORTANT*: This argument is accepted only if the `timer-queue` feature has been enabled.
+///
+/// - `round_robin = [<integer>, ..]`. A list of priority levels whose software tasks will be
+/// dispatched in round-robin fashion. See [`#[task]`](#e-task) for details.
+///
 /// The items allowed in the block value of the `const` item are specified below:
 ///
 /// # 1. `static [mut]` variables
@@ -57,10 +70,48 @@ mod syntax;
 ///
 /// [`Mutex`]: ../rtfm/trait.Mutex.html
 ///
+/// Device peripherals can also be used as resources. To do so list them as `device::<NAME>` in the
+/// `resources` argument of a function, e.g. `resources = [device::USART1]`; the other functions
+/// that access the peripheral can list it either as `device::USART1` or as `USART1`. The peripheral
+/// is moved out of the device `Peripherals` before `init` runs and then it behaves like a
+/// `static mut` resource of type `<device-path>::USART1`: it can be owned by one task or shared
+/// between several of them, in which case lower priority tasks need a `claim` to access it.
+///
+/// `static mut` resources that have the `#[retain]` attribute keep their value across resets
+/// (e.g. watchdog resets or `SCB::sys_reset`). They are placed in the `.uninit` section, which
+/// is not initialized on reset, next to a tag that's derived from their declaration and from the
+/// size and alignment of their type. Before `init` runs the tag is validated: if it doesn't match,
+/// e.g. on a cold boot or after a firmware update that changed the declaration or the layout of the
+/// type, the resource is set to its declared initial value. Otherwise they behave like any other
+/// resource: they can be owned by a task or shared between tasks. The type of a retained resource
+/// must implement `Copy`, i.e. be plain data, and retained resources can't be late resources or
+/// singletons. **NOTE**: the contents of the resource are not checksummed; a reset that happens
+/// in the middle of an update preserves the partial update.
+///
+/// `static` variables that have the `#[pool]` attribute are *memory pools*, not resources. The
+/// `app` attribute turns each of them into a type with the same name; `<name>::alloc()` claims a
+/// block of the pool and returns it as a `rtfm::pool::Box<name>`. Each block is initialized to the
+/// value of the `static`. A `Box` can be sent to software tasks without copying the block, and
+/// the block returns to the pool when the `Box` is dropped. See the [`rtfm::pool`] module.
+///
+/// [`rtfm::pool`]: ../rtfm/pool/index.html
+///
+/// The `pool` attribute accepts one optional argument:
+///
+/// - `capacity = <integer>`. The number of blocks of the pool. Each instance of a software task
+/// that takes a `Box` of the pool as input holds on to one block per `Box` while it's queued and
+/// while it runs, and a resource holds on to its blocks at all times; the capacity must be at least
+/// the sum of all those blocks. If this argument is omitted the capacity is set to that number.
+/// Only the inputs and the resources whose types are `Box<P>`, tuples, arrays and `Option`s of
+/// it, or topics of those types, are counted; any other type that contains `P` (e.g.
+/// `heapless::Vec<Box<P>, U8>`) is rejected. **NOTE**: blocks held by local variables, e.g. a
+/// `Box` that `init` keeps around, are not counted so this number is a lower bound.
+///
 /// # 2. `fn`
 ///
 /// Functions must contain *one* of the following attributes: `init`, `idle`, `interrupt`,
-/// `exception` or `task`. The attribute defines the role of the function in the application.
+/// `exception`, `task`, `pre_init`, `post_init`, `overrun` or `stalled`. The attribute defines the
+/// role of the function in the application.
 ///
 /// ## a. `#[init]`
 ///
@@ -69,10 +120,20 @@ mod syntax;
 /// signature of the `init` function must be `[unsafe] fn ()`.
 ///
 /// The `init` function runs after memory (RAM) is initialized and runs with interrupts disabled.
-/// Interrupts are re-enabled after `init` returns.
+/// Interrupts are re-enabled after `init` returns. Initialization steps that need interrupts can be
+/// moved into a [`post_init`](#g-post_init) function.
 ///
 /// The `init` attribute accepts the following optional arguments:
 ///
+/// - `controls = [Handler_A, Handler_B, ..]`. This is the list of `#[interrupt]` handlers that this
+/// function can enable, disable and pend at runtime.
+///
+/// - `pend = [Handler_A, Handler_B, ..]`. This is the list of `#[interrupt]` handlers that this
+/// function can pend. The interrupts listed in the `extern` block can't be pended.
+///
+/// - `publish = [Topic_A, Topic_B, ..]`. This is the list of [topics](#4-topic) this function can
+/// publish messages to.
+///
 /// - `resources = [RESOURCE_A, RESOURCE_B, ..]`. This is the list of resources this function has
 /// access to.
 ///
@@ -92,7 +153,8 @@ mod syntax;
 /// [`rtfm::Peripherals`]: ../rtfm/struct.Peripherals.html
 ///
 /// - `device: <device-path>::Peripherals`. Exclusive access to device-specific peripherals.
-/// `<device-path>` is the path to the device crate declared in the top `app` attribute.
+/// `<device-path>` is the path to the device crate declared in the top `app` attribute. The
+/// peripherals that are used as resources (see below) have been moved out of this `struct`.
 ///
 /// - `start: rtfm::Instant`. The `start` time of the system: `Instant(0 /* cycles */)`. **NOTE**:
 /// only present if the `timer-queue` feature is enabled.
@@ -106,6 +168,26 @@ mod syntax;
 ///
 /// - `spawn: init::Spawn`. A `struct` that can be used to spawn *software* tasks.
 ///
+/// - `controls: init::Controls`. A `struct` that contains one [`rtfm::Control`] handle per handler
+/// listed in the `controls` argument. Disabling a handler in `init` keeps it disabled after `init`
+/// returns.
+///
+/// [`rtfm::Control`]: ../rtfm/struct.Control.html
+///
+/// - `pend: init::Pend`. A `struct` with one method per handler listed in the `pend` argument;
+/// e.g. `pend.UART0()` pends the interrupt the `UART0` handler is bound to.
+///
+/// - `publish: init::Publish`. A `struct` with one method per topic listed in the `publish`
+/// argument, named after the topic in snake case; e.g. `publish.imu(sample)` publishes `sample` to
+/// the `Imu` topic.
+///
+/// - `mode: ModeSwitch`. Only present if the application declares operating modes (see
+/// `#[app].modes`). `mode.switch(Mode::B)` requests a switch to the mode `B`; the switch takes
+/// effect at the end of the current major frame of the schedule table.
+///
+/// - `crash: Option<rtfm::crash::Report>`. The report of the crash that caused the last reset, if
+/// any. **NOTE**: only present if the `crash-report` feature is enabled.
+///
 /// Other properties / constraints:
 ///
 /// - The `init` function can **not** be called from software.
@@ -125,12 +207,20 @@ mod syntax;
 ///
 /// The `idle` task is a special task that always runs in the background. The `idle` task runs at
 /// the lowest priority of `0`. If the `idle` task is not defined then the runtime sets the
-/// [SLEEPONEXIT] bit after executing `init`.
+/// [SLEEPONEXIT] bit after executing `init`, unless the application has background tasks (see
+/// [`#[task].priority`](#e-task)); in that case the runtime provides an idle loop that sleeps until
+/// a background task is spawned and then runs it.
 ///
 /// [SLEEPONEXIT]: https://developer.arm.com/products/architecture/cpu-architecture/m-profile/docs/100737/0100/power-management/sleep-mode/sleep-on-exit-bit
 ///
 /// The `idle` attribute accepts the following optional arguments:
 ///
+/// - `controls = (..)`. Same meaning / function as [`#[init].controls`](#a-init).
+///
+/// - `pend = (..)`. Same meaning / function as [`#[init].pend`](#a-init).
+///
+/// - `publish = (..)`. Same meaning / function as [`#[init].publish`](#a-init).
+///
 /// - `resources = (..)`. Same meaning / function as [`#[init].resources`](#a-init).
 ///
 /// - `schedule = (..)`. Same meaning / function as [`#[init].schedule`](#a-init).
@@ -146,6 +236,20 @@ mod syntax;
 ///
 /// - `spawn: idle::Spawn`. Same meaning / function as [`init.spawn`](#a-init).
 ///
+/// - `controls: idle::Controls`. Same meaning / function as [`init.controls`](#a-init).
+///
+/// - `pend: idle::Pend`. Same meaning / function as [`init.pend`](#a-init).
+///
+/// - `publish: idle::Publish`. Same meaning / function as [`init.publish`](#a-init).
+///
+/// - `mode: ModeSwitch`. Same meaning / function as [`init.mode`](#a-init).
+///
+/// If the application has background tasks then `resources` is always present and has a
+/// `run_background(&mut self)` method that runs the pending background tasks; `idle` must call it
+/// to let them run. The resources that `idle` shares with background tasks are handed out as
+/// `&mut` references, instead of `&'static mut` references, and can't be held across a call to
+/// `run_background`.
+///
 /// Other properties / constraints:
 ///
 /// - The `idle` function can **not** be called from software.
@@ -161,17 +265,35 @@ mod syntax;
 ///
 /// The `exception` attribute accepts the following optional arguments.
 ///
+/// - `binds = <exception-name>`. The exception this handler is bound to. If this argument is
+/// omitted the function must be named after the exception (e.g. `fn SVCall()`). Each exception can
+/// be bound to at most one handler.
+///
 /// - `priority = <integer>`. This is the static priority of the exception handler. The value must
 /// be in the range `1..=(1 << <device-path>::NVIC_PRIO_BITS)` where `<device-path>` is the path to
 /// the device crate declared in the top `app` attribute. If this argument is omitted the priority
 /// is assumed to be 1.
 ///
+/// - `controls = (..)`. Same meaning / function as [`#[init].controls`](#a-init).
+///
+/// - `pend = (..)`. Same meaning / function as [`#[init].pend`](#a-init).
+///
+/// - `publish = (..)`. Same meaning / function as [`#[init].publish`](#a-init).
+///
 /// - `resources = (..)`. Same meaning / function as [`#[init].resources`](#a-init).
 ///
 /// - `schedule = (..)`. Same meaning / function as [`#[init].schedule`](#a-init).
 ///
 /// - `spawn = (..)`. Same meaning / function as [`#[init].spawn`](#a-init).
 ///
+/// - `wcet = <integer>`. The execution budget of this handler, in clock cycles. The value must be
+/// in the range `1..=2147483647`. If the handler is still running when its budget expires, counting
+/// from the start of the handler and including the time it spends preempted, the
+/// [`#[overrun]`](#h-overrun) handler is invoked. Monitoring budgets raises the priority of the
+/// timer queue above every task, which adds latency to all of them; see
+/// [`#[overrun]`](#h-overrun). *IMPORTANT*: This argument is accepted only if the `timer-queue`
+/// feature has been enabled.
+///
 /// The `app` attribute will injected a *context* into this function that comprises the following
 /// variables:
 ///
@@ -180,9 +302,17 @@ mod syntax;
 ///
 /// - `resources: _`. Same meaning / function as [`init.resources`](#a-init).
 ///
-/// - `schedule: <exception-name>::Schedule`. Same meaning / function as [`init.schedule`](#a-init).
+/// - `schedule: <function-name>::Schedule`. Same meaning / function as [`init.schedule`](#a-init).
+///
+/// - `spawn: <function-name>::Spawn`.  Same meaning / function as [`init.spawn`](#a-init).
+///
+/// - `controls: <function-name>::Controls`. Same meaning / function as [`init.controls`](#a-init).
+///
+/// - `pend: <function-name>::Pend`. Same meaning / function as [`init.pend`](#a-init).
+///
+/// - `publish: <function-name>::Publish`. Same meaning / function as [`init.publish`](#a-init).
 ///
-/// - `spawn: <exception-name>::Spawn`.  Same meaning / function as [`init.spawn`](#a-init).
+/// - `mode: ModeSwitch`. Same meaning / function as [`init.mode`](#a-init).
 ///
 /// Other properties / constraints:
 ///
@@ -199,14 +329,29 @@ mod syntax;
 ///
 /// The `interrupt` attribute accepts the following optional arguments.
 ///
+/// - `binds = <interrupt-name>`. Same meaning / function as [`#[exception].binds`](#b-exception).
+/// The interrupt must not be listed in the `extern` block.
+///
+/// - `enabled = <bool>`. Whether the interrupt is enabled after `init` returns. If this argument
+/// is omitted the interrupt is enabled. A disabled interrupt can be enabled at runtime through its
+/// `Control` handle (see `controls`). This argument is not accepted by `exception` handlers.
+///
 /// - `priority = (..)`. Same meaning / function as [`#[exception].priority`](#b-exception).
 ///
+/// - `controls = (..)`. Same meaning / function as [`#[init].controls`](#a-init).
+///
+/// - `pend = (..)`. Same meaning / function as [`#[init].pend`](#a-init).
+///
+/// - `publish = (..)`. Same meaning / function as [`#[init].publish`](#a-init).
+///
 /// - `resources = (..)`. Same meaning / function as [`#[init].resources`](#a-init).
 ///
 /// - `schedule = (..)`. Same meaning / function as [`#[init].schedule`](#a-init).
 ///
 /// - `spawn = (..)`. Same meaning / function as [`#[init].spawn`](#a-init).
 ///
+/// - `wcet = <integer>`. Same meaning / function as [`#[exception].wcet`](#b-exception).
+///
 /// The `app` attribute will injected a *context* into this function that comprises the following
 /// variables:
 ///
@@ -214,16 +359,22 @@ mod syntax;
 ///
 /// - `resources: _`. Same meaning / function as [`init.resources`](#a-init).
 ///
-/// - `schedule: <interrupt-name>::Schedule`. Same meaning / function as [`init.schedule`](#a-init).
+/// - `schedule: <function-name>::Schedule`. Same meaning / function as [`init.schedule`](#a-init).
 ///
-/// - `spawn: <interrupt-name>::Spawn`.  Same meaning / function as [`init.spawn`](#a-init).
+/// - `spawn: <function-name>::Spawn`.  Same meaning / function as [`init.spawn`](#a-init).
 ///
-/// Other properties / constraints:
+/// - `controls: <function-name>::Controls`. Same meaning / function as [`init.controls`](#a-init).
 ///
-/// - `interrupt` handlers can **not** be called from software, but they can be [`pend`]-ed by the
-/// software from any context.
+/// - `pend: <function-name>::Pend`. Same meaning / function as [`init.pend`](#a-init).
+///
+/// - `publish: <function-name>::Publish`. Same meaning / function as [`init.publish`](#a-init).
+///
+/// - `mode: ModeSwitch`. Same meaning / function as [`init.mode`](#a-init).
+///
+/// Other properties / constraints:
 ///
-/// [`pend`]: ../rtfm/fn.pend.html
+/// - `interrupt` handlers can **not** be called from software, but they can be pended by the
+/// software from any context that lists them in its `pend` argument.
 ///
 /// - The `static mut` variables declared at the beginning of this function will be transformed into
 /// `&mut` references that are safe to access. For example, `static mut FOO: u32 = 0` will
@@ -232,22 +383,132 @@ mod syntax;
 /// ## e. `#[task]`
 ///
 /// This attribute indicates that the function is to be used as a *software task*. The signature of
-/// software `task`s must be `[unsafe] fn(<inputs>)`.
+/// software `task`s must be `[async] [unsafe] fn(<inputs>)`. The names `init`, `post_init` and
+/// `idle` are reserved for those contexts and can't be used by software tasks.
 ///
 /// The `task` attribute accepts the following optional arguments.
 ///
 /// - `capacity = <integer>`. The maximum number of instances of this task that can be queued onto
 /// the task scheduler for execution. The value must be in the range `1..=255`. If the `capacity`
-/// argument is omitted then the capacity will be inferred.
+/// argument is omitted then the capacity will be inferred. `async` tasks can't specify a
+/// `capacity`; theirs is always 1.
 ///
-/// - `priority = <integer>`. Same meaning / function as [`#[exception].priority`](#b-exception).
+/// - `deadline = <integer>`. The relative deadline of this task, in clock cycles. The value must be
+/// in the range `1..=2147483647`. If one task at a priority level specifies a `deadline` then all
+/// the tasks at that level must specify one. *IMPORTANT*: This argument is accepted only if the
+/// `timer-queue` feature has been enabled.
+///
+/// - `heartbeat = <integer>`. The longest time, in clock cycles, this task can go without
+/// completing an instance. The value must be in the range `1..=2147483647`. `async` tasks can't
+/// specify a `heartbeat`. *IMPORTANT*: This argument is accepted only if the `timer-queue` feature
+/// has been enabled.
+///
+/// - `min_inter_arrival = <integer>`. The minimum time, in clock cycles, between two releases of
+/// this task. The value must be in the range `1..=2147483647`. *IMPORTANT*: This argument is
+/// accepted only if the `timer-queue` feature has been enabled.
+///
+/// - `modes = [Mode_A, Mode_B, ..]`. The operating modes in which this time-triggered task is
+/// released. If omitted the task is released in all the modes. *IMPORTANT*: This argument is
+/// accepted only if the `timer-queue` feature has been enabled.
+///
+/// - `order = <integer>`. The sub-priority of this task among the tasks that run at the same
+/// priority level. The value must be in the range `0..=255`; pending instances of tasks with lower
+/// `order` are dispatched first. If one task at a priority level specifies an `order` then all the
+/// tasks at that level must specify a different one.
+///
+/// - `priority = <integer>`. Same meaning / function as [`#[exception].priority`](#b-exception),
+/// except that software tasks can also run at priority `0`. These *background* tasks run at the
+/// priority of `idle` and are dispatched from the idle loop rather than from an interrupt so they
+/// don't need an interrupt in the `extern` block. They share resources with `idle` and with each
+/// other without locks.
+///
+/// - `controls = (..)`. Same meaning / function as [`#[init].controls`](#a-init).
+///
+/// - `pend = (..)`. Same meaning / function as [`#[init].pend`](#a-init).
+///
+/// - `publish = (..)`. Same meaning / function as [`#[init].publish`](#a-init).
 ///
 /// - `resources = (..)`. Same meaning / function as [`#[init].resources`](#a-init).
 ///
 /// - `schedule = (..)`. Same meaning / function as [`#[init].schedule`](#a-init).
 ///
+/// - `slots = [<integer>..<integer>, ..]`. The slots of the schedule table in which this task is
+/// released, as ranges of clock cycles relative to the start of the major frame. *IMPORTANT*: This
+/// argument is accepted only if the `timer-queue` feature has been enabled.
+///
 /// - `spawn = (..)`. Same meaning / function as [`#[init].spawn`](#a-init).
 ///
+/// - `subscribe = [Topic_A, Topic_B, ..]`. This is the list of [topics](#4-topic) this task
+/// subscribes to. The task will be spawned with every message published to any of these topics, so
+/// it must take the message as its only input.
+///
+/// - `wcet = <integer>`. Same meaning / function as [`#[exception].wcet`](#b-exception). `async`
+/// tasks can't specify a `wcet`.
+///
+/// By default the pending instances of the tasks that run at the same priority level are
+/// dispatched in FIFO order. Tasks that specify an `order` are instead dispatched by sub-priority,
+/// and the tasks of the priority levels listed in `#[app].round_robin` take turns: the dispatcher
+/// runs one instance of each task that has pending instances, visiting the tasks in alphabetical
+/// order. In both cases each task gets its own ready queue.
+///
+/// The priority levels whose tasks specify a `deadline` are dispatched *earliest deadline first*
+/// (EDF) under the Stack Resource Policy: the absolute deadline of an instance is its `scheduled`
+/// time plus the relative deadline of its task, and the pending instance with the earliest absolute
+/// deadline across all these levels runs next; instances with the same absolute deadline run in
+/// the order they were released. The priorities keep their role of SRP preemption levels and they
+/// must be assigned in *deadline-monotonic* order, i.e. a task can't run at a higher priority than
+/// a task with a shorter relative deadline. A released instance preempts the running one only if
+/// it has an earlier absolute deadline *and* a higher priority than the current system ceiling; an
+/// instance with an earlier deadline but a lower (or the same) priority runs as soon as the running
+/// instance completes, before any instance with a later deadline. In exchange resources are still
+/// shared using ceilings and all tasks still run on a single stack. The scheduler that tracks the
+/// pending instances is shared by all these levels and is accessed in short global critical
+/// sections. Tasks at other priority levels are not affected: they preempt and are preempted by
+/// priority alone. `async` tasks can't specify a `deadline`.
+
+/// Tasks that specify `slots` are *time-triggered*: they are released by a static schedule table
+/// that repeats every `#[app].frame` clock cycles, starting at `Instant(0)`, at the start of each
+/// of their slots. The slots of all tasks must fit in the major frame and must not overlap. These
+/// tasks can't take inputs, can't be `async`, can't subscribe to topics and can't be spawned or
+/// scheduled; their capacity is always 1 so a release is dropped if the previous one has not
+/// completed yet. The `slots` module that the `app` attribute generates in the root of the crate
+/// has one function per time-triggered task that returns its [`rtfm::Slots`], which counts the
+/// dropped releases and the releases that completed after the end of their slot. The table
+/// coexists with hardware tasks and with event-driven software tasks.
+///
+/// [`rtfm::Slots`]: ../rtfm/struct.Slots.html
+///
+/// If the application declares operating modes (`#[app].modes`) each mode has its own schedule
+/// table, made of the slots of the tasks released in that mode; only slots of tasks that share a
+/// mode must not overlap. A mode switch requested through `mode.switch` takes effect at the end of
+/// the current major frame: the old mode completes its frame, none of its tasks is released after
+/// that and the tasks of the new mode are released from the start of the next frame. Releases that
+/// are already pending in the ready queues still run. The first frame runs in the mode requested by
+/// `init`, if any.
+///
+/// Tasks that specify a `min_inter_arrival` time are *sporadic*: `spawn` compares the baseline of
+/// the new release (the `start` or `scheduled` time of the spawner, or the current time when
+/// spawned from `idle`) against the last accepted release of the task. A release that comes
+/// sooner than `min_inter_arrival` cycles after the last one is rejected and counted as a
+/// violation. `spawn` returns a [`rtfm::ReleaseError`] instead of the bare inputs for these tasks:
+/// `TooSoon` for a violation and `Full` when the task queue has no room. A release is recorded as
+/// the last one only if the task queue accepts it. The `arrivals` module that
+/// the `app` attribute generates in the root of the crate has one function per sporadic task that
+/// returns its [`rtfm::Arrivals`]. Only `spawn` enforces the minimum inter-arrival time so these
+/// tasks must be spawned by some context, and they can't be scheduled or subscribe to topics:
+/// those releases would bypass the check.
+///
+/// [`rtfm::Arrivals`]: ../rtfm/struct.Arrivals.html
+/// [`rtfm::ReleaseError`]: ../rtfm/enum.ReleaseError.html
+///
+/// Tasks that specify a `heartbeat` are monitored by the timer queue, which checks that each of
+/// them completes an instance at least once per heartbeat; the first check happens one heartbeat
+/// after `Instant(0)`. A task that misses its heartbeat, for example a periodic task whose
+/// `schedule` call failed, is reported to the [`#[stalled]`](#i-stalled) function, if there's one.
+/// The `heartbeats` module that the `app` attribute generates in the root of the crate has a
+/// `healthy` function that returns `true` while all these tasks are meeting their heartbeats; call
+/// it before feeding a hardware watchdog to only feed it while the application is healthy.
+///
 /// The `app` attribute will injected a *context* into this function that comprises the following
 /// variables:
 ///
@@ -260,6 +521,21 @@ mod syntax;
 ///
 /// - `spawn: <interrupt-name>::Spawn`.  Same meaning / function as [`init.spawn`](#a-init).
 ///
+/// - `controls: <function-name>::Controls`. Same meaning / function as [`init.controls`](#a-init).
+///
+/// - `pend: <function-name>::Pend`. Same meaning / function as [`init.pend`](#a-init).
+///
+/// - `publish: <function-name>::Publish`. Same meaning / function as [`init.publish`](#a-init).
+///
+/// - `mode: ModeSwitch`. Same meaning / function as [`init.mode`](#a-init).
+///
+/// - `timer: <function-name>::Timer`. Only present in `async` tasks when the `timer-queue` feature
+/// is enabled. `timer.delay(duration).await` and `timer.at(instant).await` suspend the task until
+/// the given time; meanwhile other tasks, including the ones that run at the same priority, can
+/// run. `timer.timeout(duration, future).await` waits for `future` but gives up, returning `None`,
+/// if `duration` elapses first; combined with an `rtfm::Signal` it waits for the reply of another
+/// task with a timeout.
+///
 /// Other properties / constraints:
 ///
 /// - Software `task`s can **not** be called from software, but they can be `spawn`-ed and
@@ -269,6 +545,123 @@ mod syntax;
 /// `&mut` references that are safe to access. For example, `static mut FOO: u32 = 0` will
 /// become `FOO: &mut u32`.
 ///
+/// - The dispatcher of an `async` task polls the future returned by the task, which is stored in
+/// an executor allocated on the stack of `main`, until it completes. Only one instance of an
+/// `async` task can be in progress at any time; spawning it again before it completes returns an
+/// error. Resources shared between an `async` task and any other task must be accessed using
+/// `claim`, even if the other tasks run at the same priority, because the `async` task may be
+/// suspended in the middle of its execution. The critical section of a `claim` can't contain an
+/// `.await`.
+///
+/// - An `async` task has a single entry in the timer queue, which wakes it up at the earliest
+/// instant requested by its pending `Delay` futures, including the ones that have been dropped. A
+/// task that's woken up before its `Delay` completes registers the `Delay` again.
+///
+/// ## f. `#[pre_init]`
+///
+/// This attribute indicates that the function is to be run *before* memory (RAM) is initialized.
+/// There can be at most one instance of the `pre_init` attribute inside the `app` pseudo-module.
+/// The signature of the `pre_init` function must be `unsafe fn()`. The attribute takes no
+/// arguments.
+///
+/// This is the [`pre_init`] hook of the `cortex-m-rt` crate: it runs before `.bss` is zeroed and
+/// `.data` is initialized so it must not access any `static` variable. For the same reason no
+/// context is injected into this function and it can't declare `static mut` variables.
+///
+/// [`pre_init`]: https://docs.rs/cortex-m-rt/0.6/cortex_m_rt/attr.pre_init.html
+///
+/// ## g. `#[post_init]`
+///
+/// This attribute indicates that the function is to be used as the *second initialization stage*.
+/// There can be at most one instance of the `post_init` attribute inside the `app` pseudo-module.
+/// The signature of the `post_init` function must be `[unsafe] fn()`.
+///
+/// The `post_init` function runs once after `init` returns, with interrupts enabled, and before
+/// `idle` starts. Like `idle` it runs at the lowest priority of `0` so it can be preempted by all
+/// the other tasks; for example, it can wait for an interrupt handler to signal that the PLL has
+/// locked, or spawn tasks that must run before `idle`.
+///
+/// The `post_init` attribute accepts the same optional arguments as [`#[idle]`](#b-idle) and the
+/// same context is injected into this function: `resources`, `schedule: post_init::Schedule`,
+/// `spawn: post_init::Spawn`, `controls: post_init::Controls`, `pend: post_init::Pend`,
+/// `publish: post_init::Publish` and `mode`.
+///
+/// Other properties / constraints:
+///
+/// - The `post_init` function can **not** be called from software.
+///
+/// - The `static mut` variables declared at the beginning of this function will be transformed into
+/// `&'static mut` references that are safe to access.
+///
+/// - The resources of `post_init` are accessed at priority `0`, just like the resources of `idle`.
+/// Resources shared with `idle` are handed out as `&mut` references and owned singletons are
+/// handed out by reference.
+///
+/// ## h. `#[overrun]`
+///
+/// This attribute indicates that the function is to be used as the *overrun handler*. There can be
+/// at most one instance of the `overrun` attribute inside the `app` pseudo-module and there must be
+/// one if any task specifies a `wcet` budget. The signature of the `overrun` function must be
+/// `[unsafe] fn()`. The attribute takes no arguments. *IMPORTANT*: This attribute is accepted only
+/// if the `timer-queue` feature has been enabled.
+///
+/// The timer queue monitors the execution budgets: starting a task with a `wcet` budget arms an
+/// entry that expires at the end of the budget. When the timer queue has budgets to monitor it
+/// runs at a priority one level higher than the highest priority task so it can preempt any task
+/// outside of a critical section. If the task is still running when its entry expires the timer
+/// queue invokes the `overrun` function, once per overrunning instance. The task is **not**
+/// aborted; the `overrun` function can log the overrun, reset the device or degrade the
+/// application, for example by switching to another operating mode. The device must support that
+/// extra priority level; this is checked at compile time.
+///
+/// **NOTE**: the whole timer queue runs at that priority, not only the budget checks. Every
+/// `schedule` release, schedule table release, heartbeat check and async timer expiry, and
+/// every critical section that accesses the timer queue (e.g. a `schedule` call), blocks all the
+/// tasks of the application, hardware tasks included, for its duration. Account for it in their
+/// response times.
+///
+/// The `app` attribute will inject a *context* into this function that comprises the following
+/// variables:
+///
+/// - `task: Task`. The task that has exceeded its budget. `Task` is an `enum` generated by the
+/// `app` attribute that has one variant per task with a `wcet` budget, named after the task.
+///
+/// - `mode: ModeSwitch`. Same meaning / function as [`init.mode`](#a-init).
+///
+/// Other properties / constraints:
+///
+/// - The `overrun` function can **not** be called from software.
+///
+/// - The `static mut` variables declared at the beginning of this function will be transformed into
+/// `&mut` references that are safe to access.
+///
+/// ## i. `#[stalled]`
+///
+/// This attribute indicates that the function is to be used as the *stall handler*. There can be at
+/// most one instance of the `stalled` attribute inside the `app` pseudo-module; it's optional but
+/// at least one task must specify a `heartbeat`. The signature of the `stalled` function must be
+/// `[unsafe] fn()`. The attribute takes no arguments. *IMPORTANT*: This attribute is accepted only
+/// if the `timer-queue` feature has been enabled.
+///
+/// The timer queue invokes the `stalled` function when a task misses its heartbeat: the task has
+/// not completed an instance in the last `heartbeat` clock cycles. A stall is reported once; the
+/// task is reported again only if it recovers, by completing an instance, and later stalls again.
+///
+/// The `app` attribute will inject a *context* into this function that comprises the following
+/// variables:
+///
+/// - `task: Heartbeat`. The task that has missed its heartbeat. `Heartbeat` is an `enum` generated
+/// by the `app` attribute that has one variant per task with a `heartbeat`, named after the task.
+///
+/// - `mode: ModeSwitch`. Same meaning / function as [`init.mode`](#a-init).
+///
+/// Other properties / constraints:
+///
+/// - The `stalled` function can **not** be called from software.
+///
+/// - The `static mut` variables declared at the beginning of this function will be transformed into
+/// `&mut` references that are safe to access.
+///
 /// # 3. `extern` block
 ///
 /// This `extern` block contains a list of interrupts which are *not* used by the application as
@@ -280,13 +673,144 @@ mod syntax;
 ///
 /// Importantly, attributes can be applied to the functions inside this block. These attributes will
 /// be forwarded to the interrupt handlers generated by the `app` attribute.
+///
+/// By default the interrupts are assigned to the priority levels in an unspecified (but
+/// deterministic) order. The `#[priority = <integer>]` attribute binds an interrupt to a priority
+/// level: that interrupt will dispatch the software tasks that run at the given priority. At most
+/// one interrupt can be bound to each priority level and the level must have software tasks. This
+/// attribute is *not* forwarded to the interrupt handler.
+///
+/// # 4. `#[topic]`
+///
+/// This attribute declares a publish / subscribe *topic*. It must be applied to a type alias, e.g.
+/// `#[topic] type Imu = Sample;`; the type alias is kept as is and names the type of the messages.
+///
+/// Messages published to a topic are delivered to *all* the software tasks that subscribe to it:
+/// publishing spawns every subscriber with the message. The delivery is atomic: if the queue of any
+/// subscriber is full the message is not delivered to any of them and `publish` returns an
+/// [`rtfm::PublishError`] that contains the message and reports which subscribers overflowed.
+///
+/// [`rtfm::PublishError`]: ../rtfm/struct.PublishError.html
+///
+/// Each `publish` call site counts as one `spawn` of each subscriber when inferring the capacity of
+/// their queues. The message type must implement the [`Clone`] trait if the topic has more than one
+/// subscriber, and the [`Send`] trait if it crosses priority levels.
+///
+/// [`Clone`]: https://doc.rust-lang.org/core/clone/trait.Clone.html
+///
+/// A topic can have at most 32 subscribers. Topics share the type namespace with tasks and memory
+/// pools so their names must not collide.
+///
+/// # Unused items
+///
+/// The `app` attribute warns about the parts of the specification that are not used:
+///
+/// - resources that no function lists in its `resources` argument,
+///
+/// - software tasks that are never spawned or scheduled, not even by other software tasks that
+/// can run. No queues or dispatcher are generated for these tasks but their bodies are still type
+/// checked; their `spawn`, `schedule` and `publish` calls always fail (the message is handed back)
+/// and their resource accesses still count towards the ceilings of the resources,
+///
+/// - interrupts in the `extern` block that don't dispatch any software task,
+///
+/// - topics that are never published to or that have no subscribers.
+///
+/// These warnings are reported by the `deprecated` lint, as the use of a deprecated unit struct
+/// named after the kind of item (e.g. `unused_resource`) whose note names the unused item and the
+/// problem, e.g. "resource `X` is never used". Each warning can be silenced by adding the
+/// `#[allow(dead_code)]` (or `#[allow(unused)]`) attribute to the unused item itself. Don't use
+/// `#[allow(deprecated)]` for this purpose: it doesn't work on the item and, at the crate level,
+/// it also hides the uses of items that are actually deprecated.
+///
+/// # Symbols
+///
+/// The code generated by the `app` attribute is deterministic: building the same application twice
+/// produces the same symbols. The following runtime data structures are exported under stable
+/// names that external tools (e.g. debugger scripts) can rely on:
+///
+/// - `P<priority>::READY_QUEUE`. The ready queue of the dispatcher that runs at priority
+/// `<priority>`, e.g. `P1::READY_QUEUE`. For levels that are not dispatched in FIFO order this is
+/// a tuple that contains one queue per task; the tasks are sorted by `order` or by name.
+///
+/// - `EDF_QUEUE`. The scheduler of the priority levels that are dispatched earliest deadline
+/// first; these levels have no `P<priority>::READY_QUEUE`.
+///
+/// - `<task>::FREE_QUEUE`. The queue of free message slots of the software task `<task>`.
+///
+/// - `<task>::INPUTS`. The message (input) buffers of the software task `<task>`.
+///
+/// - `<task>::SCHEDULED_TIMES`. The `scheduled` instants of the software task `<task>`. **NOTE**:
+/// only present if the `timer-queue` feature is enabled.
+///
+/// - `<pool>::NODES`. The blocks of the memory pool `<pool>`.
+///
+/// - `TIMER_QUEUE`. The timer queue. **NOTE**: only present if the `timer-queue` feature is enabled
+/// and at least one task is `schedule`-d.
+///
+/// Other items, like resources and the functions that contain the bodies of `init`, `post_init`,
+/// `idle` and software tasks, are exported as `<name>::<hash>` where `<hash>` only depends on the
+/// names of the resources and tasks declared in the application.
+///
+/// # Introspection
+///
+/// When the `introspect` feature is enabled the `app` attribute keeps track of the occupancy of
+/// the task queues and injects an `introspect` module in the root of the crate. This module
+/// contains the following functions:
+///
+/// - `tasks::<task>() -> &'static rtfm::introspect::Stats`. Pending instances of the software task
+/// `<task>`.
+///
+/// - `ready_queue(priority: u8) -> Option<&'static rtfm::introspect::Stats>`. Ready queue of the
+/// dispatcher that runs at `priority`.
+///
+/// - `timer_queue() -> Option<&'static rtfm::introspect::Stats>`. Pending timer queue entries.
+///
+/// - `symbols() -> &'static [rtfm::introspect::Symbol]`. The symbol table that's also placed in
+/// the `.rtfm_introspect` linker section. See the [`rtfm::introspect`] module for its layout.
+///
+/// [`rtfm::introspect`]: ../rtfm/introspect/index.html
+///
+/// The statistics are also exported as `<task>::STATS`, `P<priority>::STATS` and
+/// `TIMER_QUEUE::STATS`.
+///
+/// # Execution time measurements
+///
+/// When the `wcet` feature is enabled every execution of a task (software tasks, hardware tasks
+/// and the timer queue handler, `SysTick`) is measured using the cycle counter. The time spent in
+/// preempting tasks is not included in the measurements. The `app` attribute injects a `wcet`
+/// module in the root of the crate that contains the following functions:
+///
+/// - `tasks::<task>() -> &'static rtfm::wcet::Times`. Execution times of the task `<task>`.
+///
+/// - `all() -> [(&'static str, &'static rtfm::wcet::Times); N]`. Name and execution times of every
+/// task, sorted by name.
+///
+/// The measurements are also exported as `<task>::WCET`. See the [`rtfm::wcet`] module for more
+/// details.
+///
+/// [`rtfm::wcet`]: ../rtfm/wcet/index.html
+///
+/// # Crash reports
+///
+/// When the `crash-report` feature is enabled the `app` attribute keeps track of the context
+/// (`init`, `post_init`, `idle`, a task or the timer queue handler, `SysTick`) that's currently
+/// running. If the device hard faults, or panics and the `#[panic_handler]` calls
+/// `rtfm::crash::panic`, the runtime records the running context, its dynamic priority and the
+/// number of pending instances of each software task in a section of RAM that survives the reset.
+/// `init` receives this report through the `crash` variable on the next boot.
+///
+/// The runtime defines the `HardFault` handler in this mode so the application must not define
+/// one. See the [`rtfm::crash`] module for more details.
+///
+/// [`rtfm::crash`]: ../rtfm/crash/index.html
 #[proc_macro_attribute]
 pub fn app(args: TokenStream, input: TokenStream) -> TokenStream {
     // Parse
     let args = parse_macro_input!(args as syntax::AppArgs);
     let items = parse_macro_input!(input as syntax::Input).items;
 
-    let app = match syntax::App::parse(items, args) {
+    let mut app = match syntax::App::parse(items, args) {
         Err(e) => return e.to_compile_error().into(),
         Ok(app) => app,
     };
@@ -296,9 +820,17 @@ pub fn app(args: TokenStream, input: TokenStream) -> TokenStream {
         return e.to_compile_error().into();
     }
 
+    // Tasks that are never spawned or scheduled are left out of the scheduling
+    app.remove_dead_tasks();
+
     // Ceiling analysis
     let analysis = analyze::app(&app);
 
+    // Check the memory pools against the analysis
+    if let Err(e) = check::pools(&app, &analysis) {
+        return e.to_compile_error().into();
+    }
+
     // Code generation
     codegen::app(&app, &analysis)
 }
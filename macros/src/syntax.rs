@@ -1,27 +1,39 @@
 use std::{
-    collections::{HashMap, HashSet},
-    iter, u8,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt::Display,
+    hash::BuildHasherDefault,
+    i32, iter, u8,
 };
 
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
 use syn::{
     braced, bracketed, parenthesized,
-    parse::{self, Parse, ParseStream},
+    parse::{self, Parse, ParseStream, Parser},
     punctuated::Punctuated,
     spanned::Spanned,
     token::Brace,
     ArgCaptured, AttrStyle, Attribute, Expr, FnArg, ForeignItem, Ident, IntSuffix, Item, ItemFn,
-    ItemForeignMod, ItemStatic, LitInt, Path, PathArguments, PathSegment, ReturnType, Stmt, Token,
-    Type, TypeTuple, Visibility,
+    ItemForeignMod, ItemStatic, ItemType, LitBool, LitInt, Path, PathArguments, PathSegment,
+    ReturnType, Stmt, Token, Type, TypePath, TypeTuple, Visibility,
 };
 
 pub struct AppArgs {
     pub device: Path,
+    /// Length of the major frame of the time-triggered schedule table, in clock cycles
+    pub frame: Option<LitInt>,
+    /// Operating modes; each one runs its own subset of the schedule table. The first one is the
+    /// initial mode
+    pub modes: Vec<Ident>,
+    /// Priority levels whose tasks are dispatched in round-robin order
+    pub round_robin: Vec<LitInt>,
 }
 
 impl Parse for AppArgs {
     fn parse(input: ParseStream) -> parse::Result<Self> {
         let mut device = None;
+        let mut frame = None;
+        let mut modes = None;
+        let mut round_robin = None;
         loop {
             if input.is_empty() {
                 break;
@@ -43,10 +55,115 @@ impl Parse for AppArgs {
 
                     device = Some(input.parse()?);
                 }
+                "frame" if cfg!(not(feature = "timer-queue")) => {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "The `frame` argument requires that the `timer-queue` feature is \
+                         enabled in the `cortex-m-rtfm` crate",
+                    ));
+                }
+                "frame" => {
+                    if frame.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    // #lit
+                    let lit = parse_cycles(input)?;
+
+                    if lit.value() == 0 {
+                        return Err(parse::Error::new(
+                            lit.span(),
+                            "this literal must be in the range 1...2147483647",
+                        ));
+                    }
+
+                    frame = Some(lit);
+                }
+                "modes" if cfg!(not(feature = "timer-queue")) => {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "The `modes` argument requires that the `timer-queue` feature is \
+                         enabled in the `cortex-m-rtfm` crate",
+                    ));
+                }
+                "modes" => {
+                    if modes.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    // .. [#(#idents)*]
+                    let inner;
+                    bracketed!(inner in input);
+                    let mut list: Vec<Ident> = vec![];
+                    for ident in inner.call(Punctuated::<Ident, Token![,]>::parse_terminated)? {
+                        if list.contains(&ident) {
+                            return Err(parse::Error::new(
+                                ident.span(),
+                                "element appears more than once in list",
+                            ));
+                        }
+
+                        list.push(ident);
+                    }
+
+                    if list.len() > usize::from(u8::MAX) {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "at most 255 modes can be declared",
+                        ));
+                    }
+
+                    modes = Some(list);
+                }
+                "round_robin" => {
+                    if round_robin.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    // .. [#(#lits)*]
+                    let inner;
+                    bracketed!(inner in input);
+                    let mut levels: Vec<LitInt> = vec![];
+                    for lit in inner.call(Punctuated::<LitInt, Token![,]>::parse_terminated)? {
+                        if lit.suffix() != IntSuffix::None {
+                            return Err(parse::Error::new(
+                                lit.span(),
+                                "this literal must be unsuffixed",
+                            ));
+                        }
+
+                        if lit.value() == 0 || lit.value() > u64::from(u8::MAX) {
+                            return Err(parse::Error::new(
+                                lit.span(),
+                                "this literal must be in the range 1...255",
+                            ));
+                        }
+
+                        if levels.iter().any(|level| level.value() == lit.value()) {
+                            return Err(parse::Error::new(
+                                lit.span(),
+                                "element appears more than once in list",
+                            ));
+                        }
+
+                        levels.push(lit);
+                    }
+
+                    round_robin = Some(levels);
+                }
                 _ => {
                     return Err(parse::Error::new(
                         ident.span(),
-                        "expected `device`; other keys are not accepted",
+                        "expected one of: device, frame, modes or round_robin",
                     ))
                 }
             }
@@ -64,6 +181,9 @@ impl Parse for AppArgs {
                 Span::call_site(),
                 "`device` argument is required",
             ))?,
+            frame,
+            modes: modes.unwrap_or_else(Vec::new),
+            round_robin: round_robin.unwrap_or_else(Vec::new),
         })
     }
 }
@@ -109,142 +229,525 @@ pub struct App {
     pub args: AppArgs,
     pub idle: Option<Idle>,
     pub init: Init,
+    pub pre_init: Option<PreInit>,
+    pub post_init: Option<PostInit>,
+    pub overrun: Option<Hook>,
+    pub stalled: Option<Hook>,
     pub exceptions: Exceptions,
     pub interrupts: Interrupts,
+    pub pools: Pools,
     pub resources: Resources,
     pub tasks: Tasks,
+    pub topics: Topics,
+    /// Software tasks that are never spawned or scheduled (see `remove_dead_tasks`)
+    pub dead_tasks: Tasks,
     pub free_interrupts: FreeInterrupts,
 }
 
 impl App {
-    pub fn parse(items: Vec<Item>, args: AppArgs) -> parse::Result<Self> {
+    pub fn parse(items: Vec<Item>, args: AppArgs) -> Result<Self, Errors> {
+        let mut errors = Errors::default();
+
         let mut idle = None;
         let mut init = None;
-        let mut exceptions = HashMap::new();
-        let mut interrupts = HashMap::new();
-        let mut resources = HashMap::new();
-        let mut tasks = HashMap::new();
+        let mut pre_init = None;
+        let mut post_init = None;
+        let mut overrun = None;
+        let mut stalled = None;
+        let mut exceptions = Exceptions::default();
+        let mut interrupts = Interrupts::default();
+        let mut pools = Pools::default();
+        let mut resources = Resources::default();
+        let mut tasks = Tasks::default();
+        let mut topics = Topics::default();
         let mut free_interrupts = None;
 
         for item in items {
+            // NOTE on error we move on to the next item; the erroneous item is left out of the
+            // `App` so no other error is reported about it
             match item {
                 Item::Fn(mut item) => {
                     if let Some(pos) = item.attrs.iter().position(|attr| eq(attr, "idle")) {
                         if idle.is_some() {
-                            return Err(parse::Error::new(
-                                item.span(),
-                                "`#[idle]` function must appear at most once",
-                            ));
+                            errors.push(item.span(), "`#[idle]` function must appear at most once");
+                            continue;
                         }
 
-                        let args = syn::parse2(item.attrs.swap_remove(pos).tts)?;
-
-                        idle = Some(Idle::check(args, item)?);
+                        let args = item.attrs.swap_remove(pos).tts;
+                        match syn::parse2(args).and_then(|args| Idle::check(args, item)) {
+                            Ok(idle_) => idle = Some(idle_),
+                            Err(e) => errors.errors.push(e),
+                        }
                     } else if let Some(pos) = item.attrs.iter().position(|attr| eq(attr, "init")) {
                         if init.is_some() {
-                            return Err(parse::Error::new(
-                                item.span(),
-                                "`#[init]` function must appear exactly once",
-                            ));
+                            errors.push(item.span(), "`#[init]` function must appear exactly once");
+                            continue;
                         }
 
-                        let args = syn::parse2(item.attrs.swap_remove(pos).tts)?;
-
-                        init = Some(Init::check(args, item)?);
+                        let args = item.attrs.swap_remove(pos).tts;
+                        match syn::parse2(args).and_then(|args| Init::check(args, item)) {
+                            Ok(init_) => init = Some(init_),
+                            Err(e) => errors.errors.push(e),
+                        }
                     } else if let Some(pos) =
-                        item.attrs.iter().position(|attr| eq(attr, "exception"))
+                        item.attrs.iter().position(|attr| eq(attr, "pre_init"))
                     {
-                        if exceptions.contains_key(&item.ident)
-                            || interrupts.contains_key(&item.ident)
-                            || tasks.contains_key(&item.ident)
-                        {
-                            return Err(parse::Error::new(
-                                item.ident.span(),
-                                "this task is defined multiple times",
-                            ));
+                        if pre_init.is_some() {
+                            errors.push(
+                                item.span(),
+                                "`#[pre_init]` function must appear at most once",
+                            );
+                            continue;
                         }
 
-                        let args = syn::parse2(item.attrs.swap_remove(pos).tts)?;
+                        let args = item.attrs.swap_remove(pos).tts;
+                        if !args.is_empty() {
+                            errors.push(args.span(), "`#[pre_init]` doesn't take any arguments");
+                            continue;
+                        }
 
-                        exceptions.insert(item.ident.clone(), Exception::check(args, item)?);
+                        match PreInit::check(item) {
+                            Ok(pre_init_) => pre_init = Some(pre_init_),
+                            Err(e) => errors.errors.push(e),
+                        }
                     } else if let Some(pos) =
-                        item.attrs.iter().position(|attr| eq(attr, "interrupt"))
+                        item.attrs.iter().position(|attr| eq(attr, "post_init"))
                     {
-                        if exceptions.contains_key(&item.ident)
-                            || interrupts.contains_key(&item.ident)
-                            || tasks.contains_key(&item.ident)
-                        {
-                            return Err(parse::Error::new(
-                                item.ident.span(),
-                                "this task is defined multiple times",
-                            ));
+                        if post_init.is_some() {
+                            errors.push(
+                                item.span(),
+                                "`#[post_init]` function must appear at most once",
+                            );
+                            continue;
                         }
 
-                        let args = syn::parse2(item.attrs.swap_remove(pos).tts)?;
+                        let args = item.attrs.swap_remove(pos).tts;
+                        match syn::parse2(args).and_then(|args| PostInit::check(args, item)) {
+                            Ok(post_init_) => post_init = Some(post_init_),
+                            Err(e) => errors.errors.push(e),
+                        }
+                    } else if let Some(pos) = item
+                        .attrs
+                        .iter()
+                        .position(|attr| eq(attr, "overrun") || eq(attr, "stalled"))
+                    {
+                        let (kind, hook) = if eq(&item.attrs[pos], "overrun") {
+                            ("overrun", &mut overrun)
+                        } else {
+                            ("stalled", &mut stalled)
+                        };
+
+                        if cfg!(not(feature = "timer-queue")) {
+                            errors.push(
+                                item.span(),
+                                format!(
+                                    "The `#[{}]` handler requires that the `timer-queue` \
+                                     feature is enabled in the `cortex-m-rtfm` crate",
+                                    kind
+                                ),
+                            );
+                            continue;
+                        }
 
-                        interrupts.insert(item.ident.clone(), Interrupt::check(args, item)?);
-                    } else if let Some(pos) = item.attrs.iter().position(|attr| eq(attr, "task")) {
-                        if exceptions.contains_key(&item.ident)
-                            || interrupts.contains_key(&item.ident)
-                            || tasks.contains_key(&item.ident)
-                        {
-                            return Err(parse::Error::new(
-                                item.ident.span(),
-                                "this task is defined multiple times",
-                            ));
+                        if hook.is_some() {
+                            errors.push(
+                                item.span(),
+                                format!("`#[{}]` function must appear at most once", kind),
+                            );
+                            continue;
+                        }
+
+                        let args = item.attrs.swap_remove(pos).tts;
+                        if !args.is_empty() {
+                            errors.push(
+                                args.span(),
+                                format!("`#[{}]` doesn't take any arguments", kind),
+                            );
+                            continue;
                         }
 
-                        let args = syn::parse2(item.attrs.swap_remove(pos).tts)?;
+                        match Hook::check(item, kind) {
+                            Ok(hook_) => *hook = Some(hook_),
+                            Err(e) => errors.errors.push(e),
+                        }
+                    } else if let Some(pos) = item.attrs.iter().position(|attr| {
+                        eq(attr, "exception") || eq(attr, "interrupt") || eq(attr, "task")
+                    }) {
+                        if let Some(first) = exceptions
+                            .keys()
+                            .chain(interrupts.keys())
+                            .chain(tasks.keys())
+                            .find(|name| **name == item.ident)
+                        {
+                            errors.push(item.ident.span(), "this task is defined multiple times");
+                            errors.note(first.span(), "the task is first defined here");
+                            continue;
+                        }
 
-                        tasks.insert(item.ident.clone(), Task::check(args, item)?);
+                        let attr = item.attrs.swap_remove(pos);
+                        let name = item.ident.clone();
+                        let res = if eq(&attr, "exception") {
+                            syn::parse2(attr.tts)
+                                .and_then(|args| Exception::check(args, item))
+                                .map(|exception| {
+                                    exceptions.insert(name, exception);
+                                })
+                        } else if eq(&attr, "interrupt") {
+                            syn::parse2(attr.tts)
+                                .and_then(|args| Interrupt::check(args, item))
+                                .map(|interrupt| {
+                                    interrupts.insert(name, interrupt);
+                                })
+                        } else {
+                            syn::parse2(attr.tts)
+                                .and_then(|args| Task::check(args, item))
+                                .map(|task| {
+                                    tasks.insert(name, task);
+                                })
+                        };
+
+                        if let Err(e) = res {
+                            errors.errors.push(e);
+                        }
                     } else {
-                        return Err(parse::Error::new(
+                        errors.push(
                             item.span(),
                             "this item must live outside the `#[app]` module",
-                        ));
+                        );
                     }
                 }
-                Item::Static(item) => {
-                    if resources.contains_key(&item.ident) {
-                        return Err(parse::Error::new(
-                            item.ident.span(),
-                            "this resource is listed twice",
-                        ));
+                Item::Static(mut item) => {
+                    if let Some(first) = resources
+                        .keys()
+                        .chain(pools.keys())
+                        .find(|name| **name == item.ident)
+                    {
+                        errors.push(item.ident.span(), "this resource is listed twice");
+                        errors.note(first.span(), "the resource is first declared here");
+                        continue;
+                    }
+
+                    let name = item.ident.clone();
+                    let res = if let Some(pos) = item.attrs.iter().position(|attr| eq(attr, "pool"))
+                    {
+                        let args = item.attrs.swap_remove(pos).tts;
+                        syn::parse2(args)
+                            .and_then(|args| Pool::check(args, item))
+                            .map(|pool| {
+                                pools.insert(name, pool);
+                            })
+                    } else {
+                        Resource::check(item).map(|res| {
+                            resources.insert(name, res);
+                        })
+                    };
+
+                    if let Err(e) = res {
+                        errors.errors.push(e);
+                    }
+                }
+                Item::Type(mut item) => {
+                    let pos = match item.attrs.iter().position(|attr| eq(attr, "topic")) {
+                        Some(pos) => pos,
+                        None => {
+                            errors.push(
+                                item.span(),
+                                "this item must live outside the `#[app]` module",
+                            );
+                            continue;
+                        }
+                    };
+
+                    if let Some(first) = topics.keys().find(|name| **name == item.ident) {
+                        errors.push(item.ident.span(), "this topic is declared twice");
+                        errors.note(first.span(), "the topic is first declared here");
+                        continue;
+                    }
+
+                    let args = item.attrs.swap_remove(pos).tts;
+                    if !args.is_empty() {
+                        errors.push(args.span(), "`#[topic]` doesn't take any arguments");
+                        continue;
                     }
 
-                    resources.insert(item.ident.clone(), Resource::check(item)?);
+                    let name = item.ident.clone();
+                    match Topic::check(item) {
+                        Ok(topic) => {
+                            topics.insert(name, topic);
+                        }
+                        Err(e) => errors.errors.push(e),
+                    }
                 }
                 Item::ForeignMod(item) => {
                     if free_interrupts.is_some() {
-                        return Err(parse::Error::new(
+                        errors.push(
                             item.abi.extern_token.span(),
                             "`extern` block can only appear at most once",
-                        ));
+                        );
+                        continue;
                     }
 
-                    free_interrupts = Some(FreeInterrupt::parse(item)?);
+                    match FreeInterrupt::parse(item) {
+                        Ok(fi) => free_interrupts = Some(fi),
+                        Err(e) => errors.errors.push(e),
+                    }
                 }
                 _ => {
-                    return Err(parse::Error::new(
+                    errors.push(
                         item.span(),
                         "this item must live outside the `#[app]` module",
-                    ))
+                    );
+                }
+            }
+        }
+
+        let init = match init {
+            Some(init) => init,
+            None => {
+                if errors.is_empty() {
+                    errors.push(Span::call_site(), "`#[init]` function is missing");
                 }
+
+                return Err(errors);
+            }
+        };
+
+        // Device peripherals listed as resources become resources of type `<device>::<NAME>`
+        let peripherals = init
+            .args
+            .peripherals
+            .iter()
+            .chain(idle.iter().flat_map(|idle| &idle.args.peripherals))
+            .chain(post_init.iter().flat_map(|pi| &pi.args.peripherals))
+            .chain(exceptions.values().flat_map(|e| &e.args.peripherals))
+            .chain(interrupts.values().flat_map(|i| &i.args.peripherals))
+            .chain(tasks.values().flat_map(|t| &t.args.peripherals))
+            .cloned()
+            .collect::<Idents>();
+        for name in peripherals {
+            if let Some(static_) = resources
+                .keys()
+                .chain(pools.keys())
+                .find(|static_| **static_ == name)
+            {
+                errors.push(
+                    name.span(),
+                    "this name is used by both a `static` and a device peripheral",
+                );
+                errors.note(static_.span(), "the `static` is declared here");
+                continue;
             }
+
+            let mut path = args.device.clone();
+            path.segments.push(PathSegment::from(name.clone()));
+
+            resources.insert(
+                name,
+                Resource {
+                    peripheral: true,
+                    retain: false,
+                    singleton: false,
+                    attrs: vec![],
+                    mutability: Some(<Token![mut]>::default()),
+                    ty: Box::new(Type::Path(TypePath { qself: None, path })),
+                    expr: None,
+                },
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
         }
 
         Ok(App {
             args,
             idle,
-            init: init.expect("`#[init]` function is missing"),
+            init,
+            pre_init,
+            post_init,
+            overrun,
+            stalled,
             exceptions,
             interrupts,
+            pools,
             resources,
             tasks,
-            free_interrupts: free_interrupts.unwrap_or_else(|| FreeInterrupts::new()),
+            topics,
+            dead_tasks: Tasks::default(),
+            free_interrupts: free_interrupts.unwrap_or_else(|| FreeInterrupts::default()),
         })
     }
 
+    /// Returns the tasks, hardware and software, that have a `wcet` budget and their budgets
+    pub fn budgets(&self) -> Vec<(&Ident, &LitInt)> {
+        let mut budgets = self
+            .exceptions
+            .iter()
+            .filter_map(|(name, e)| e.args.wcet.as_ref().map(|wcet| (name, wcet)))
+            .chain(
+                self.interrupts
+                    .iter()
+                    .filter_map(|(name, i)| i.args.wcet.as_ref().map(|wcet| (name, wcet))),
+            )
+            .chain(
+                self.tasks
+                    .iter()
+                    .filter_map(|(name, t)| t.args.wcet.as_ref().map(|wcet| (name, wcet))),
+            )
+            .collect::<Vec<_>>();
+        budgets.sort_by_key(|(name, _)| name.to_string());
+        budgets
+    }
+
+    /// Returns the software tasks that have a `heartbeat` and their heartbeats, sorted by name
+    pub fn heartbeats(&self) -> Vec<(&Ident, &LitInt)> {
+        let mut heartbeats = self
+            .tasks
+            .iter()
+            .filter_map(|(name, t)| t.args.heartbeat.as_ref().map(|hb| (name, hb)))
+            .collect::<Vec<_>>();
+        heartbeats.sort_by_key(|(name, _)| name.to_string());
+        heartbeats
+    }
+
+    /// Returns the time-triggered schedule table: the slots of all tasks sorted by their start
+    pub fn table(&self) -> Vec<(&Slot, &Ident)> {
+        let mut table = self
+            .tasks
+            .iter()
+            .flat_map(|(name, task)| task.args.slots.iter().map(move |slot| (slot, name)))
+            .collect::<Vec<_>>();
+        table.sort_by_key(|(slot, _)| slot.start.value());
+        table
+    }
+
+    /// Returns the schedule table of each operating mode: the slots of the tasks released in that
+    /// mode sorted by their start. Without modes there's a single table that contains all the slots
+    pub fn mode_tables(&self) -> Vec<(Option<&Ident>, Vec<(&Slot, &Ident)>)> {
+        if self.args.modes.is_empty() {
+            return vec![(None, self.table())];
+        }
+
+        self.args
+            .modes
+            .iter()
+            .map(|mode| {
+                let table = self
+                    .table()
+                    .into_iter()
+                    .filter(|(_, task)| {
+                        let modes = &self.tasks[*task].args.modes;
+                        modes.is_empty() || modes.contains(mode)
+                    })
+                    .collect();
+
+                (Some(mode), table)
+            })
+            .collect()
+    }
+
+    /// Returns the software tasks that can run: the tasks spawned, scheduled or published to by
+    /// `init`, `post_init`, `idle`, the hardware tasks or other software tasks that can run, and
+    /// the tasks released by the schedule table
+    pub fn live_tasks(&self) -> Idents {
+        let published = self
+            .publish_callers()
+            .filter(|(name, _)| !self.tasks.contains_key(name))
+            .flat_map(|(_, publish)| publish)
+            .flat_map(|topic| self.subscribers(topic))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut live = self
+            .init
+            .args
+            .spawn
+            .iter()
+            .chain(&self.init.args.schedule)
+            .chain(
+                self.post_init
+                    .iter()
+                    .flat_map(|pi| pi.args.spawn.iter().chain(&pi.args.schedule)),
+            )
+            .chain(
+                self.idle
+                    .iter()
+                    .flat_map(|idle| idle.args.spawn.iter().chain(&idle.args.schedule)),
+            )
+            .chain(
+                self.exceptions
+                    .values()
+                    .flat_map(|e| e.args.spawn.iter().chain(&e.args.schedule)),
+            )
+            .chain(
+                self.interrupts
+                    .values()
+                    .flat_map(|i| i.args.spawn.iter().chain(&i.args.schedule)),
+            )
+            .cloned()
+            .chain(published)
+            .chain(self.table().into_iter().map(|(_, task)| task.clone()))
+            .collect::<Idents>();
+
+        loop {
+            let more = live
+                .iter()
+                .filter_map(|name| self.tasks.get(name))
+                .flat_map(|t| {
+                    t.args
+                        .spawn
+                        .iter()
+                        .chain(&t.args.schedule)
+                        .chain(t.args.publish.iter().flat_map(|topic| self.subscribers(topic)))
+                })
+                .filter(|name| !live.contains(*name))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if more.is_empty() {
+                break live;
+            }
+
+            live.extend(more);
+        }
+    }
+
+    /// Moves the software tasks that can't run into `dead_tasks`; their bodies are type checked
+    /// but no queues or dispatcher code are generated for them
+    pub fn remove_dead_tasks(&mut self) {
+        let live = self.live_tasks();
+
+        let dead = self
+            .tasks
+            .keys()
+            .filter(|name| !live.contains(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in dead {
+            let task = self.tasks.remove(&name).expect("UNREACHABLE");
+            self.dead_tasks.insert(name, task);
+        }
+    }
+
+    /// Returns an iterator over the hardware tasks listed in `controls`, including by the tasks
+    /// that never run. A task may appear more than once in this iterator
+    pub fn controls(&self) -> impl Iterator<Item = &Ident> {
+        self.init
+            .args
+            .controls
+            .iter()
+            .chain(self.post_init.iter().flat_map(|pi| &pi.args.controls))
+            .chain(self.idle.iter().flat_map(|idle| &idle.args.controls))
+            .chain(self.exceptions.values().flat_map(|e| &e.args.controls))
+            .chain(self.interrupts.values().flat_map(|i| &i.args.controls))
+            .chain(
+                self.tasks
+                    .values()
+                    .chain(self.dead_tasks.values())
+                    .flat_map(|t| &t.args.controls),
+            )
+    }
+
     /// Returns an iterator over all resource accesses.
     ///
     /// Each resource access include the priority it's accessed at (`u8`) and the name of the
@@ -256,6 +759,11 @@ impl App {
                 Box::new(idle.args.resources.iter().map(|res| (0, res)))
             })
             .unwrap_or_else(|| Box::new(iter::empty()))
+            .chain(
+                self.post_init
+                    .iter()
+                    .flat_map(|pi| pi.args.resources.iter().map(|res| (0, res))),
+            )
             .chain(self.exceptions.values().flat_map(|e| {
                 e.args
                     .resources
@@ -296,6 +804,11 @@ impl App {
                     })
                     .unwrap_or_else(|| Box::new(iter::empty())),
             )
+            .chain(
+                self.post_init
+                    .iter()
+                    .flat_map(|pi| pi.args.spawn.iter().map(|s| (Some(0), s))),
+            )
             .chain(
                 self.exceptions
                     .values()
@@ -332,6 +845,11 @@ impl App {
                     })
                     .unwrap_or_else(|| Box::new(iter::empty())),
             )
+            .chain(
+                self.post_init
+                    .iter()
+                    .flat_map(|pi| pi.args.schedule.iter().map(|s| (Some(0), s))),
+            )
             .chain(self.exceptions.values().flat_map(|e| {
                 e.args
                     .schedule
@@ -367,6 +885,12 @@ impl App {
                 Ident::new("init", Span::call_site()),
                 &self.init.args.schedule,
             )))
+            .chain(self.post_init.iter().map(|pi| {
+                (
+                    Ident::new("post_init", Span::call_site()),
+                    &pi.args.schedule,
+                )
+            }))
             .chain(
                 self.exceptions
                     .iter()
@@ -398,6 +922,11 @@ impl App {
                 Ident::new("init", Span::call_site()),
                 &self.init.args.spawn,
             )))
+            .chain(
+                self.post_init
+                    .iter()
+                    .map(|pi| (Ident::new("post_init", Span::call_site()), &pi.args.spawn)),
+            )
             .chain(
                 self.exceptions
                     .iter()
@@ -414,32 +943,220 @@ impl App {
                     .map(|(name, task)| (name.clone(), &task.args.spawn)),
             )
     }
-}
 
-pub type Idents = HashSet<Ident>;
-
-pub type Exceptions = HashMap<Ident, Exception>;
-
-pub type Interrupts = HashMap<Ident, Interrupt>;
-
-pub type Resources = HashMap<Ident, Resource>;
-
-pub type Statics = Vec<ItemStatic>;
-
-pub type Tasks = HashMap<Ident, Task>;
-
-pub type FreeInterrupts = HashMap<Ident, FreeInterrupt>;
+    /// Returns the software task called `name`, whether it can run or not
+    pub fn task(&self, name: &Ident) -> &Task {
+        self.tasks
+            .get(name)
+            .or_else(|| self.dead_tasks.get(name))
+            .expect("BUG: App::task")
+    }
 
-pub struct Idle {
-    pub args: IdleArgs,
-    pub attrs: Vec<Attribute>,
-    pub statics: HashMap<Ident, Static>,
-    pub stmts: Vec<Stmt>,
-}
+    /// Returns the software tasks that subscribe to `topic`, sorted by name
+    pub fn subscribers(&self, topic: &Ident) -> Vec<&Ident> {
+        let mut subscribers = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.args.subscribe.contains(topic))
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        subscribers.sort_by_key(|name| name.to_string());
+        subscribers
+    }
 
-pub type IdleArgs = InitArgs;
+    /// Returns an iterator over all `publish` calls
+    ///
+    /// Each publish call includes the priority of the context from which it's issued and the name
+    /// of the topic. A topic may appear more that once in this iterator.
+    ///
+    /// A priority of `None` means that this being called from `init`
+    pub fn publish_calls(&self) -> impl Iterator<Item = (Option<u8>, &Ident)> {
+        self.init
+            .args
+            .publish
+            .iter()
+            .map(|t| (None, t))
+            .chain(
+                self.idle
+                    .iter()
+                    .map(|idle| &idle.args)
+                    .chain(self.post_init.iter().map(|pi| &pi.args))
+                    .flat_map(|args| args.publish.iter().map(|t| (Some(0), t))),
+            )
+            .chain(self.exceptions.values().flat_map(|e| {
+                e.args
+                    .publish
+                    .iter()
+                    .map(move |t| (Some(e.args.priority), t))
+            }))
+            .chain(self.interrupts.values().flat_map(|i| {
+                i.args
+                    .publish
+                    .iter()
+                    .map(move |t| (Some(i.args.priority), t))
+            }))
+            .chain(self.tasks.values().flat_map(|t| {
+                t.args
+                    .publish
+                    .iter()
+                    .map(move |topic| (Some(t.args.priority), topic))
+            }))
+    }
 
-impl Idle {
+    pub fn publish_callers(&self) -> impl Iterator<Item = (Ident, &Idents)> {
+        self.idle
+            .iter()
+            .map(|idle| (Ident::new("idle", Span::call_site()), &idle.args.publish))
+            .chain(iter::once((
+                Ident::new("init", Span::call_site()),
+                &self.init.args.publish,
+            )))
+            .chain(
+                self.post_init
+                    .iter()
+                    .map(|pi| (Ident::new("post_init", Span::call_site()), &pi.args.publish)),
+            )
+            .chain(
+                self.exceptions
+                    .iter()
+                    .map(|(name, exception)| (name.clone(), &exception.args.publish)),
+            )
+            .chain(
+                self.interrupts
+                    .iter()
+                    .map(|(name, interrupt)| (name.clone(), &interrupt.args.publish)),
+            )
+            .chain(
+                self.tasks
+                    .iter()
+                    .map(|(name, task)| (name.clone(), &task.args.publish)),
+            )
+    }
+
+    pub fn pend_callers(&self) -> impl Iterator<Item = (Ident, &Idents)> {
+        self.idle
+            .as_ref()
+            .map(|idle| -> Box<Iterator<Item = _>> {
+                Box::new(iter::once((
+                    Ident::new("idle", Span::call_site()),
+                    &idle.args.pend,
+                )))
+            })
+            .unwrap_or_else(|| Box::new(iter::empty()))
+            .chain(iter::once((
+                Ident::new("init", Span::call_site()),
+                &self.init.args.pend,
+            )))
+            .chain(
+                self.post_init
+                    .iter()
+                    .map(|pi| (Ident::new("post_init", Span::call_site()), &pi.args.pend)),
+            )
+            .chain(
+                self.exceptions
+                    .iter()
+                    .map(|(name, exception)| (name.clone(), &exception.args.pend)),
+            )
+            .chain(
+                self.interrupts
+                    .iter()
+                    .map(|(name, interrupt)| (name.clone(), &interrupt.args.pend)),
+            )
+            .chain(
+                self.tasks
+                    .iter()
+                    .chain(&self.dead_tasks)
+                    .map(|(name, task)| (name.clone(), &task.args.pend)),
+            )
+    }
+}
+
+/// `HashMap` that uses a fixed hasher
+///
+/// The default hasher is randomly seeded; using it would make the iteration order, and thus the
+/// expansion of `#[app]`, change from build to build
+pub type Map<K, V> = HashMap<K, V, BuildHasherDefault<DefaultHasher>>;
+
+/// `HashSet` that uses a fixed hasher (see `Map`)
+pub type Set<T> = HashSet<T, BuildHasherDefault<DefaultHasher>>;
+
+/// Errors found in the specification
+///
+/// Errors are collected instead of returned as soon as they are found so that they can all be
+/// reported in a single compilation
+#[derive(Default)]
+pub struct Errors {
+    errors: Vec<parse::Error>,
+}
+
+impl Errors {
+    pub fn push<T>(&mut self, span: Span, message: T)
+    where
+        T: Display,
+    {
+        self.errors.push(parse::Error::new(span, message))
+    }
+
+    /// Adds a note about the previous error that points to a different part of the specification,
+    /// usually the declaration of the item the error is about
+    pub fn note<T>(&mut self, span: Span, message: T)
+    where
+        T: Display,
+    {
+        debug_assert!(!self.errors.is_empty());
+
+        self.errors.push(parse::Error::new(span, message))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn to_compile_error(&self) -> TokenStream {
+        let mut tokens = TokenStream::new();
+        for error in &self.errors {
+            tokens.extend(error.to_compile_error());
+        }
+        tokens
+    }
+}
+
+impl From<parse::Error> for Errors {
+    fn from(error: parse::Error) -> Errors {
+        Errors {
+            errors: vec![error],
+        }
+    }
+}
+
+pub type Idents = Set<Ident>;
+
+pub type Exceptions = Map<Ident, Exception>;
+
+pub type Interrupts = Map<Ident, Interrupt>;
+
+pub type Pools = Map<Ident, Pool>;
+
+pub type Topics = Map<Ident, Topic>;
+
+pub type Resources = Map<Ident, Resource>;
+
+pub type Statics = Vec<ItemStatic>;
+
+pub type Tasks = Map<Ident, Task>;
+
+pub type FreeInterrupts = Map<Ident, FreeInterrupt>;
+
+pub struct Idle {
+    pub args: IdleArgs,
+    pub attrs: Vec<Attribute>,
+    pub statics: Map<Ident, Static>,
+    pub stmts: Vec<Stmt>,
+}
+
+pub type IdleArgs = InitArgs;
+
+impl Idle {
     fn check(args: IdleArgs, item: ItemFn) -> parse::Result<Self> {
         let valid_signature = item.vis == Visibility::Inherited
             && item.constness.is_none()
@@ -471,7 +1188,143 @@ impl Idle {
     }
 }
 
+/// Runs before RAM is initialized; see `cortex_m_rt::pre_init`
+pub struct PreInit {
+    pub attrs: Vec<Attribute>,
+    pub stmts: Vec<Stmt>,
+}
+
+impl PreInit {
+    fn check(item: ItemFn) -> parse::Result<Self> {
+        let valid_signature = item.vis == Visibility::Inherited
+            && item.constness.is_none()
+            && item.asyncness.is_none()
+            && item.unsafety.is_some()
+            && item.abi.is_none()
+            && item.decl.generics.params.is_empty()
+            && item.decl.generics.where_clause.is_none()
+            && item.decl.inputs.is_empty()
+            && item.decl.variadic.is_none()
+            && is_unit(&item.decl.output);
+
+        let span = item.span();
+
+        if !valid_signature {
+            return Err(parse::Error::new(
+                span,
+                "`pre_init` must have type signature `unsafe fn()`",
+            ));
+        }
+
+        let (statics, stmts) = extract_statics(item.block.stmts);
+
+        if let Some(static_) = statics.first() {
+            return Err(parse::Error::new(
+                static_.ident.span(),
+                "`pre_init` can't have `static mut` variables; RAM has not been initialized \
+                 when it runs",
+            ));
+        }
+
+        Ok(PreInit {
+            attrs: item.attrs,
+            stmts,
+        })
+    }
+}
+
+/// Runs after `init` with interrupts enabled, before `idle`
+pub struct PostInit {
+    pub args: PostInitArgs,
+    pub attrs: Vec<Attribute>,
+    pub statics: Map<Ident, Static>,
+    pub stmts: Vec<Stmt>,
+}
+
+pub type PostInitArgs = InitArgs;
+
+impl PostInit {
+    fn check(args: PostInitArgs, item: ItemFn) -> parse::Result<Self> {
+        let valid_signature = item.vis == Visibility::Inherited
+            && item.constness.is_none()
+            && item.asyncness.is_none()
+            && item.abi.is_none()
+            && item.decl.generics.params.is_empty()
+            && item.decl.generics.where_clause.is_none()
+            && item.decl.inputs.is_empty()
+            && item.decl.variadic.is_none()
+            && is_unit(&item.decl.output);
+
+        let span = item.span();
+
+        if !valid_signature {
+            return Err(parse::Error::new(
+                span,
+                "`post_init` must have type signature `[unsafe] fn()`",
+            ));
+        }
+
+        let (statics, stmts) = extract_statics(item.block.stmts);
+
+        Ok(PostInit {
+            args,
+            attrs: item.attrs,
+            statics: Static::parse(statics)?,
+            stmts,
+        })
+    }
+}
+
+/// Runs from the timer queue handler: `#[overrun]` when a task exceeds its `wcet` budget and
+/// `#[stalled]` when a task misses its `heartbeat`
+pub struct Hook {
+    pub attrs: Vec<Attribute>,
+    pub name: Ident,
+    pub statics: Map<Ident, Static>,
+    pub stmts: Vec<Stmt>,
+}
+
+impl Hook {
+    fn check(item: ItemFn, kind: &str) -> parse::Result<Self> {
+        let valid_signature = item.vis == Visibility::Inherited
+            && item.constness.is_none()
+            && item.asyncness.is_none()
+            && item.abi.is_none()
+            && item.decl.generics.params.is_empty()
+            && item.decl.generics.where_clause.is_none()
+            && item.decl.inputs.is_empty()
+            && item.decl.variadic.is_none()
+            && is_unit(&item.decl.output);
+
+        let span = item.span();
+
+        if !valid_signature {
+            return Err(parse::Error::new(
+                span,
+                format!("`{}` must have type signature `[unsafe] fn()`", kind),
+            ));
+        }
+
+        let (statics, stmts) = extract_statics(item.block.stmts);
+
+        Ok(Hook {
+            attrs: item.attrs,
+            name: item.ident,
+            statics: Static::parse(statics)?,
+            stmts,
+        })
+    }
+}
+
 pub struct InitArgs {
+    /// Hardware tasks this context can enable / disable
+    pub controls: Idents,
+    /// Hardware tasks this context can pend
+    pub pend: Idents,
+    /// Device peripherals listed in `resources` as `device::NAME`
+    pub peripherals: Idents,
+    /// Topics this context can publish to
+    pub publish: Idents,
     pub resources: Idents,
     pub schedule: Idents,
     pub spawn: Idents,
@@ -480,9 +1333,13 @@ pub struct InitArgs {
 impl Default for InitArgs {
     fn default() -> Self {
         InitArgs {
-            resources: Idents::new(),
-            schedule: Idents::new(),
-            spawn: Idents::new(),
+            controls: Idents::default(),
+            pend: Idents::default(),
+            peripherals: Idents::default(),
+            publish: Idents::default(),
+            resources: Idents::default(),
+            schedule: Idents::default(),
+            spawn: Idents::default(),
         }
     }
 }
@@ -493,6 +1350,10 @@ impl Parse for InitArgs {
             return Ok(InitArgs::default());
         }
 
+        let mut controls = None;
+        let mut pend = None;
+        let mut peripherals = Idents::default();
+        let mut publish = None;
         let mut resources = None;
         let mut schedule = None;
         let mut spawn = None;
@@ -517,11 +1378,11 @@ impl Parse for InitArgs {
                          enabled in the `cortex-m-rtfm` crate",
                     ));
                 }
-                "resources" | "schedule" | "spawn" => {} // OK
+                "controls" | "pend" | "publish" | "resources" | "schedule" | "spawn" => {} // OK
                 _ => {
                     return Err(parse::Error::new(
                         ident.span(),
-                        "expected one of: resources, schedule or spawn",
+                        "expected one of: controls, pend, publish, resources, schedule or spawn",
                     ))
                 }
             }
@@ -529,20 +1390,47 @@ impl Parse for InitArgs {
             // .. [#(#idents)*]
             let inner;
             bracketed!(inner in content);
-            let mut idents = Idents::new();
-            for ident in inner.call(Punctuated::<_, Token![,]>::parse_terminated)? {
-                if idents.contains(&ident) {
-                    return Err(parse::Error::new(
-                        ident.span(),
-                        "element appears more than once in list",
-                    ));
-                }
-
-                idents.insert(ident);
-            }
+            let idents = parse_list(
+                &inner,
+                if ident_s == "resources" {
+                    Some(&mut peripherals)
+                } else {
+                    None
+                },
+            )?;
 
             let ident_s = ident.to_string();
             match &*ident_s {
+                "controls" => {
+                    if controls.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    controls = Some(idents);
+                }
+                "pend" => {
+                    if pend.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    pend = Some(idents);
+                }
+                "publish" => {
+                    if publish.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    publish = Some(idents);
+                }
                 "resources" => {
                     if resources.is_some() {
                         return Err(parse::Error::new(
@@ -585,9 +1473,13 @@ impl Parse for InitArgs {
         }
 
         Ok(InitArgs {
-            resources: resources.unwrap_or(Idents::new()),
-            schedule: schedule.unwrap_or(Idents::new()),
-            spawn: spawn.unwrap_or(Idents::new()),
+            controls: controls.unwrap_or(Idents::default()),
+            pend: pend.unwrap_or(Idents::default()),
+            peripherals,
+            publish: publish.unwrap_or(Idents::default()),
+            resources: resources.unwrap_or(Idents::default()),
+            schedule: schedule.unwrap_or(Idents::default()),
+            spawn: spawn.unwrap_or(Idents::default()),
         })
     }
 }
@@ -600,7 +1492,7 @@ pub struct Assign {
 pub struct Init {
     pub args: InitArgs,
     pub attrs: Vec<Attribute>,
-    pub statics: HashMap<Ident, Static>,
+    pub statics: Map<Ident, Static>,
     pub stmts: Vec<Stmt>,
     pub assigns: Vec<Assign>,
 }
@@ -647,33 +1539,76 @@ pub struct Exception {
 }
 
 pub struct ExceptionArgs {
+    /// The exception / interrupt this handler is bound to, if it's not named after it
+    pub binds: Option<Ident>,
+    /// Hardware tasks this context can enable / disable
+    pub controls: Idents,
+    /// Whether the hardware task is enabled after `init` returns; only valid for interrupts
+    pub enabled: Option<LitBool>,
+    /// Hardware tasks this context can pend
+    pub pend: Idents,
+    /// Device peripherals listed in `resources` as `device::NAME`
+    pub peripherals: Idents,
     pub priority: u8,
+    /// Topics this context can publish to
+    pub publish: Idents,
     pub resources: Idents,
     pub schedule: Idents,
     pub spawn: Idents,
+    /// Execution budget, in clock cycles, monitored by the timer queue
+    pub wcet: Option<LitInt>,
 }
 
 impl Parse for ExceptionArgs {
     fn parse(input: ParseStream) -> parse::Result<Self> {
         parse_args(input, false).map(
             |TaskArgs {
+                 binds,
+                 controls,
+                 enabled,
+                 pend,
+                 peripherals,
                  priority,
+                 publish,
                  resources,
                  schedule,
                  spawn,
+                 wcet,
                  ..
              }| {
                 ExceptionArgs {
+                    binds,
+                    controls,
+                    enabled,
+                    pend,
+                    peripherals,
                     priority,
+                    publish,
                     resources,
                     schedule,
                     spawn,
+                    wcet,
                 }
             },
         )
     }
 }
 
+impl ExceptionArgs {
+    /// Returns the name of the exception / interrupt the `handler` function is bound to
+    pub fn binds<'a>(&'a self, handler: &'a Ident) -> &'a Ident {
+        self.binds.as_ref().unwrap_or(handler)
+    }
+
+    /// Returns whether the handler is enabled after `init` returns (`enabled` argument)
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+            .as_ref()
+            .map(|enabled| enabled.value)
+            .unwrap_or(true)
+    }
+}
+
 impl Exception {
     fn check(args: ExceptionArgs, item: ItemFn) -> parse::Result<Self> {
         let valid_signature = item.vis == Visibility::Inherited
@@ -748,6 +1683,11 @@ impl Interrupt {
 }
 
 pub struct Resource {
+    /// A device peripheral (`device::NAME`); these are moved out of the device `Peripherals` before
+    /// `init` runs
+    pub peripheral: bool,
+    /// A `#[retain]` resource; it lives in the `.uninit` section and keeps its value across resets
+    pub retain: bool,
     pub singleton: bool,
     pub attrs: Vec<Attribute>,
     pub mutability: Option<Token![mut]>,
@@ -769,8 +1709,43 @@ impl Resource {
             _ => false,
         };
 
+        let retain = if let Some(pos) = item.attrs.iter().position(|attr| eq(attr, "retain")) {
+            let attr = item.attrs.remove(pos);
+            if !attr.tts.is_empty() {
+                return Err(parse::Error::new(
+                    attr.tts.span(),
+                    "`#[retain]` doesn't take any arguments",
+                ));
+            }
+
+            if item.mutability.is_none() {
+                return Err(parse::Error::new(
+                    item.ident.span(),
+                    "retained resources must be `static mut` variables",
+                ));
+            }
+
+            if uninitialized {
+                return Err(parse::Error::new(
+                    item.ident.span(),
+                    "retained resources need an initial value; it's used when the value is lost",
+                ));
+            }
+
+            true
+        } else {
+            false
+        };
+
         let pos = item.attrs.iter().position(|attr| eq(attr, "Singleton"));
 
+        if retain && pos.is_some() {
+            return Err(parse::Error::new(
+                item.ident.span(),
+                "singletons can NOT be retained",
+            ));
+        }
+
         if let Some(pos) = pos {
             item.attrs[pos].path.segments.insert(
                 0,
@@ -779,6 +1754,8 @@ impl Resource {
         }
 
         Ok(Resource {
+            peripheral: false,
+            retain,
             singleton: pos.is_some(),
             attrs: item.attrs,
             mutability: item.mutability,
@@ -788,22 +1765,212 @@ impl Resource {
     }
 }
 
-pub struct TaskArgs {
-    pub capacity: Option<u8>,
-    pub priority: u8,
-    pub resources: Idents,
-    pub spawn: Idents,
-    pub schedule: Idents,
+/// A topic (`#[topic] type Name = Message;`); its messages are delivered to all its subscribers
+pub struct Topic {
+    pub attrs: Vec<Attribute>,
+    pub ty: Box<Type>,
 }
 
-impl Default for TaskArgs {
-    fn default() -> Self {
-        TaskArgs {
-            capacity: None,
-            priority: 1,
-            resources: Idents::new(),
-            schedule: Idents::new(),
-            spawn: Idents::new(),
+impl Topic {
+    fn check(item: ItemType) -> parse::Result<Self> {
+        if item.vis != Visibility::Inherited {
+            return Err(parse::Error::new(
+                item.span(),
+                "topics must have inherited / private visibility",
+            ));
+        }
+
+        if !item.generics.params.is_empty() || item.generics.where_clause.is_some() {
+            return Err(parse::Error::new(
+                item.generics.span(),
+                "topics can't be generic",
+            ));
+        }
+
+        Ok(Topic {
+            attrs: item.attrs,
+            ty: item.ty,
+        })
+    }
+
+    /// Name of the `publish` method of the topic `name`: `name` in snake case (e.g. `ImuSample` ->
+    /// `imu_sample`)
+    pub fn method(name: &Ident) -> Ident {
+        let mut method = String::new();
+        let mut prev_is_lower = false;
+        for c in name.to_string().chars() {
+            if c.is_uppercase() && prev_is_lower {
+                method.push('_');
+            }
+
+            prev_is_lower = c.is_lowercase() || c.is_numeric();
+            method.extend(c.to_lowercase());
+        }
+
+        Ident::new(&method, name.span())
+    }
+}
+
+pub struct Pool {
+    pub args: PoolArgs,
+    pub attrs: Vec<Attribute>,
+    pub ty: Box<Type>,
+    /// Initial value of each block
+    pub expr: Box<Expr>,
+}
+
+impl Pool {
+    fn check(args: PoolArgs, item: ItemStatic) -> parse::Result<Self> {
+        if item.vis != Visibility::Inherited {
+            return Err(parse::Error::new(
+                item.span(),
+                "pools must have inherited / private visibility",
+            ));
+        }
+
+        if let Some(mutability) = item.mutability {
+            return Err(parse::Error::new(
+                mutability.span(),
+                "pools must be declared as `static`, not `static mut`",
+            ));
+        }
+
+        Ok(Pool {
+            args,
+            attrs: item.attrs,
+            ty: item.ty,
+            expr: item.expr,
+        })
+    }
+}
+
+pub struct PoolArgs {
+    /// Number of blocks; inferred from the tasks that receive `Box`es of this pool if omitted
+    pub capacity: Option<LitInt>,
+}
+
+impl Parse for PoolArgs {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        if input.is_empty() {
+            return Ok(PoolArgs { capacity: None });
+        }
+
+        let content;
+        parenthesized!(content in input);
+
+        let mut capacity = None;
+        loop {
+            if content.is_empty() {
+                break;
+            }
+
+            // #ident = ..
+            let ident: Ident = content.parse()?;
+            let _: Token![=] = content.parse()?;
+
+            let ident_s = ident.to_string();
+            match &*ident_s {
+                "capacity" => {
+                    if capacity.is_some() {
+                        return Err(parse::Error::new(
+                            ident.span(),
+                            "argument appears more than once",
+                        ));
+                    }
+
+                    // #lit
+                    let lit: LitInt = content.parse()?;
+
+                    if lit.suffix() != IntSuffix::None {
+                        return Err(parse::Error::new(
+                            lit.span(),
+                            "this literal must be unsuffixed",
+                        ));
+                    }
+
+                    if lit.value() == 0 {
+                        return Err(parse::Error::new(
+                            lit.span(),
+                            "this literal must be greater than 0",
+                        ));
+                    }
+
+                    capacity = Some(lit);
+                }
+                _ => {
+                    return Err(parse::Error::new(ident.span(), "expected: capacity"));
+                }
+            }
+
+            if content.is_empty() {
+                break;
+            }
+
+            // ,
+            let _: Token![,] = content.parse()?;
+        }
+
+        Ok(PoolArgs { capacity })
+    }
+}
+
+pub struct TaskArgs {
+    pub binds: Option<Ident>,
+    pub capacity: Option<u8>,
+    /// Hardware tasks this context can enable / disable
+    pub controls: Idents,
+    /// Relative deadline, in clock cycles; the priority level is dispatched earliest deadline first
+    pub deadline: Option<LitInt>,
+    /// Whether the hardware task is enabled after `init` returns
+    pub enabled: Option<LitBool>,
+    /// Longest time, in clock cycles, this task can go without completing an instance
+    pub heartbeat: Option<LitInt>,
+    /// Minimum time, in clock cycles, between two releases of this task through `spawn`
+    pub min_inter_arrival: Option<LitInt>,
+    /// Operating modes in which this time-triggered task is released; empty means all of them
+    pub modes: Idents,
+    /// Sub-priority within the priority level; lower values are dispatched first
+    pub order: Option<LitInt>,
+    /// Hardware tasks this context can pend
+    pub pend: Idents,
+    /// Device peripherals listed in `resources` as `device::NAME`
+    pub peripherals: Idents,
+    pub priority: u8,
+    /// Topics this context can publish to
+    pub publish: Idents,
+    pub resources: Idents,
+    pub spawn: Idents,
+    pub schedule: Idents,
+    /// Slots of the time-triggered schedule table in which this task is released
+    pub slots: Vec<Slot>,
+    /// Topics whose messages this task receives; only valid for software tasks
+    pub subscribe: Idents,
+    /// Execution budget, in clock cycles, monitored by the timer queue
+    pub wcet: Option<LitInt>,
+}
+
+impl Default for TaskArgs {
+    fn default() -> Self {
+        TaskArgs {
+            binds: None,
+            capacity: None,
+            controls: Idents::default(),
+            deadline: None,
+            enabled: None,
+            heartbeat: None,
+            min_inter_arrival: None,
+            modes: Idents::default(),
+            order: None,
+            pend: Idents::default(),
+            peripherals: Idents::default(),
+            priority: 1,
+            publish: Idents::default(),
+            resources: Idents::default(),
+            schedule: Idents::default(),
+            slots: vec![],
+            spawn: Idents::default(),
+            subscribe: Idents::default(),
+            wcet: None,
         }
     }
 }
@@ -815,16 +1982,30 @@ impl Parse for TaskArgs {
 }
 
 // Parser shared by TaskArgs and ExceptionArgs / InterruptArgs
-fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskArgs> {
+fn parse_args(input: ParseStream, software_task: bool) -> parse::Result<TaskArgs> {
     if input.is_empty() {
         return Ok(TaskArgs::default());
     }
 
+    let mut binds = None;
     let mut capacity = None;
+    let mut controls = None;
+    let mut deadline = None;
+    let mut enabled = None;
+    let mut heartbeat = None;
+    let mut min_inter_arrival = None;
+    let mut modes = None;
+    let mut pend = None;
+    let mut peripherals = Idents::default();
+    let mut order = None;
     let mut priority = None;
+    let mut publish = None;
     let mut resources = None;
     let mut schedule = None;
+    let mut slots = None;
     let mut spawn = None;
+    let mut subscribe = None;
+    let mut wcet = None;
 
     let content;
     parenthesized!(content in input);
@@ -839,7 +2020,29 @@ fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskAr
 
         let ident_s = ident.to_string();
         match &*ident_s {
-            "capacity" if accept_capacity => {
+            "binds" if !software_task => {
+                if binds.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #ident
+                binds = Some(content.parse()?);
+            }
+            "enabled" if !software_task => {
+                if enabled.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #lit
+                enabled = Some(content.parse()?);
+            }
+            "capacity" if software_task => {
                 // #lit
                 let lit: LitInt = content.parse()?;
 
@@ -860,6 +2063,208 @@ fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskAr
 
                 capacity = Some(value as u8);
             }
+            "deadline" if software_task && cfg!(not(feature = "timer-queue")) => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "The `deadline` argument requires that the `timer-queue` feature is \
+                     enabled in the `cortex-m-rtfm` crate",
+                ));
+            }
+            "deadline" if software_task => {
+                if deadline.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #lit
+                let lit = parse_cycles(&content)?;
+
+                if lit.value() == 0 {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "this literal must be in the range 1...2147483647",
+                    ));
+                }
+
+                deadline = Some(lit);
+            }
+            "heartbeat" if software_task && cfg!(not(feature = "timer-queue")) => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "The `heartbeat` argument requires that the `timer-queue` feature is \
+                     enabled in the `cortex-m-rtfm` crate",
+                ));
+            }
+            "heartbeat" if software_task => {
+                if heartbeat.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #lit
+                let lit = parse_cycles(&content)?;
+
+                if lit.value() == 0 {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "this literal must be in the range 1...2147483647",
+                    ));
+                }
+
+                heartbeat = Some(lit);
+            }
+            "min_inter_arrival" if software_task && cfg!(not(feature = "timer-queue")) => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "The `min_inter_arrival` argument requires that the `timer-queue` feature is \
+                     enabled in the `cortex-m-rtfm` crate",
+                ));
+            }
+            "min_inter_arrival" if software_task => {
+                if min_inter_arrival.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #lit
+                let lit = parse_cycles(&content)?;
+
+                if lit.value() == 0 {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "this literal must be in the range 1...2147483647",
+                    ));
+                }
+
+                min_inter_arrival = Some(lit);
+            }
+            "wcet" if cfg!(not(feature = "timer-queue")) => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "The `wcet` argument requires that the `timer-queue` feature is \
+                     enabled in the `cortex-m-rtfm` crate",
+                ));
+            }
+            "wcet" => {
+                if wcet.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #lit
+                let lit = parse_cycles(&content)?;
+
+                if lit.value() == 0 {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "this literal must be in the range 1...2147483647",
+                    ));
+                }
+
+                wcet = Some(lit);
+            }
+            "slots" if software_task && cfg!(not(feature = "timer-queue")) => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "The `slots` argument requires that the `timer-queue` feature is \
+                     enabled in the `cortex-m-rtfm` crate",
+                ));
+            }
+            "slots" if software_task => {
+                if slots.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // [#(#lit..#lit),*]
+                let inner;
+                bracketed!(inner in content);
+                let mut list = vec![];
+                loop {
+                    if inner.is_empty() {
+                        break;
+                    }
+
+                    let start = parse_cycles(&inner)?;
+                    let _: Token![..] = inner.parse()?;
+                    let end = parse_cycles(&inner)?;
+
+                    if end.value() <= start.value() {
+                        return Err(parse::Error::new(
+                            end.span(),
+                            "the end of a slot must come after its start",
+                        ));
+                    }
+
+                    list.push(Slot { start, end });
+
+                    if inner.is_empty() {
+                        break;
+                    }
+
+                    // ,
+                    let _: Token![,] = inner.parse()?;
+                }
+
+                slots = Some(list);
+            }
+            "modes" if software_task && cfg!(not(feature = "timer-queue")) => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "The `modes` argument requires that the `timer-queue` feature is \
+                     enabled in the `cortex-m-rtfm` crate",
+                ));
+            }
+            "modes" if software_task => {
+                if modes.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // .. [#(#idents)*]
+                let inner;
+                bracketed!(inner in content);
+                modes = Some(parse_list(&inner, None)?);
+            }
+            "order" if software_task => {
+                if order.is_some() {
+                    return Err(parse::Error::new(
+                        ident.span(),
+                        "argument appears more than once",
+                    ));
+                }
+
+                // #lit
+                let lit: LitInt = content.parse()?;
+
+                if lit.suffix() != IntSuffix::None {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "this literal must be unsuffixed",
+                    ));
+                }
+
+                if lit.value() > u64::from(u8::MAX) {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "this literal must be in the range 0...255",
+                    ));
+                }
+
+                order = Some(lit);
+            }
             "priority" => {
                 // #lit
                 let lit: LitInt = content.parse()?;
@@ -879,6 +2284,14 @@ fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskAr
                     ));
                 }
 
+                if value == 0 && !software_task {
+                    return Err(parse::Error::new(
+                        lit.span(),
+                        "only software tasks can run at priority 0; the priority of hardware \
+                         tasks must be in the range 1...255",
+                    ));
+                }
+
                 priority = Some(value as u8);
             }
             "schedule" if cfg!(not(feature = "timer-queue")) => {
@@ -888,23 +2301,56 @@ fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskAr
                      enabled in the `cortex-m-rtfm` crate",
                 ));
             }
-            "resources" | "schedule" | "spawn" => {
+            "subscribe" if !software_task => {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "only software tasks can subscribe to topics",
+                ));
+            }
+            "controls" | "pend" | "publish" | "resources" | "schedule" | "spawn" | "subscribe" => {
                 // .. [#(#idents)*]
                 let inner;
                 bracketed!(inner in content);
-                let mut idents = Idents::new();
-                for ident in inner.call(Punctuated::<_, Token![,]>::parse_terminated)? {
-                    if idents.contains(&ident) {
-                        return Err(parse::Error::new(
-                            ident.span(),
-                            "element appears more than once in list",
-                        ));
+                let idents = parse_list(
+                    &inner,
+                    if ident_s == "resources" {
+                        Some(&mut peripherals)
+                    } else {
+                        None
+                    },
+                )?;
+
+                match &*ident_s {
+                    "controls" => {
+                        if controls.is_some() {
+                            return Err(parse::Error::new(
+                                ident.span(),
+                                "argument appears more than once",
+                            ));
+                        }
+
+                        controls = Some(idents);
                     }
+                    "pend" => {
+                        if pend.is_some() {
+                            return Err(parse::Error::new(
+                                ident.span(),
+                                "argument appears more than once",
+                            ));
+                        }
 
-                    idents.insert(ident);
-                }
+                        pend = Some(idents);
+                    }
+                    "publish" => {
+                        if publish.is_some() {
+                            return Err(parse::Error::new(
+                                ident.span(),
+                                "argument appears more than once",
+                            ));
+                        }
 
-                match &*ident_s {
+                        publish = Some(idents);
+                    }
                     "resources" => {
                         if resources.is_some() {
                             return Err(parse::Error::new(
@@ -935,13 +2381,24 @@ fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskAr
 
                         spawn = Some(idents);
                     }
+                    "subscribe" => {
+                        if subscribe.is_some() {
+                            return Err(parse::Error::new(
+                                ident.span(),
+                                "argument appears more than once",
+                            ));
+                        }
+
+                        subscribe = Some(idents);
+                    }
                     _ => unreachable!(),
                 }
             }
             _ => {
                 return Err(parse::Error::new(
                     ident.span(),
-                    "expected one of: priority, resources, schedule or spawn",
+                    "expected one of: controls, pend, priority, publish, resources, schedule or \
+                     spawn",
                 ))
             }
         }
@@ -955,14 +2412,109 @@ fn parse_args(input: ParseStream, accept_capacity: bool) -> parse::Result<TaskAr
     }
 
     Ok(TaskArgs {
+        binds,
         capacity,
+        controls: controls.unwrap_or(Idents::default()),
+        deadline,
+        enabled,
+        heartbeat,
+        min_inter_arrival,
+        modes: modes.unwrap_or(Idents::default()),
+        order,
+        pend: pend.unwrap_or(Idents::default()),
+        peripherals,
         priority: priority.unwrap_or(1),
-        resources: resources.unwrap_or(Idents::new()),
-        schedule: schedule.unwrap_or(Idents::new()),
-        spawn: spawn.unwrap_or(Idents::new()),
+        publish: publish.unwrap_or(Idents::default()),
+        resources: resources.unwrap_or(Idents::default()),
+        schedule: schedule.unwrap_or(Idents::default()),
+        slots: slots.unwrap_or_else(Vec::new),
+        spawn: spawn.unwrap_or(Idents::default()),
+        subscribe: subscribe.unwrap_or(Idents::default()),
+        wcet,
     })
 }
 
+/// A slot of the time-triggered schedule table: `start..end`, in clock cycles from the start of the
+/// major frame
+pub struct Slot {
+    pub start: LitInt,
+    pub end: LitInt,
+}
+
+// Parses an unsuffixed integer literal that represents a number of clock cycles. Instants can't be
+// more than `i32::MAX` cycles apart so that's the largest valid value
+fn parse_cycles(input: ParseStream) -> parse::Result<LitInt> {
+    let lit: LitInt = input.parse()?;
+
+    if lit.suffix() != IntSuffix::None {
+        return Err(parse::Error::new(
+            lit.span(),
+            "this literal must be unsuffixed",
+        ));
+    }
+
+    if lit.value() > i32::MAX as u64 {
+        return Err(parse::Error::new(
+            lit.span(),
+            "this literal must be in the range 0...2147483647",
+        ));
+    }
+
+    Ok(lit)
+}
+
+// Parses the elements of a `controls`, `modes`, `pend`, `publish`, `resources`, `schedule`, `spawn`
+// or `subscribe` list. Device peripherals (`device::NAME`) are only accepted in `resources` lists;
+// they are also added to `peripherals`
+fn parse_list(content: ParseStream, mut peripherals: Option<&mut Idents>) -> parse::Result<Idents> {
+    let mut idents = Idents::default();
+    loop {
+        if content.is_empty() {
+            break;
+        }
+
+        // #ident | device::#ident
+        let mut ident: Ident = content.parse()?;
+        if content.peek(Token![::]) {
+            let _: Token![::] = content.parse()?;
+
+            if ident.to_string() != "device" {
+                return Err(parse::Error::new(ident.span(), "expected `device`"));
+            }
+
+            let name: Ident = content.parse()?;
+            if let Some(peripherals) = peripherals.as_mut() {
+                peripherals.insert(name.clone());
+            } else {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    "device peripherals can only be listed in `resources`",
+                ));
+            }
+
+            ident = name;
+        }
+
+        if idents.contains(&ident) {
+            return Err(parse::Error::new(
+                ident.span(),
+                "element appears more than once in list",
+            ));
+        }
+
+        idents.insert(ident);
+
+        if content.is_empty() {
+            break;
+        }
+
+        // ,
+        let _: Token![,] = content.parse()?;
+    }
+
+    Ok(idents)
+}
+
 pub struct Static {
     pub attrs: Vec<Attribute>,
     pub ty: Box<Type>,
@@ -970,8 +2522,8 @@ pub struct Static {
 }
 
 impl Static {
-    fn parse(items: Vec<ItemStatic>) -> parse::Result<HashMap<Ident, Static>> {
-        let mut statics = HashMap::new();
+    fn parse(items: Vec<ItemStatic>) -> parse::Result<Map<Ident, Static>> {
+        let mut statics = Map::default();
 
         for item in items {
             if statics.contains_key(&item.ident) {
@@ -999,7 +2551,9 @@ pub struct Task {
     pub args: TaskArgs,
     pub attrs: Vec<Attribute>,
     pub inputs: Vec<ArgCaptured>,
-    pub statics: HashMap<Ident, Static>,
+    /// `async fn`
+    pub is_async: bool,
+    pub statics: Map<Ident, Static>,
     pub stmts: Vec<Stmt>,
 }
 
@@ -1007,7 +2561,6 @@ impl Task {
     fn check(args: TaskArgs, item: ItemFn) -> parse::Result<Self> {
         let valid_signature = item.vis == Visibility::Inherited
             && item.constness.is_none()
-            && item.asyncness.is_none()
             && item.abi.is_none()
             && item.decl.generics.params.is_empty()
             && item.decl.generics.where_clause.is_none()
@@ -1019,7 +2572,7 @@ impl Task {
         if !valid_signature {
             return Err(parse::Error::new(
                 span,
-                "`task` handlers must have type signature `[unsafe] fn(..)`",
+                "`task` handlers must have type signature `[async] [unsafe] fn(..)`",
             ));
         }
 
@@ -1041,6 +2594,7 @@ impl Task {
             args,
             attrs: item.attrs,
             inputs,
+            is_async: item.asyncness.is_some(),
             statics: Static::parse(statics)?,
             stmts,
         })
@@ -1049,11 +2603,13 @@ impl Task {
 
 pub struct FreeInterrupt {
     pub attrs: Vec<Attribute>,
+    /// The priority level this interrupt must dispatch, specified with `#[priority = <integer>]`
+    pub priority: Option<LitInt>,
 }
 
 impl FreeInterrupt {
     fn parse(mod_: ItemForeignMod) -> parse::Result<FreeInterrupts> {
-        let mut free_interrupts = FreeInterrupts::new();
+        let mut free_interrupts = FreeInterrupts::default();
 
         for item in mod_.items {
             if let ForeignItem::Fn(f) = item {
@@ -1078,7 +2634,38 @@ impl FreeInterrupt {
                     ));
                 }
 
-                free_interrupts.insert(f.ident, FreeInterrupt { attrs: f.attrs });
+                let mut attrs = f.attrs;
+                let priority = if let Some(pos) = attrs.iter().position(|attr| eq(attr, "priority"))
+                {
+                    let attr = attrs.remove(pos);
+
+                    // = #lit
+                    let lit = (|input: ParseStream| -> parse::Result<LitInt> {
+                        let _: Token![=] = input.parse()?;
+                        input.parse()
+                    })
+                    .parse2(attr.tts)?;
+
+                    if lit.suffix() != IntSuffix::None {
+                        return Err(parse::Error::new(
+                            lit.span(),
+                            "this literal must be unsuffixed",
+                        ));
+                    }
+
+                    if lit.value() == 0 || lit.value() > u64::from(u8::MAX) {
+                        return Err(parse::Error::new(
+                            lit.span(),
+                            "this literal must be in the range 1...255",
+                        ));
+                    }
+
+                    Some(lit)
+                } else {
+                    None
+                };
+
+                free_interrupts.insert(f.ident, FreeInterrupt { attrs, priority });
             } else {
                 return Err(parse::Error::new(
                     mod_.abi.extern_token.span(),
@@ -0,0 +1,265 @@
+//! Crash reports preserved across resets
+//!
+//! This module is only available when the `crash-report` feature is enabled. In this mode the
+//! runtime keeps track of the task that's currently running in a small record that lives in the
+//! `.uninit` section, which is not initialized on reset. When the device crashes the record is
+//! completed with a snapshot of the state of the application:
+//!
+//! - the task that was running and its dynamic priority, which accounts for the resources it had
+//! locked (on ARMv6-M and ARMv8-M Baseline only the static priority of the task is recorded),
+//! - and the number of pending instances of each software task, in alphabetical order (an async
+//! task counts as pending until it completes); at most the first 32 software tasks are recorded.
+//!
+//! The runtime provides the `HardFault` handler, which takes the snapshot and resets the device,
+//! so the application must not define its own. Panics are recorded by calling [`panic`] from the
+//! `#[panic_handler]`.
+//!
+//! On the next boot `init` receives the report of the previous crash, if there was one, as
+//! `crash: Option<rtfm::crash::Report>`. The report is handed out only once; it's cleared when
+//! `init` starts.
+//!
+//! [`panic`]: fn.panic.html
+
+use core::{fmt, ptr};
+
+#[cfg(any(armv7m, armv8m_main))]
+use cortex_m::register::basepri;
+use cortex_m::peripheral::SCB;
+use cortex_m_rt::{exception, ExceptionFrame};
+
+// Number of task queues that fit in the record
+const QUEUES: usize = 32;
+
+// Marks a record that holds the snapshot of a crash; anything else is a cold boot (random RAM) or
+// a crash report that has already been handed out
+const MAGIC: u32 = 0xC4A5_4ED5;
+
+#[repr(C)]
+struct Record {
+    // The running task; 0 means none (`init` has not started yet or the device is sleeping)
+    current: u8,
+    // Snapshot of the crash; only valid if `magic == MAGIC`
+    task: u8,
+    priority: u8,
+    cause: u8,
+    pc: u32,
+    queues: [u8; QUEUES],
+    magic: u32,
+}
+
+// NOTE(static mut) the fields are only accessed using volatile operations; single byte writes are
+// atomic so a preempting task always restores the value it found in `current`
+#[link_section = ".uninit.rtfm.crash"]
+static mut RECORD: Record = Record {
+    current: 0,
+    task: 0,
+    priority: 0,
+    cause: 0,
+    pc: 0,
+    queues: [0; QUEUES],
+    magic: 0,
+};
+
+extern "Rust" {
+    // Defined by the `app` attribute. Writes the occupancy of the task queues into `queues` and
+    // returns the dynamic priority of the given (running) task
+    #[link_name = "rtfm::crash::inspect"]
+    fn inspect(task: u8, queues: &mut [u8]) -> u8;
+}
+
+/// What caused the crash
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Cause {
+    /// A hard fault exception
+    HardFault,
+    /// A panic reported through [`panic`](fn.panic.html)
+    Panic,
+}
+
+/// Report of the crash that reset the device
+pub struct Report {
+    cause: Cause,
+    pc: u32,
+    task: u8,
+    priority: u8,
+    queues: [u8; QUEUES],
+    contexts: &'static [&'static str],
+    tasks: &'static [&'static str],
+}
+
+impl Report {
+    /// What caused the crash
+    pub fn cause(&self) -> Cause {
+        self.cause
+    }
+
+    /// The address of the instruction that caused the hard fault
+    ///
+    /// Returns `None` if the crash was caused by a panic
+    pub fn pc(&self) -> Option<u32> {
+        if self.cause == Cause::HardFault {
+            Some(self.pc)
+        } else {
+            None
+        }
+    }
+
+    /// The task that was running when the device crashed
+    ///
+    /// Returns `None` if the device crashed before `init` started or while it was sleeping
+    pub fn task(&self) -> Option<&'static str> {
+        if self.task == 0 {
+            None
+        } else {
+            self.contexts.get(usize::from(self.task) - 1).cloned()
+        }
+    }
+
+    /// The dynamic priority at which the device crashed
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// The number of pending instances of each software task when the device crashed
+    pub fn queues(&self) -> impl Iterator<Item = (&'static str, u8)> + '_ {
+        self.tasks.iter().cloned().zip(self.queues.iter().cloned())
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub unsafe fn take(
+        contexts: &'static [&'static str],
+        tasks: &'static [&'static str],
+    ) -> Option<Self> {
+        if ptr::read_volatile(&RECORD.magic) != MAGIC {
+            return None;
+        }
+        ptr::write_volatile(&mut RECORD.magic, 0);
+
+        let task = ptr::read_volatile(&RECORD.task);
+        Some(Report {
+            cause: if ptr::read_volatile(&RECORD.cause) == Cause::HardFault as u8 {
+                Cause::HardFault
+            } else {
+                Cause::Panic
+            },
+            pc: ptr::read_volatile(&RECORD.pc),
+            // the record may come from a different firmware
+            task: if usize::from(task) > contexts.len() {
+                0
+            } else {
+                task
+            },
+            priority: ptr::read_volatile(&RECORD.priority),
+            queues: ptr::read_volatile(&RECORD.queues),
+            contexts,
+            tasks: if tasks.len() > QUEUES {
+                &tasks[..QUEUES]
+            } else {
+                tasks
+            },
+        })
+    }
+}
+
+impl fmt::Debug for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Queues<'a>(&'a Report);
+
+        impl<'a> fmt::Debug for Queues<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map().entries(self.0.queues()).finish()
+            }
+        }
+
+        f.debug_struct("Report")
+            .field("cause", &self.cause)
+            .field("pc", &self.pc())
+            .field("task", &self.task())
+            .field("priority", &self.priority)
+            .field("queues", &Queues(self))
+            .finish()
+    }
+}
+
+/// Records a crash caused by a panic and resets the device
+///
+/// Call this function from the `#[panic_handler]`.
+pub fn panic() -> ! {
+    unsafe {
+        snapshot(Cause::Panic, 0);
+        reset()
+    }
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+#[doc(hidden)]
+#[inline(always)]
+pub fn enter(task: u8) -> u8 {
+    unsafe {
+        let previous = ptr::read_volatile(&RECORD.current);
+        ptr::write_volatile(&mut RECORD.current, task);
+        previous
+    }
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+#[doc(hidden)]
+#[inline(always)]
+pub fn set(task: u8) {
+    unsafe { ptr::write_volatile(&mut RECORD.current, task) }
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+///
+/// Returns the dynamic priority of a task that runs at `priority`
+#[doc(hidden)]
+#[cfg_attr(not(any(armv7m, armv8m_main)), allow(unused_variables))]
+pub fn priority(priority: u8, nvic_prio_bits: u8) -> u8 {
+    #[cfg(any(armv7m, armv8m_main))]
+    {
+        let hw = u16::from(basepri::read());
+        if hw != 0 {
+            let logical = (1 << nvic_prio_bits) - (hw >> (8 - nvic_prio_bits));
+            if logical > u16::from(priority) {
+                return logical as u8;
+            }
+        }
+    }
+
+    priority
+}
+
+unsafe fn snapshot(cause: Cause, pc: u32) {
+    let task = ptr::read_volatile(&RECORD.current);
+    // the reset ends the task; a crash that happens before `main` runs again (e.g. in
+    // `#[pre_init]`) must not inspect the task queues, which are not initialized at that point
+    ptr::write_volatile(&mut RECORD.current, 0);
+
+    let mut queues = [0; QUEUES];
+    // the task queues are initialized right before `init` starts
+    let priority = if task == 0 {
+        0
+    } else {
+        inspect(task, &mut queues)
+    };
+
+    ptr::write_volatile(&mut RECORD.task, task);
+    ptr::write_volatile(&mut RECORD.priority, priority);
+    ptr::write_volatile(&mut RECORD.cause, cause as u8);
+    ptr::write_volatile(&mut RECORD.pc, pc);
+    ptr::write_volatile(&mut RECORD.queues, queues);
+    ptr::write_volatile(&mut RECORD.magic, MAGIC);
+}
+
+unsafe fn reset() -> ! {
+    SCB::sys_reset()
+}
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    unsafe {
+        snapshot(Cause::HardFault, ef.pc);
+        reset()
+    }
+}
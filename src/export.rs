@@ -1,23 +1,37 @@
 /// IMPLEMENTATION DETAILS. DO NOT USE ANYTHING IN THIS MODULE
-use core::{hint, ptr};
+use core::{
+    cell::Cell,
+    future::Future,
+    hint, mem,
+    pin::Pin,
+    ptr,
+    sync::atomic::{self, AtomicBool},
+    task::{Context, RawWaker, RawWakerVTable, Waker},
+};
 
-#[cfg(armv7m)]
+#[cfg(any(armv7m, armv8m_main))]
 use cortex_m::register::basepri;
+use cortex_m::{interrupt::Nr, peripheral::NVIC};
 pub use cortex_m::{
     asm::wfi, interrupt, peripheral::scb::SystemHandler, peripheral::syst::SystClkSource,
     peripheral::Peripherals,
 };
-pub use cortex_m_rt::{entry, exception};
+pub use cortex_m_rt::{entry, exception, pre_init};
 pub use heapless::consts;
 use heapless::spsc::Queue;
 
 #[cfg(feature = "timer-queue")]
-pub use crate::tq::{isr as sys_tick, NotReady, TimerQueue};
+use crate::Instant;
+#[cfg(feature = "timer-queue")]
+pub use crate::tq::{
+    isr as sys_tick, Budget, BudgetCheck, Edf, Heartbeat, NotReady, TimerQueue,
+};
 
 pub type FreeQueue<N> = Queue<u8, N>;
 pub type ReadyQueue<T, N> = Queue<(T, u8), N>;
+pub type TaskQueue<N> = Queue<u8, N>;
 
-#[cfg(armv7m)]
+#[cfg(any(armv7m, armv8m_main))]
 #[inline(always)]
 pub fn run<F>(f: F)
 where
@@ -28,7 +42,7 @@ where
     unsafe { basepri::write(initial) }
 }
 
-#[cfg(not(armv7m))]
+#[cfg(not(any(armv7m, armv8m_main)))]
 #[inline(always)]
 pub fn run<F>(f: F)
 where
@@ -37,6 +51,102 @@ where
     f();
 }
 
+#[inline(always)]
+pub fn pend<I>(interrupt: I)
+where
+    I: Nr,
+{
+    NVIC::pend(interrupt)
+}
+
+// Source masking (used on devices without BASEPRI)
+#[inline(always)]
+pub unsafe fn mask<I>(interrupt: I)
+where
+    I: Nr,
+{
+    let nr = interrupt.nr();
+    (*NVIC::ptr()).icer[usize::from(nr / 32)].write(1 << (nr % 32))
+}
+
+#[inline(always)]
+pub unsafe fn unmask<I>(interrupt: I)
+where
+    I: Nr,
+{
+    let nr = interrupt.nr();
+    (*NVIC::ptr()).iser[usize::from(nr / 32)].write(1 << (nr % 32))
+}
+
+// State of a hardware task that has a `Control` handle
+//
+// NOTE(Sync) only modified in critical sections, or by a `claim` that has masked the interrupt
+pub struct ControlState {
+    // The task has not been disabled through its `Control` handle
+    enabled: Cell<bool>,
+    // The interrupt is masked by a `claim` (only used on devices without BASEPRI)
+    masked: Cell<bool>,
+}
+
+unsafe impl Sync for ControlState {}
+
+impl ControlState {
+    pub const fn new(enabled: bool) -> Self {
+        ControlState {
+            enabled: Cell::new(enabled),
+            masked: Cell::new(false),
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    pub unsafe fn enable(&self, nr: u8) {
+        interrupt::free(|_| {
+            self.enabled.set(true);
+
+            // a `claim` will unmask the interrupt when it ends
+            if !self.masked.get() {
+                (*NVIC::ptr()).iser[usize::from(nr / 32)].write(1 << (nr % 32))
+            }
+        })
+    }
+
+    pub unsafe fn disable(&self, nr: u8) {
+        interrupt::free(|_| {
+            self.enabled.set(false);
+            (*NVIC::ptr()).icer[usize::from(nr / 32)].write(1 << (nr % 32))
+        })
+    }
+
+    // Source masking: used by `claim` instead of `mask` / `unmask`
+    #[inline(always)]
+    pub unsafe fn mask<I>(&self, interrupt: I)
+    where
+        I: Nr,
+    {
+        self.masked.set(true);
+        mask(interrupt);
+    }
+
+    #[inline(always)]
+    pub unsafe fn unmask<I>(&self, interrupt: I)
+    where
+        I: Nr,
+    {
+        interrupt::free(|_| {
+            self.masked.set(false);
+
+            // don't re-enable a task that was disabled while the `claim` was in progress
+            if self.enabled.get() {
+                unmask(interrupt);
+            }
+        })
+    }
+}
+
 // TODO(MaybeUninit) Until core::mem::MaybeUninit is stabilized we use our own (inefficient)
 // implementation
 pub struct MaybeUninit<T> {
@@ -69,6 +179,249 @@ impl<T> MaybeUninit<T> {
     }
 }
 
+// Validity tag of a `#[retain]` resource; like the resource it lives in the `.uninit` section so
+// it survives resets. The resource holds a retained value only if both words of the tag match the
+// key of its declaration; on a cold boot RAM holds garbage and after a firmware update that
+// changes the declaration the key changes
+#[repr(C)]
+pub struct Retention {
+    key: u32,
+    check: u32,
+}
+
+impl Retention {
+    pub const fn new() -> Self {
+        Retention { key: 0, check: 0 }
+    }
+
+    pub fn is_valid<T>(&self, key: u32) -> bool {
+        let key = layout_key::<T>(key);
+        unsafe { ptr::read_volatile(&self.key) == key && ptr::read_volatile(&self.check) == !key }
+    }
+
+    pub fn set<T>(&mut self, key: u32) {
+        let key = layout_key::<T>(key);
+        unsafe {
+            ptr::write_volatile(&mut self.key, key);
+            ptr::write_volatile(&mut self.check, !key);
+        }
+    }
+}
+
+// The key of a declaration only covers its tokens; the layout of the type it names can still
+// change between firmware versions (e.g. a field is added to a struct declared elsewhere)
+fn layout_key<T>(key: u32) -> u32 {
+    const FNV_PRIME: u32 = 0x0100_0193;
+
+    [mem::size_of::<T>() as u32, mem::align_of::<T>() as u32]
+        .iter()
+        .fold(key, |key, word| (key ^ word).wrapping_mul(FNV_PRIME))
+}
+
+// Executors of async software tasks
+//
+// The background (priority 0) level has no interrupt to dispatch its tasks; they are run from the
+// idle loop instead and this flag stands in for the pending bit of the dispatcher
+pub struct Background {
+    pending: AtomicBool,
+}
+
+impl Background {
+    pub const fn new() -> Self {
+        Background {
+            pending: AtomicBool::new(false),
+        }
+    }
+
+    #[inline(always)]
+    pub fn pend(&self) {
+        self.pending.store(true, atomic::Ordering::Release);
+    }
+
+    // Runs the background tasks from a user defined `idle`
+    #[inline(always)]
+    pub unsafe fn run(&self, dispatch: unsafe fn()) {
+        self.pending.store(false, atomic::Ordering::Release);
+        dispatch();
+    }
+
+    // The idle loop used when there's no user defined `idle`
+    pub unsafe fn idle(&self, dispatch: unsafe fn()) -> ! {
+        loop {
+            // NOTE the flag is checked with interrupts disabled so that a task spawned right
+            // before `wfi` still wakes up the processor (`wfi` ignores PRIMASK)
+            interrupt::disable();
+            let pending = self.pending.load(atomic::Ordering::Acquire);
+            if pending {
+                self.pending.store(false, atomic::Ordering::Release);
+            } else {
+                wfi();
+            }
+            interrupt::enable();
+
+            if pending {
+                dispatch();
+            }
+        }
+    }
+}
+
+// NOTE the executors are allocated on the stack of `main` (which never returns) because the type
+// of the future returned by an `async fn` can't be named, so it can't be stored in a `static`.
+// The address of each executor is stored in a `static mut` pointer and the dispatchers recover its
+// type from the task constructor (see `executor_ref`)
+
+// State that doesn't depend on the type of the future; this is what the `Waker` points to
+pub struct Header {
+    // The future needs to be polled
+    woken: AtomicBool,
+    // Instant of the timer queue entry that will wake this task, if any. Only accessed with the
+    // timer queue locked
+    #[cfg(feature = "timer-queue")]
+    armed: Cell<Option<Instant>>,
+    // Pends the dispatcher of this task
+    pend: fn(),
+}
+
+unsafe impl Sync for Header {}
+
+// What `Header::arm` requires from the timer queue
+#[cfg(feature = "timer-queue")]
+pub enum Arm {
+    // The task has no entry in the timer queue; enqueue one
+    Enqueue,
+    // The entry of the task wakes it up too late; move it to the requested instant
+    Advance,
+    // The entry of the task wakes it up before the requested instant; the task will register the
+    // instant again when it's polled
+    Keep,
+}
+
+impl Header {
+    pub const fn new(pend: fn()) -> Self {
+        Header {
+            woken: AtomicBool::new(false),
+            #[cfg(feature = "timer-queue")]
+            armed: Cell::new(None),
+            pend,
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_woken(&self) -> bool {
+        self.woken.load(atomic::Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    pub fn wake(&self) {
+        self.woken.store(true, atomic::Ordering::Release);
+        (self.pend)();
+    }
+
+    // NOTE(unsafe) must be called with the timer queue locked
+    #[cfg(feature = "timer-queue")]
+    #[inline(always)]
+    pub unsafe fn arm(&self, instant: Instant) -> Arm {
+        match self.armed.get() {
+            None => {
+                self.armed.set(Some(instant));
+                Arm::Enqueue
+            }
+            Some(armed) if instant < armed => {
+                self.armed.set(Some(instant));
+                Arm::Advance
+            }
+            Some(_) => Arm::Keep,
+        }
+    }
+
+    // NOTE(unsafe) must be called with the timer queue locked
+    #[cfg(feature = "timer-queue")]
+    #[inline(always)]
+    pub unsafe fn disarm(&self) {
+        self.armed.set(None);
+    }
+}
+
+pub struct Executor<F> {
+    header: &'static Header,
+    // The free queue slot held by the running instance
+    index: u8,
+    future: Option<F>,
+}
+
+impl<F> Executor<F>
+where
+    F: Future<Output = ()>,
+{
+    #[inline(always)]
+    pub fn spawn(&mut self, index: u8, future: F) {
+        self.index = index;
+        self.future = Some(future);
+    }
+
+    // Returns the free queue slot of the task instance if it has completed
+    pub fn poll(&mut self) -> Option<u8> {
+        self.header.woken.store(false, atomic::Ordering::Release);
+
+        let done = if let Some(future) = self.future.as_mut() {
+            let waker = unsafe {
+                Waker::from_raw(RawWaker::new(
+                    self.header as *const Header as *const (),
+                    &WAKER_VTABLE,
+                ))
+            };
+
+            // NOTE(unsafe) the executor never moves: it lives on the stack of `main`
+            let future = unsafe { Pin::new_unchecked(future) };
+            future.poll(&mut Context::from_waker(&waker)).is_ready()
+        } else {
+            false
+        };
+
+        if done {
+            self.future = None;
+            Some(self.index)
+        } else {
+            None
+        }
+    }
+}
+
+#[inline(always)]
+pub fn executor<A, F, T>(_constructor: T, header: &'static Header) -> Executor<F>
+where
+    T: FnOnce(A) -> F,
+{
+    Executor {
+        header,
+        index: 0,
+        future: None,
+    }
+}
+
+// `_constructor` must be the same task constructor that was passed to `executor`
+#[inline(always)]
+pub unsafe fn executor_ref<A, F, T>(_constructor: T, ptr: *mut ()) -> &'static mut Executor<F>
+where
+    T: FnOnce(A) -> F,
+{
+    &mut *(ptr as *mut Executor<F>)
+}
+
+static WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(waker_clone, waker_wake, waker_wake, waker_drop);
+
+unsafe fn waker_clone(header: *const ()) -> RawWaker {
+    RawWaker::new(header, &WAKER_VTABLE)
+}
+
+unsafe fn waker_wake(header: *const ()) {
+    (*(header as *const Header)).wake()
+}
+
+unsafe fn waker_drop(_: *const ()) {}
+
 #[inline(always)]
 pub fn assert_send<T>()
 where
@@ -76,6 +429,20 @@ where
 {
 }
 
+#[inline(always)]
+pub fn assert_clone<T>()
+where
+    T: Clone,
+{
+}
+
+#[inline(always)]
+pub fn assert_copy<T>()
+where
+    T: Copy,
+{
+}
+
 #[inline(always)]
 pub fn assert_sync<T>()
 where
@@ -0,0 +1,162 @@
+//! Run-time introspection of the task queues
+//!
+//! This module is only available when the `introspect` feature is enabled. The `app` attribute
+//! will generate an `introspect` module in the root of the crate that gives access to the
+//! statistics (`Stats`) of each software task, of the ready queue of each priority level and of
+//! the timer queue.
+//!
+//! # Symbol table
+//!
+//! The `app` attribute also emits a table of `Symbol`s, exported as `RTFM_INTROSPECT`, into the
+//! `.rtfm_introspect` linker section; the number of entries is the size of the section divided by
+//! 12 (the size of a `Symbol`). Both `Symbol` and `Stats` are `#[repr(C)]` so an external tool
+//! (e.g. a debugger script) can walk the table and read the statistics out of RAM. The layout of a
+//! `Symbol` entry is:
+//!
+//! - `name: *const u8` (offset 0). NUL terminated name of the task (e.g. `foo`) or the queue
+//! (e.g. `P1::READY_QUEUE` or `TIMER_QUEUE`)
+//! - `stats: *const Stats` (offset 4)
+//! - `kind: u8` (offset 8). See `Kind`
+//! - `priority: u8` (offset 9)
+//!
+//! The layout of `Stats` is:
+//!
+//! - `enqueued: u32` (offset 0). Number of elements ever enqueued (wrapping)
+//! - `dequeued: u32` (offset 4). Number of elements ever dequeued (wrapping)
+//! - `capacity: u8` (offset 8)
+//! - `peak: u8` (offset 9)
+//!
+//! The current occupancy is `enqueued - dequeued` (wrapping subtraction).
+
+use core::cell::Cell;
+
+use cortex_m::interrupt;
+
+/// Occupancy statistics of a queue
+#[repr(C)]
+pub struct Stats {
+    // NOTE `enqueued` (and `peak`) are only written by producers *while they hold the lock* of the
+    // queue and `dequeued` is only written by the (single) consumer so we don't need atomics
+    enqueued: Cell<u32>,
+    dequeued: Cell<u32>,
+    capacity: u8,
+    peak: Cell<u8>,
+}
+
+unsafe impl Sync for Stats {}
+
+impl Stats {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub const fn new(capacity: u8) -> Self {
+        Stats {
+            enqueued: Cell::new(0),
+            dequeued: Cell::new(0),
+            capacity,
+            peak: Cell::new(0),
+        }
+    }
+
+    /// Maximum number of elements the queue can hold
+    pub fn capacity(&self) -> u8 {
+        self.capacity
+    }
+
+    /// Number of elements currently in the queue
+    pub fn current(&self) -> u8 {
+        interrupt::free(|_| self.enqueued.get().wrapping_sub(self.dequeued.get()) as u8)
+    }
+
+    /// Highest number of elements the queue has held at any point
+    pub fn peak(&self) -> u8 {
+        self.peak.get()
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    #[inline(always)]
+    pub unsafe fn enqueue(&self) {
+        let enqueued = self.enqueued.get().wrapping_add(1);
+        self.enqueued.set(enqueued);
+
+        let current = enqueued.wrapping_sub(self.dequeued.get()) as u8;
+        if current > self.peak.get() {
+            self.peak.set(current);
+        }
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    #[inline(always)]
+    pub unsafe fn dequeue(&self) {
+        self.dequeued.set(self.dequeued.get().wrapping_add(1));
+    }
+}
+
+/// The kind of queue a `Symbol` refers to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Kind {
+    /// Pending instances of a software task
+    Task = 0,
+    /// Ready queue of the dispatcher of a priority level
+    ReadyQueue = 1,
+    /// The timer queue
+    TimerQueue = 2,
+}
+
+/// An entry of the symbol table
+#[repr(C)]
+pub struct Symbol {
+    name: *const u8,
+    stats: *const Stats,
+    kind: Kind,
+    priority: u8,
+}
+
+unsafe impl Sync for Symbol {}
+
+impl Symbol {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub const unsafe fn new(
+        name: &'static str,
+        stats: &'static Stats,
+        kind: Kind,
+        priority: u8,
+    ) -> Self {
+        Symbol {
+            name: name.as_ptr(),
+            stats,
+            kind,
+            priority,
+        }
+    }
+
+    /// The name of the task or queue
+    pub fn name(&self) -> &'static str {
+        unsafe {
+            let mut len = 0;
+            while *self.name.add(len) != 0 {
+                len += 1;
+            }
+
+            core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.name, len))
+        }
+    }
+
+    /// The kind of queue
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// The priority of the task or the queue consumer
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// The statistics of the queue
+    pub fn stats(&self) -> &'static Stats {
+        unsafe { &*self.stats }
+    }
+}
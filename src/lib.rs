@@ -21,27 +21,67 @@
 //!
 //! [`Instant`]: struct.Instant.html
 //! [`Duration`]: struct.Duration.html
+//!
+//! - `introspect`. This opt-in feature keeps track of the occupancy of the task queues and exposes
+//! it at runtime and to external tools. See the [`introspect`] module.
+//!
+//! [`introspect`]: introspect/index.html
+//!
+//! - `wcet`. This opt-in feature measures the execution time of every task using the cycle counter.
+//! See the [`wcet`] module.
+//!
+//! [`wcet`]: wcet/index.html
+//!
+//! - `crash-report`. This opt-in feature records the state of the application when it crashes and
+//! hands it to `init` on the next boot. See the [`crash`] module.
+//!
+//! [`crash`]: crash/index.html
+//!
+//! # Memory pools
+//!
+//! Large messages can be sent to software tasks without copying them using the fixed size blocks
+//! of a memory pool. See the [`pool`] module.
+//!
+//! [`pool`]: pool/index.html
 
 #![deny(missing_docs)]
 #![deny(warnings)]
 #![no_std]
 
-use core::{cell::Cell, u8};
+use core::{
+    cell::{Cell, UnsafeCell},
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    u8,
+};
 #[cfg(feature = "timer-queue")]
 use core::{cmp::Ordering, ops};
 
 #[cfg(not(feature = "timer-queue"))]
 use cortex_m::peripheral::SYST;
-#[cfg(armv7m)]
+#[cfg(not(any(armv7m, armv8m_main)))]
+use cortex_m::asm;
+#[cfg(any(feature = "timer-queue", not(feature = "wcet")))]
+use cortex_m::peripheral::DWT;
+#[cfg(any(armv7m, armv8m_main))]
 use cortex_m::register::basepri;
 use cortex_m::{
     interrupt::{self, Nr},
-    peripheral::{CBP, CPUID, DCB, DWT, FPB, FPU, ITM, MPU, NVIC, SCB, TPIU},
+    peripheral::{CBP, CPUID, DCB, FPB, FPU, ITM, MPU, NVIC, SCB, TPIU},
 };
 pub use cortex_m_rtfm_macros::app;
 
+#[cfg(feature = "crash-report")]
+pub mod crash;
 #[doc(hidden)]
 pub mod export;
+#[cfg(feature = "introspect")]
+pub mod introspect;
+pub mod pool;
+#[cfg(feature = "wcet")]
+pub mod wcet;
 #[doc(hidden)]
 #[cfg(feature = "timer-queue")]
 mod tq;
@@ -53,6 +93,7 @@ mod tq;
 /// - The `NVIC` field is never present.
 /// - When the `timer-queue` feature is enabled the following fields are *not* present: `DWT` and
 /// `SYST`.
+/// - When the `wcet` feature is enabled the `DWT` field is *not* present.
 #[allow(non_snake_case)]
 pub struct Peripherals<'a> {
     /// Cache and branch predictor maintenance operations (not present on Cortex-M0 variants)
@@ -61,16 +102,17 @@ pub struct Peripherals<'a> {
     /// CPUID
     pub CPUID: CPUID,
 
-    /// Debug Control Block (by value if the `timer-queue` feature is disabled)
-    #[cfg(feature = "timer-queue")]
+    /// Debug Control Block (by value if the `timer-queue` and `wcet` features are disabled)
+    #[cfg(any(feature = "timer-queue", feature = "wcet"))]
     pub DCB: &'a mut DCB,
 
-    /// Debug Control Block (borrowed if the `timer-queue` feature is enabled)
-    #[cfg(not(feature = "timer-queue"))]
+    /// Debug Control Block (borrowed if the `timer-queue` or `wcet` feature is enabled)
+    #[cfg(not(any(feature = "timer-queue", feature = "wcet")))]
     pub DCB: DCB,
 
-    /// Data Watchpoint and Trace unit (not present if the `timer-queue` feature is enabled)
-    #[cfg(not(feature = "timer-queue"))]
+    /// Data Watchpoint and Trace unit (not present if the `timer-queue` or `wcet` feature is
+    /// enabled)
+    #[cfg(not(any(feature = "timer-queue", feature = "wcet")))]
     pub DWT: DWT,
 
     /// Flash Patch and Breakpoint unit (not present on Cortex-M0 variants)
@@ -115,7 +157,7 @@ impl Instant {
 
     /// Returns an instant corresponding to "now"
     pub fn now() -> Self {
-        Instant(DWT::get_cycle_count() as i32)
+        Instant(DWT::cycle_count() as i32)
     }
 
     /// Returns the amount of time elapsed since this instant was created.
@@ -246,6 +288,374 @@ impl U32Ext for u32 {
     }
 }
 
+/// A future that completes at the given `Instant`
+///
+/// Async software tasks create these using the `delay` and `at` methods of their `timer`.
+///
+/// This data type is only available when the `timer-queue` feature is enabled
+#[cfg(feature = "timer-queue")]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Delay<R>
+where
+    R: FnMut(Instant),
+{
+    instant: Instant,
+    register: R,
+}
+
+#[cfg(feature = "timer-queue")]
+impl<R> Delay<R>
+where
+    R: FnMut(Instant),
+{
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub fn new(instant: Instant, register: R) -> Self {
+        Delay { instant, register }
+    }
+
+    /// The instant at which this future completes
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+}
+
+// NOTE the fields are never pinned
+#[cfg(feature = "timer-queue")]
+impl<R> Unpin for Delay<R> where R: FnMut(Instant) {}
+
+#[cfg(feature = "timer-queue")]
+impl<R> Future for Delay<R>
+where
+    R: FnMut(Instant),
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+
+        if this.instant <= Instant::now() {
+            Poll::Ready(())
+        } else {
+            // make sure the timer queue will wake us up
+            (this.register)(this.instant);
+            Poll::Pending
+        }
+    }
+}
+
+/// A future that completes when the future it wraps does or at the given `Instant`, whichever
+/// comes first
+///
+/// Async software tasks create these using the `timeout` method of their `timer`. The output is
+/// `None` if the timeout expired first; the wrapped future is dropped in that case.
+///
+/// This data type is only available when the `timer-queue` feature is enabled
+#[cfg(feature = "timer-queue")]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Timeout<F, R>
+where
+    R: FnMut(Instant),
+{
+    future: F,
+    delay: Delay<R>,
+}
+
+#[cfg(feature = "timer-queue")]
+impl<F, R> Timeout<F, R>
+where
+    R: FnMut(Instant),
+{
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub fn new(future: F, delay: Delay<R>) -> Self {
+        Timeout { future, delay }
+    }
+}
+
+#[cfg(feature = "timer-queue")]
+impl<F, R> Future for Timeout<F, R>
+where
+    F: Future,
+    R: FnMut(Instant),
+{
+    type Output = Option<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<F::Output>> {
+        // NOTE(unsafe) `future` is never moved out of the pinned `Timeout`
+        let this = unsafe { self.get_unchecked_mut() };
+
+        if let Poll::Ready(output) = unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+
+        match Pin::new(&mut this.delay).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A single value mailbox that an async software task can wait on
+///
+/// Any context can `signal` a value; the async task that `wait`s on the `Signal` is woken up to
+/// take it. This is how a task replies to a request issued by an async task; combine `wait` with
+/// the `timeout` method of the `timer` to also give up after some time.
+///
+/// A `Signal` is meant to be stored in a `static` and to have a single waiter at any time. A
+/// value that's signaled while nobody is waiting is kept until the next `wait` (or `take`); a
+/// second value signaled before the first one is taken replaces it.
+pub struct Signal<T> {
+    value: UnsafeCell<Option<T>>,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe impl<T> Sync for Signal<T> where T: Send {}
+
+impl<T> Signal<T> {
+    /// Creates an empty `Signal`
+    pub const fn new() -> Self {
+        Signal {
+            value: UnsafeCell::new(None),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Stores `value` and wakes up the waiting task, if any. Returns the value that had not been
+    /// taken yet, if any
+    pub fn signal(&self, value: T) -> Option<T> {
+        let (previous, waker) = interrupt::free(|_| unsafe {
+            (
+                (*self.value.get()).replace(value),
+                (*self.waker.get()).take(),
+            )
+        });
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        previous
+    }
+
+    /// Takes the signaled value, if any, without waiting
+    pub fn take(&self) -> Option<T> {
+        interrupt::free(|_| unsafe { (*self.value.get()).take() })
+    }
+
+    /// Returns a future that completes with the next signaled value
+    pub fn wait<'a>(&'a self) -> Wait<'a, T> {
+        Wait { signal: self }
+    }
+}
+
+/// A future that completes with the value of a `Signal`
+///
+/// See [`Signal.wait`](struct.Signal.html#method.wait)
+#[must_use = "futures do nothing unless awaited"]
+pub struct Wait<'a, T>
+where
+    T: 'a,
+{
+    signal: &'a Signal<T>,
+}
+
+impl<'a, T> Future for Wait<'a, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let signal = self.signal;
+
+        interrupt::free(|_| unsafe {
+            if let Some(value) = (*signal.value.get()).take() {
+                Poll::Ready(value)
+            } else {
+                // a `Wait` that's dropped leaves its waker behind; the task may be polled once
+                // more than necessary
+                *signal.waker.get() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+/// Releases of a software task that has a minimum inter-arrival time
+///
+/// `spawn` rejects, and counts as a violation, any release of the task that comes sooner than the
+/// `min_inter_arrival` time after the previous accepted release. The `app` attribute generates an
+/// `arrivals` module in the root of the crate that gives access to the `Arrivals` of each of these
+/// tasks.
+///
+/// `spawn` returns a [`ReleaseError`] that tells the two reasons to reject a release apart: only a
+/// release that comes too soon counts as a violation, and a release rejected because the task
+/// queue was full is not recorded as the last release.
+///
+/// [`ReleaseError`]: enum.ReleaseError.html
+///
+/// This data type is only available when the `timer-queue` feature is enabled
+#[cfg(feature = "timer-queue")]
+pub struct Arrivals {
+    last: Cell<Option<Instant>>,
+    min: u32,
+    violations: Cell<u32>,
+}
+
+#[cfg(feature = "timer-queue")]
+unsafe impl Sync for Arrivals {}
+
+#[cfg(feature = "timer-queue")]
+impl Arrivals {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub const fn new(min: u32) -> Self {
+        Arrivals {
+            last: Cell::new(None),
+            min,
+            violations: Cell::new(0),
+        }
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    ///
+    /// Checks, without recording it, whether `release` comes late enough after the last accepted
+    /// release; a release that comes too soon is counted as a violation
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn admit(&self, release: Instant) -> bool {
+        interrupt::free(|_| match self.last.get() {
+            Some(last) if release < last + Duration(self.min) => {
+                self.violations.set(self.violations.get().wrapping_add(1));
+                false
+            }
+            _ => true,
+        })
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    ///
+    /// Records an admitted release once the task queue has accepted it
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn record(&self, release: Instant) {
+        interrupt::free(|_| self.last.set(Some(release)))
+    }
+
+    /// The instant of the last accepted release, or `None` if the task has never been released
+    pub fn last(&self) -> Option<Instant> {
+        interrupt::free(|_| self.last.get())
+    }
+
+    /// The minimum time between two releases of the task
+    pub fn min_inter_arrival(&self) -> Duration {
+        Duration(self.min)
+    }
+
+    /// Number of releases that have been rejected because they came too soon (wrapping)
+    pub fn violations(&self) -> u32 {
+        self.violations.get()
+    }
+}
+
+/// Error returned by `spawn` when it rejects a release of a task that has a minimum inter-arrival
+/// time
+///
+/// Both variants hand back the inputs of the rejected release.
+///
+/// This data type is only available when the `timer-queue` feature is enabled
+#[cfg(feature = "timer-queue")]
+#[derive(Debug, Eq, PartialEq)]
+pub enum ReleaseError<T> {
+    /// The release came sooner than the minimum inter-arrival time after the last accepted one;
+    /// it's counted as a violation
+    TooSoon(T),
+    /// The task queue had no room for the release
+    Full(T),
+}
+
+#[cfg(feature = "timer-queue")]
+impl<T> ReleaseError<T> {
+    /// Returns the inputs of the rejected release
+    pub fn into_inner(self) -> T {
+        match self {
+            ReleaseError::TooSoon(inputs) | ReleaseError::Full(inputs) => inputs,
+        }
+    }
+}
+
+/// Releases of a time-triggered software task
+///
+/// The schedule table releases the task at the start of each of its slots. A release is *missed*
+/// if the previous one has not completed yet; the task runs at most once per slot so the missed
+/// release is dropped. A release *overruns* its slot if it completes after the end of the slot.
+/// The `app` attribute generates a `slots` module in the root of the crate that gives access to
+/// the `Slots` of each of these tasks.
+///
+/// This data type is only available when the `timer-queue` feature is enabled
+#[cfg(feature = "timer-queue")]
+pub struct Slots {
+    // end of the slot of the last release
+    end: Cell<Instant>,
+    misses: Cell<u32>,
+    overruns: Cell<u32>,
+}
+
+#[cfg(feature = "timer-queue")]
+unsafe impl Sync for Slots {}
+
+#[cfg(feature = "timer-queue")]
+impl Slots {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Slots {
+            end: Cell::new(Instant(0)),
+            misses: Cell::new(0),
+            overruns: Cell::new(0),
+        }
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    ///
+    /// Records a release that the task queue has accepted; its slot ends at `end`
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn release(&self, end: Instant) {
+        interrupt::free(|_| self.end.set(end))
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    ///
+    /// Counts a release that was dropped because the previous one hasn't completed
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn miss(&self) {
+        interrupt::free(|_| self.misses.set(self.misses.get().wrapping_add(1)))
+    }
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    ///
+    /// Checks the completion of the last release against the end of its slot
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn complete(&self) {
+        interrupt::free(|_| {
+            if Instant::now() > self.end.get() {
+                self.overruns.set(self.overruns.get().wrapping_add(1));
+            }
+        })
+    }
+
+    /// Number of releases that were dropped because the previous one hadn't completed (wrapping)
+    pub fn misses(&self) -> u32 {
+        self.misses.get()
+    }
+
+    /// Number of releases that completed after the end of their slot (wrapping)
+    pub fn overruns(&self) -> u32 {
+        self.overruns.get()
+    }
+}
+
 /// Memory safe access to shared resources
 ///
 /// In RTFM, locks are implemented as critical sections that prevent other tasks from *starting*.
@@ -253,6 +663,11 @@ impl U32Ext for u32 {
 /// [BASEPRI]) of the execution context.
 ///
 /// [BASEPRI]: https://developer.arm.com/products/architecture/cpu-architecture/m-profile/docs/100701/latest/special-purpose-mask-registers
+///
+/// ARMv6-M and ARMv8-M Baseline devices have no BASEPRI register. On these devices locks instead
+/// disable (mask) the interrupts of the tasks that could preempt the owner of the lock. If one of
+/// those tasks is bound to an exception (e.g. `SysTick`) the lock falls back to a global critical
+/// section (`interrupt::free`).
 pub unsafe trait Mutex {
     /// Logical priority ceiling
     #[doc(hidden)]
@@ -270,9 +685,20 @@ pub unsafe trait Mutex {
     #[doc(hidden)]
     fn ptr(&self) -> *mut Self::Data;
 
+    /// IMPLEMENTATION DETAIL. DO NOT USE THIS METHOD
+    ///
+    /// Disables (`enable = false`) or re-enables the interrupts of the tasks whose priority is in
+    /// the range `(current, ceiling]`. Returns `false`, without touching any interrupt, if some of
+    /// those tasks can't be masked.
+    #[doc(hidden)]
+    #[inline(always)]
+    unsafe fn sources(&self, _current: u8, _ceiling: u8, _enable: bool) -> bool {
+        false
+    }
+
     /// Creates a critical section and grants temporary access to the protected data
     #[inline(always)]
-    #[cfg(armv7m)]
+    #[cfg(any(armv7m, armv8m_main))]
     fn claim<R, F>(&mut self, f: F) -> R
     where
         F: FnOnce(&mut Self::Data) -> R,
@@ -301,7 +727,7 @@ pub unsafe trait Mutex {
     }
 
     /// Creates a critical section and grants temporary access to the protected data
-    #[cfg(not(armv7m))]
+    #[cfg(not(any(armv7m, armv8m_main)))]
     fn claim<R, F>(&mut self, f: F) -> R
     where
         F: FnOnce(&mut Self::Data) -> R,
@@ -310,10 +736,22 @@ pub unsafe trait Mutex {
             let current = self.priority().get();
 
             if self.priority().get() < Self::CEILING {
-                self.priority().set(u8::MAX);
-                let r = interrupt::free(|_| f(&mut *self.ptr()));
-                self.priority().set(current);
-                r
+                if self.sources(current, Self::CEILING, false) {
+                    // make sure the interrupts are disabled before entering the critical section
+                    asm::dsb();
+                    asm::isb();
+
+                    self.priority().set(Self::CEILING);
+                    let r = f(&mut *self.ptr());
+                    self.priority().set(current);
+                    self.sources(current, Self::CEILING, true);
+                    r
+                } else {
+                    self.priority().set(u8::MAX);
+                    let r = interrupt::free(|_| f(&mut *self.ptr()));
+                    self.priority().set(current);
+                    r
+                }
             } else {
                 f(&mut *self.ptr())
             }
@@ -321,17 +759,128 @@ pub unsafe trait Mutex {
     }
 }
 
-#[cfg(armv7m)]
+#[cfg(any(armv7m, armv8m_main))]
 #[inline]
 fn logical2hw(logical: u8, nvic_prio_bits: u8) -> u8 {
     ((1 << nvic_prio_bits) - logical) << (8 - nvic_prio_bits)
 }
 
+/// Control handle of a hardware task
+///
+/// These handles are granted to the functions that list the hardware task (`#[interrupt]`) in the
+/// `controls` argument of their attribute.
+pub struct Control {
+    nr: u8,
+    state: &'static export::ControlState,
+}
+
+impl Control {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub unsafe fn new<I>(interrupt: I, state: &'static export::ControlState) -> Self
+    where
+        I: Nr,
+    {
+        Control {
+            nr: interrupt.nr(),
+            state,
+        }
+    }
+
+    /// Enables the hardware task; if its interrupt is pending the task will be executed
+    pub fn enable(&self) {
+        unsafe { self.state.enable(self.nr) }
+    }
+
+    /// Disables the hardware task; the task won't be executed even if its interrupt is pended
+    ///
+    /// If the task is running it will run to completion.
+    pub fn disable(&self) {
+        unsafe { self.state.disable(self.nr) }
+    }
+
+    /// Returns `true` if the hardware task has not been disabled
+    pub fn is_enabled(&self) -> bool {
+        self.state.is_enabled()
+    }
+
+    /// Returns `true` if the interrupt of the hardware task is pending
+    pub fn is_pending(&self) -> bool {
+        let nr = self.nr;
+        unsafe { (*NVIC::ptr()).ispr[usize::from(nr / 32)].read() & (1 << (nr % 32)) != 0 }
+    }
+
+    /// Pends the interrupt of the hardware task
+    pub fn pend(&self) {
+        let nr = self.nr;
+        unsafe { (*NVIC::ptr()).ispr[usize::from(nr / 32)].write(1 << (nr % 32)) }
+    }
+}
+
+/// Error returned by `publish` when some of the subscribers had no room for the message
+///
+/// In that case the message is not delivered to *any* of the subscribers and is handed back
+/// through the `message` field.
+pub struct PublishError<T> {
+    /// The message that could not be published
+    pub message: T,
+    subscribers: &'static [&'static str],
+    overflowed: u32,
+}
+
+impl<T> PublishError<T> {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub fn new(message: T, subscribers: &'static [&'static str], overflowed: u32) -> Self {
+        PublishError {
+            message,
+            subscribers,
+            overflowed,
+        }
+    }
+
+    /// Returns the names of the subscribers whose queues were full
+    pub fn overflowed(&self) -> impl Iterator<Item = &'static str> {
+        let overflowed = self.overflowed;
+        self.subscribers
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| overflowed & (1 << i) != 0)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl<T> fmt::Debug for PublishError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Overflowed<'a, T>(&'a PublishError<T>)
+        where
+            T: 'a;
+
+        impl<'a, T> fmt::Debug for Overflowed<'a, T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_list().entries(self.0.overflowed()).finish()
+            }
+        }
+
+        f.debug_struct("PublishError")
+            .field("overflowed", &Overflowed(self))
+            .finish()
+    }
+}
+
 /// Sets the given `interrupt` as pending
 ///
 /// This is a convenience function around
 /// [`NVIC::pend`](../cortex-m-/peripheral/struct.NVIC.html#method.pend)
-pub fn pend<I>(interrupt: I)
+///
+/// Prefer the `pend` argument of the `app` attributes, which only grants access to the interrupts
+/// bound to `#[interrupt]` handlers.
+///
+/// # Safety
+///
+/// Pending an interrupt that dispatches software tasks makes its dispatcher run when it has no
+/// work to do; pending an interrupt that has no handler makes the default handler run.
+pub unsafe fn pend<I>(interrupt: I)
 where
     I: Nr,
 {
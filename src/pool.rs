@@ -0,0 +1,196 @@
+//! Statically allocated memory pools
+//!
+//! A memory pool is declared in the `app` using the `#[pool]` attribute on a `static` item. The
+//! `app` attribute generates a type with the same name as the `static`; this type is used to
+//! allocate blocks from the pool.
+//!
+//! ``` ignore
+//! #[app(device = ..)]
+//! const APP: () = {
+//!     // 4 blocks of 512 bytes each; every block is initialized to `[0; 512]`
+//!     #[pool(capacity = 4)]
+//!     static PACKET: [u8; 512] = [0; 512];
+//!
+//!     #[interrupt(spawn = [process])]
+//!     fn UART0() {
+//!         if let Some(packet) = PACKET::alloc() {
+//!             spawn.process(packet).ok();
+//!         }
+//!     }
+//!
+//!     #[task]
+//!     fn process(packet: Box<PACKET>) {
+//!         // `packet` returns to the pool when it goes out of scope
+//!     }
+//!
+//!     // ..
+//! };
+//! ```
+//!
+//! A [`Box`] is a pointer to a block of the pool so it can be sent to other tasks without copying
+//! the block. The block returns to the pool when the `Box` is dropped. The contents of a block are
+//! *not* reset when the block returns to the pool.
+//!
+//! [`Box`]: struct.Box.html
+//!
+//! The allocator uses short critical sections (`interrupt::free`) so blocks can be allocated and
+//! dropped from any context.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use cortex_m::interrupt;
+
+/// A memory pool declared with `#[pool]`
+///
+/// This trait is implemented by the `app` attribute; it can't be implemented by hand.
+pub unsafe trait Pool: Sized + 'static {
+    /// The type of the blocks of this pool
+    type Data: 'static;
+
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    fn stack() -> &'static Stack<Self::Data>;
+
+    /// Claims a block from the pool
+    ///
+    /// Returns `None` if all the blocks are in use. The block contains the value it had when it was
+    /// last returned to the pool.
+    fn alloc() -> Option<Box<Self>> {
+        Self::stack().pop().map(|node| Box {
+            node,
+            _pool: PhantomData,
+        })
+    }
+}
+
+/// A block of the memory pool `P`
+///
+/// The block returns to the pool when this value is dropped
+pub struct Box<P>
+where
+    P: Pool,
+{
+    node: NonNull<Node<P::Data>>,
+    _pool: PhantomData<P>,
+}
+
+unsafe impl<P> Send for Box<P>
+where
+    P: Pool,
+    P::Data: Send,
+{
+}
+
+unsafe impl<P> Sync for Box<P>
+where
+    P: Pool,
+    P::Data: Sync,
+{
+}
+
+impl<P> Deref for Box<P>
+where
+    P: Pool,
+{
+    type Target = P::Data;
+
+    fn deref(&self) -> &P::Data {
+        unsafe { &(*self.node.as_ptr()).data }
+    }
+}
+
+impl<P> DerefMut for Box<P>
+where
+    P: Pool,
+{
+    fn deref_mut(&mut self) -> &mut P::Data {
+        unsafe { &mut (*self.node.as_ptr()).data }
+    }
+}
+
+impl<P> Drop for Box<P>
+where
+    P: Pool,
+{
+    fn drop(&mut self) {
+        unsafe { P::stack().push(self.node) }
+    }
+}
+
+impl<P> fmt::Debug for Box<P>
+where
+    P: Pool,
+    P::Data: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        P::Data::fmt(self, f)
+    }
+}
+
+impl<P> fmt::Display for Box<P>
+where
+    P: Pool,
+    P::Data: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        P::Data::fmt(self, f)
+    }
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+///
+/// Intrusive stack of free blocks
+#[doc(hidden)]
+pub struct Stack<T> {
+    head: UnsafeCell<Option<NonNull<Node<T>>>>,
+}
+
+// NOTE(unsafe) the stack is only modified in critical sections
+unsafe impl<T> Sync for Stack<T> where T: Send {}
+
+impl<T> Stack<T> {
+    pub const fn new() -> Self {
+        Stack {
+            head: UnsafeCell::new(None),
+        }
+    }
+
+    // `node` must not be in the stack
+    pub unsafe fn push(&self, node: NonNull<Node<T>>) {
+        interrupt::free(|_| {
+            (*node.as_ptr()).next = *self.head.get();
+            *self.head.get() = Some(node);
+        })
+    }
+
+    fn pop(&self) -> Option<NonNull<Node<T>>> {
+        interrupt::free(|_| unsafe {
+            let head = *self.head.get();
+
+            if let Some(node) = head {
+                *self.head.get() = (*node.as_ptr()).next;
+            }
+
+            head
+        })
+    }
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+#[doc(hidden)]
+pub struct Node<T> {
+    next: Option<NonNull<Node<T>>>,
+    data: T,
+}
+
+impl<T> Node<T> {
+    pub const fn new(data: T) -> Self {
+        Node { next: None, data }
+    }
+}
@@ -1,9 +1,16 @@
-use core::cmp::{self, Ordering};
+use core::{
+    cell::Cell,
+    cmp::{self, Ordering},
+    hint, mem,
+};
 
-use cortex_m::peripheral::{SCB, SYST};
-use heapless::{binary_heap::Min, ArrayLength, BinaryHeap};
+use cortex_m::{
+    interrupt,
+    peripheral::{SCB, SYST},
+};
+use heapless::{binary_heap::Min, ArrayLength, BinaryHeap, Vec};
 
-use crate::{Instant, Mutex};
+use crate::{Duration, Instant, Mutex};
 
 pub struct TimerQueue<T, N>
 where
@@ -48,6 +55,35 @@ where
 
         self.queue.push_unchecked(nr);
     }
+
+    /// Moves the entry picked by `is_entry`, if it's still in the queue, to the earlier `instant`
+    pub fn advance<F>(&mut self, instant: Instant, is_entry: F)
+    where
+        F: Fn(T) -> bool,
+    {
+        // `BinaryHeap` can't change the instant of one of its entries in place so the whole queue
+        // is rebuilt; this queue holds a handful of entries
+        let mut entries = Vec::<NotReady<T>, N>::new();
+        while let Some(mut nr) = self.queue.pop() {
+            if is_entry(nr.task) {
+                nr.instant = instant;
+            }
+
+            // NOTE(unsafe) `entries` has the same capacity as the queue
+            unsafe {
+                entries
+                    .push(nr)
+                    .unwrap_or_else(|_| hint::unreachable_unchecked())
+            }
+        }
+
+        while let Some(nr) = entries.pop() {
+            unsafe { self.queue.push_unchecked(nr) }
+        }
+
+        // the head of the queue may have changed; the SysTick handler sets the next timeout
+        unsafe { (*SCB::ptr()).icsr.write(1 << 26) }
+    }
 }
 
 pub struct NotReady<T>
@@ -133,3 +169,275 @@ where
         }
     }
 }
+
+/// Ready queue of the priority levels dispatched earliest deadline first; pending instances that
+/// have the same deadline are dispatched in the order they were released
+pub struct DeadlineQueue<T, N>
+where
+    N: ArrayLength<Deadline<T>>,
+{
+    heap: BinaryHeap<Deadline<T>, N, Min>,
+    // release order of the next instance
+    sequence: u32,
+}
+
+impl<T, N> DeadlineQueue<T, N>
+where
+    N: ArrayLength<Deadline<T>>,
+{
+    pub fn new() -> Self {
+        DeadlineQueue {
+            heap: BinaryHeap::new(),
+            sequence: 0,
+        }
+    }
+
+    /// Releases instance `index` of `task`, which runs at priority `level`
+    #[inline(always)]
+    pub unsafe fn push_unchecked(&mut self, deadline: Instant, level: u8, index: u8, task: T) {
+        let sequence = self.sequence;
+        self.sequence = sequence.wrapping_add(1);
+
+        self.heap.push_unchecked(Deadline {
+            deadline,
+            sequence,
+            level,
+            index,
+            task,
+        })
+    }
+
+    /// Returns the pending instance with the earliest deadline
+    #[inline(always)]
+    pub fn peek(&self) -> Option<&Deadline<T>> {
+        self.heap.peek()
+    }
+
+    /// Removes the pending instance with the earliest deadline
+    #[inline(always)]
+    pub fn pop(&mut self) -> Option<Deadline<T>> {
+        self.heap.pop()
+    }
+}
+
+pub struct Deadline<T> {
+    pub deadline: Instant,
+    // the queue holds at most 255 instances so comparing sequence numbers modulo 2^32 is exact
+    sequence: u32,
+    pub level: u8,
+    pub index: u8,
+    pub task: T,
+}
+
+impl<T> Eq for Deadline<T> {}
+
+impl<T> Ord for Deadline<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline
+            .cmp(&other.deadline)
+            .then_with(|| (self.sequence.wrapping_sub(other.sequence) as i32).cmp(&0))
+    }
+}
+
+impl<T> PartialEq for Deadline<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> PartialOrd for Deadline<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(&other))
+    }
+}
+
+/// Earliest deadline first scheduler of the priority levels that are dispatched by deadline
+///
+/// Priorities are used as SRP preemption levels: a pending instance starts only if it has the
+/// earliest deadline of all the pending instances, an earlier deadline than the running instance
+/// and (this is up to the NVIC) a priority higher than the system ceiling. All the methods must be
+/// called from within a global critical section
+pub struct Edf<T, N>
+where
+    N: ArrayLength<Deadline<T>>,
+{
+    queue: DeadlineQueue<T, N>,
+    // deadline of the instance that's running, if any
+    running: Option<Instant>,
+}
+
+impl<T, N> Edf<T, N>
+where
+    N: ArrayLength<Deadline<T>>,
+{
+    pub fn new() -> Self {
+        Edf {
+            queue: DeadlineQueue::new(),
+            running: None,
+        }
+    }
+
+    /// Releases instance `index` of `task`; returns the priority level that must be pended, if any
+    #[inline(always)]
+    pub unsafe fn release(
+        &mut self,
+        deadline: Instant,
+        level: u8,
+        index: u8,
+        task: T,
+    ) -> Option<u8> {
+        self.queue.push_unchecked(deadline, level, index, task);
+
+        self.next()
+    }
+
+    /// Starts the pending instance with the earliest deadline if it runs at priority `level` and
+    /// its deadline is earlier than the one of the running instance
+    ///
+    /// Returns the instance and the deadline that must be passed to `complete` once it finishes
+    #[inline(always)]
+    pub fn start(&mut self, level: u8) -> Option<(Deadline<T>, Option<Instant>)> {
+        if self.next() != Some(level) {
+            return None;
+        }
+
+        let ready = self.queue.pop()?;
+        let previous = mem::replace(&mut self.running, Some(ready.deadline));
+        Some((ready, previous))
+    }
+
+    /// Ends the running instance; returns the priority level that must be pended, if any
+    #[inline(always)]
+    pub fn complete(&mut self, previous: Option<Instant>) -> Option<u8> {
+        self.running = previous;
+
+        self.next()
+    }
+
+    // priority level of the pending instance with the earliest deadline if it can preempt the
+    // running instance
+    fn next(&self) -> Option<u8> {
+        let top = self.queue.peek()?;
+
+        match self.running {
+            Some(running) if top.deadline >= running => None,
+            _ => Some(top.level),
+        }
+    }
+}
+
+/// Execution budget of a task; the timer queue checks it while an instance of the task runs
+pub struct Budget {
+    // whether the timer queue holds the entry of this task
+    armed: bool,
+    // end of the budget of the running instance
+    expiry: Option<Instant>,
+}
+
+pub enum BudgetCheck {
+    // no instance is running
+    Idle,
+    // the running instance has exceeded its budget
+    Overrun,
+    // a newer instance is running; its budget ends at the given instant
+    Rearm(Instant),
+}
+
+impl Budget {
+    pub const fn new() -> Self {
+        Budget {
+            armed: false,
+            expiry: None,
+        }
+    }
+
+    /// Starts the budget of a new instance; returns `true` if the timer queue doesn't hold the
+    /// entry of this task and one must be enqueued
+    pub fn start(&mut self, expiry: Instant) -> bool {
+        self.expiry = Some(expiry);
+
+        !mem::replace(&mut self.armed, true)
+    }
+
+    /// The running instance has completed
+    pub fn stop(&mut self) {
+        self.expiry = None;
+    }
+
+    /// Checks the budget when the entry of this task is dequeued from the timer queue
+    pub fn check(&mut self) -> BudgetCheck {
+        match self.expiry {
+            Some(expiry) if expiry.0.wrapping_sub(Instant::now().0) > 0 => {
+                BudgetCheck::Rearm(expiry)
+            }
+            Some(_) => {
+                // report the overrun only once per instance
+                self.expiry = None;
+                self.armed = false;
+
+                BudgetCheck::Overrun
+            }
+            None => {
+                self.armed = false;
+
+                BudgetCheck::Idle
+            }
+        }
+    }
+}
+
+/// Heartbeat of a task: the completion of its instances
+pub struct Heartbeat {
+    // completion of the last instance; the start of the application (`Instant(0)`) at first
+    last: Cell<Instant>,
+    period: u32,
+    // whether the task has missed its heartbeat since `last`
+    stalled: Cell<bool>,
+}
+
+unsafe impl Sync for Heartbeat {}
+
+impl Heartbeat {
+    pub const fn new(period: u32) -> Self {
+        Heartbeat {
+            last: Cell::new(Instant(0)),
+            period,
+            stalled: Cell::new(false),
+        }
+    }
+
+    /// An instance of the task has completed
+    pub fn beat(&self) {
+        interrupt::free(|_| {
+            self.last.set(Instant::now());
+            self.stalled.set(false);
+        })
+    }
+
+    /// Checks the heartbeat when the entry of this task is dequeued from the timer queue; returns
+    /// `true` if the task has just stalled, and the next time the heartbeat must be checked
+    pub fn check(&self) -> (bool, Instant) {
+        interrupt::free(|_| {
+            let now = Instant::now();
+            let deadline = self.last.get() + Duration(self.period);
+
+            if self.stalled.get() {
+                (false, now + Duration(self.period))
+            } else if now < deadline {
+                (false, deadline)
+            } else {
+                // report the stall only once; keep checking until the task recovers
+                self.stalled.set(true);
+
+                (true, now + Duration(self.period))
+            }
+        })
+    }
+
+    /// Whether the last instance of the task completed less than one period ago
+    pub fn is_alive(&self) -> bool {
+        interrupt::free(|_| {
+            !self.stalled.get() && Instant::now() < self.last.get() + Duration(self.period)
+        })
+    }
+}
@@ -0,0 +1,122 @@
+//! Per-task execution time measurements
+//!
+//! This module is only available when the `wcet` feature is enabled. In this mode the runtime uses
+//! the cycle counter (`DWT.CYCCNT`) to measure every execution of every task: software tasks,
+//! hardware tasks and the timer queue handler (`SysTick`). The time spent in tasks that preempted
+//! the task being measured is subtracted from its measurement.
+//!
+//! The `app` attribute will generate a `wcet` module in the root of the crate that gives access to
+//! the execution `Times` of each task.
+//!
+//! **NOTE**: The cycle counter is not available on ARMv6-M (Cortex-M0 / Cortex-M0+) devices.
+
+use core::{cell::Cell, u32};
+
+use cortex_m::{interrupt, peripheral::DWT};
+
+// Sum of the execution times of all the task instances that have completed; it's used to compute
+// how much time a task has spent preempted. NOTE(static mut) only accessed in critical sections
+static mut BUSY: u32 = 0;
+
+/// Execution time statistics of a task
+pub struct Times {
+    count: Cell<u32>,
+    max: Cell<u32>,
+    min: Cell<u32>,
+    total: Cell<u64>,
+}
+
+unsafe impl Sync for Times {}
+
+impl Times {
+    /// IMPLEMENTATION DETAIL. DO NOT USE
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Times {
+            count: Cell::new(0),
+            max: Cell::new(0),
+            min: Cell::new(u32::MAX),
+            total: Cell::new(0),
+        }
+    }
+
+    /// Number of completed executions
+    pub fn count(&self) -> u32 {
+        self.count.get()
+    }
+
+    /// Longest observed execution time, in clock cycles
+    ///
+    /// Returns `None` if the task has not yet run to completion
+    pub fn max(&self) -> Option<u32> {
+        interrupt::free(|_| {
+            if self.count.get() == 0 {
+                None
+            } else {
+                Some(self.max.get())
+            }
+        })
+    }
+
+    /// Shortest observed execution time, in clock cycles
+    ///
+    /// Returns `None` if the task has not yet run to completion
+    pub fn min(&self) -> Option<u32> {
+        interrupt::free(|_| {
+            if self.count.get() == 0 {
+                None
+            } else {
+                Some(self.min.get())
+            }
+        })
+    }
+
+    /// Sum of all the execution times, in clock cycles
+    pub fn total(&self) -> u64 {
+        interrupt::free(|_| self.total.get())
+    }
+
+    fn record(&self, cycles: u32) {
+        self.count.set(self.count.get().wrapping_add(1));
+        self.total.set(self.total.get().wrapping_add(u64::from(cycles)));
+
+        if cycles > self.max.get() {
+            self.max.set(cycles);
+        }
+
+        if cycles < self.min.get() {
+            self.min.set(cycles);
+        }
+    }
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+#[doc(hidden)]
+pub struct Measurement {
+    busy: u32,
+    start: u32,
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+#[doc(hidden)]
+#[inline(always)]
+pub fn start() -> Measurement {
+    interrupt::free(|_| Measurement {
+        busy: unsafe { BUSY },
+        start: DWT::cycle_count(),
+    })
+}
+
+/// IMPLEMENTATION DETAIL. DO NOT USE
+#[doc(hidden)]
+#[inline(always)]
+pub fn stop(measurement: Measurement, times: &Times) {
+    interrupt::free(|_| unsafe {
+        let elapsed = DWT::cycle_count().wrapping_sub(measurement.start);
+        let preempted = BUSY.wrapping_sub(measurement.busy);
+        let cycles = elapsed.wrapping_sub(preempted);
+
+        BUSY = BUSY.wrapping_add(cycles);
+        times.record(cycles);
+    })
+}
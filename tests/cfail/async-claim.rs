@@ -0,0 +1,33 @@
+// edition:2018
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+
+    #[init(spawn = [foo, bar])]
+    fn init() {}
+
+    // an async task can be suspended while it holds a reference so it must `claim` the resources
+    // it shares, even with tasks that run at the same priority
+    #[task(resources = [SHARED])]
+    async fn foo() {
+        *resources.SHARED += 1; //~ ERROR cannot be dereferenced
+    }
+
+    #[task(resources = [SHARED])]
+    fn bar() {
+        *resources.SHARED += 1;
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo])]
+    fn init() {}
+
+    // only async tasks get a `timer`
+    #[task]
+    fn foo() {
+        let _ = timer; //~ ERROR cannot find value `timer` in this scope
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
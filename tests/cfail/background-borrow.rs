@@ -0,0 +1,31 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut X: u32 = 0;
+
+    #[init(spawn = [foo])]
+    fn init() {}
+
+    #[idle(resources = [X])]
+    fn idle() -> ! {
+        let x = &mut *resources.X;
+
+        // `foo` could observe `X` while `idle` holds a reference to it
+        resources.run_background(); //~ ERROR cannot borrow `resources` as mutable
+
+        *x += 1;
+
+        loop {}
+    }
+
+    #[task(priority = 0, resources = [X])]
+    fn foo() {}
+};
@@ -0,0 +1,20 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[interrupt]
+    fn UART0() {} //~ ERROR `UART0` is bound to more than one handler
+
+    #[interrupt(binds = UART0)] //~ ERROR `UART0` is bound to more than one handler
+    fn foo() {}
+};
@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(controls = [foo])] //~ ERROR only `#[interrupt]` handlers can be listed in `controls`
+    fn init() {}
+
+    #[task]
+    fn foo() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,34 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut X: u32 = 0;
+
+    #[init(spawn = [foo])]
+    fn init() {}
+
+    #[task(resources = [X])]
+    fn foo() {
+        *resources.X += 1;
+    }
+
+    // never spawned but still type checked
+    #[allow(dead_code)]
+    #[task(resources = [X], spawn = [foo])]
+    fn bar() {
+        let _: bool = *resources.X; //~ ERROR mismatched types
+
+        spawn.foo().unwrap();
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,25 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[task]
+    fn foo() {}
+
+    extern "C" {
+        #[priority = 1] //~ ERROR interrupt `UART1` already dispatches the tasks at this priority level
+        fn UART0();
+
+        #[priority = 1] //~ ERROR interrupt `UART0` already dispatches the tasks at this priority level
+        fn UART1();
+    }
+};
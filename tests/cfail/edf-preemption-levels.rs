@@ -0,0 +1,28 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [a, b, c])]
+    fn init() {}
+
+    #[task(deadline = 1_000)]
+    fn a() {}
+
+    #[task(priority = 2, deadline = 2_000)] //~ ERROR priorities must not increase
+    fn b() {}
+
+    #[task]
+    fn c() {} //~ ERROR this task must specify a `deadline`
+
+    extern "C" {
+        fn UART0();
+        fn UART1();
+    }
+};
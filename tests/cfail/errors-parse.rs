@@ -0,0 +1,32 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+// all the errors found while parsing the specification are reported at once
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut X: u32 = 0; //~ ERROR the resource is first declared here
+
+    static mut X: u32 = 1; //~ ERROR this resource is listed twice
+
+    #[init]
+    fn init() {}
+
+    #[interrupt]
+    fn UART0() {} //~ ERROR the task is first defined here
+
+    #[task]
+    fn UART0() {} //~ ERROR this task is defined multiple times
+
+    #[task(priority = 1, capacity = 0)] //~ ERROR this literal must be in the range 1...255
+    fn foo() {}
+
+    extern "C" {
+        fn UART1();
+    }
+};
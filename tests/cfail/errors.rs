@@ -0,0 +1,32 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+// all the errors in the specification are reported at once
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut SHARED: u32 = 0; //~ ERROR `SHARED` is declared here
+
+    #[init(resources = [SHRED])] //~ ERROR this resource has NOT been declared; did you mean `SHARED`?
+    fn init() {}
+
+    #[idle(spawn = [fooo])] //~ ERROR this task has NOT been declared; did you mean `foo`?
+    fn idle() -> ! {
+        loop {}
+    }
+
+    #[interrupt(resources = [X])] //~ ERROR this resource has NOT been declared
+    fn UART0() {}
+
+    #[task(resources = [SHARED])]
+    fn foo() {} //~ ERROR `foo` is declared here
+
+    extern "C" {
+        fn UART1();
+    }
+};
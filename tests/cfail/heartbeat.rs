@@ -0,0 +1,22 @@
+// edition:2018
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo])]
+    fn init() {}
+
+    #[task(heartbeat = 1_000)] //~ ERROR async tasks can't specify a `heartbeat`
+    async fn foo() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
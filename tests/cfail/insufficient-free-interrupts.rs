@@ -9,7 +9,7 @@ use rtfm::app;
 
 #[app(device = lm3s6965)] //~ ERROR 1 free interrupt (`extern { .. }`) is required
 const APP: () = {
-    #[init]
+    #[init(spawn = [foo])]
     fn init() {}
 
     #[task]
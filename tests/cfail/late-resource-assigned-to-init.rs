@@ -9,7 +9,8 @@ use rtfm::app;
 
 #[app(device = lm3s6965)]
 const APP: () = {
-    static mut X: u32 = ();
+    static mut X: u32 = (); //~ ERROR `X` is declared here as a late resource
+    //~^ ERROR late resources MUST be initialized at the end of `init`
 
     #[init(resources = [X])] //~ ERROR late resources can NOT be assigned to `init`
     fn init() {}
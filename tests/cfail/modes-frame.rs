@@ -0,0 +1,14 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, modes = [Idle, Active])] //~ ERROR operating modes need a schedule table
+const APP: () = {
+    #[init]
+    fn init() {}
+};
@@ -0,0 +1,33 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, frame = 1_000, modes = [Idle, Active])] //~ ERROR `Active` is declared here
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[task(slots = [0..500], modes = [Idle, Actve])] //~ ERROR did you mean `Active`?
+    fn a() {}
+
+    #[task(slots = [100..200])] //~ ERROR overlaps the slot `0..500` of task `a`
+    fn b() {}
+
+    #[task(slots = [600..700], modes = [Idle])]
+    fn c() {}
+
+    #[task(slots = [650..750], modes = [Active])]
+    fn d() {}
+
+    #[task(modes = [Idle])]
+    fn e() {} //~ ERROR only time-triggered tasks (tasks with `slots`) can specify `modes`
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[task(order = 0)] //~ ERROR task `bar` has the same `order` and priority as this one
+    fn foo() {}
+
+    #[task(order = 0)] //~ ERROR task `foo` has the same `order` and priority as this one
+    fn bar() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
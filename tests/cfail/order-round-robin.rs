@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, round_robin = [1])]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[task(order = 0)] //~ ERROR `order` can't be used on tasks dispatched in round-robin fashion
+    fn foo() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [a])]
+    fn init() {}
+
+    #[task(wcet = 1_000)] //~ ERROR tasks with a `wcet` budget need an `#[overrun]` handler
+    fn a() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
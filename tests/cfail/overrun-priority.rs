@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+// the lm3s6965 supports priorities up to 8 (`NVIC_PRIO_BITS = 3`)
+#[app(device = lm3s6965)] //~ ERROR the overrun monitor runs at priority 9
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[interrupt(priority = 8, wcet = 1_000)]
+    fn UART0() {}
+
+    #[overrun]
+    fn overrun() {}
+};
@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use lm3s6965::Interrupt;
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo])]
+    fn init() {
+        rtfm::pend(Interrupt::UART0); //~ ERROR requires unsafe function or block
+    }
+
+    #[task]
+    fn foo() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
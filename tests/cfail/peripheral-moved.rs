@@ -0,0 +1,51 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+// `lm3s6965` has no peripherals; this wrapper adds one
+pub mod device {
+    use core::marker::PhantomData;
+
+    pub use lm3s6965::{interrupt, Interrupt, NVIC_PRIO_BITS};
+
+    #[allow(non_snake_case)]
+    pub struct Peripherals {
+        pub UART0: UART0,
+    }
+
+    impl Peripherals {
+        pub unsafe fn steal() -> Self {
+            Peripherals {
+                UART0: UART0 {
+                    _marker: PhantomData,
+                },
+            }
+        }
+    }
+
+    pub struct UART0 {
+        _marker: PhantomData<*const ()>,
+    }
+
+    unsafe impl Send for UART0 {}
+}
+
+#[app(device = device)]
+const APP: () = {
+    #[init(spawn = [foo])]
+    fn init() {
+        let _uart0 = device.UART0; //~ ERROR use of moved value
+    }
+
+    #[task(resources = [device::UART0])]
+    fn foo() {}
+
+    extern "C" {
+        fn UART1();
+    }
+};
@@ -0,0 +1,34 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::{app, pool::Box};
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    // 3 instances of `foo` (2 queued and 1 running) hold 2 blocks each, 2 instances of `bar` hold
+    // 1 block each and `R` holds 1 block
+    #[pool(capacity = 8)] //~ ERROR the capacity of this pool must be at least 9
+    static P: [u8; 16] = [0; 16];
+
+    static mut R: Option<Box<P>> = None;
+
+    #[topic]
+    type T = Box<P>;
+
+    #[init(spawn = [foo], publish = [T])]
+    fn init() {}
+
+    #[task(capacity = 2, resources = [R])]
+    fn foo(_x: [Box<P>; 2]) {}
+
+    #[task(subscribe = [T], resources = [R])]
+    fn bar(_x: T) {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::{app, pool::Box};
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[pool]
+    static P: [u8; 16] = [0; 16];
+
+    #[init(spawn = [foo])]
+    fn init() {}
+
+    #[task]
+    fn foo(_x: heapless::Vec<Box<P>, heapless::consts::U8>) {} //~ ERROR can't be counted
+
+    extern "C" {
+        fn UART0();
+    }
+};
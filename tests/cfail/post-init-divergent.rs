@@ -0,0 +1,20 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[post_init]
+    fn post_init() -> ! {
+        //~^ ERROR `post_init` must have type signature `[unsafe] fn()`
+        loop {}
+    }
+};
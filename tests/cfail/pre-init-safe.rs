@@ -0,0 +1,19 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[pre_init]
+    fn pre_init() {
+        //~^ ERROR `pre_init` must have type signature `unsafe fn()`
+    }
+
+    #[init]
+    fn init() {}
+};
@@ -0,0 +1,19 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[pre_init]
+    unsafe fn pre_init() {
+        static mut X: u32 = 0; //~ ERROR `pre_init` can't have `static mut` variables
+    }
+
+    #[init]
+    fn init() {}
+};
@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [idle, post_init])]
+    fn init() {}
+
+    #[task]
+    fn idle() {} //~ ERROR `idle` is reserved for the `#[idle]` context
+
+    #[task]
+    fn post_init() {} //~ ERROR `post_init` is reserved for the `#[post_init]` context
+
+    extern "C" {
+        fn UART0();
+    }
+};
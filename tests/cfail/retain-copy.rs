@@ -0,0 +1,23 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+pub struct NotCopy {
+    _0: u32,
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[retain]
+    static mut X: NotCopy = NotCopy { _0: 0 }; //~ ERROR the trait bound `NotCopy
+
+    #[init(resources = [X])]
+    fn init() {
+        resources.X._0 += 1;
+    }
+};
@@ -0,0 +1,27 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[retain(0)] //~ ERROR `#[retain]` doesn't take any arguments
+    static mut A: u32 = 0;
+
+    #[retain]
+    static B: u32 = 0; //~ ERROR retained resources must be `static mut` variables
+
+    #[retain]
+    static mut C: u32 = (); //~ ERROR retained resources need an initial value
+
+    #[retain]
+    #[Singleton]
+    static mut D: u32 = 0; //~ ERROR singletons can NOT be retained
+
+    #[init]
+    fn init() {}
+};
@@ -0,0 +1,30 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[topic]
+    type Imu = u32;
+
+    #[init(spawn = [bar, baz], schedule = [bar], publish = [Imu])]
+    fn init() {}
+
+    #[task(min_inter_arrival = 1_000)] //~ ERROR this task is never spawned
+    fn foo() {}
+
+    #[task(min_inter_arrival = 1_000)] //~ ERROR can't be scheduled
+    fn bar() {}
+
+    #[task(min_inter_arrival = 1_000, subscribe = [Imu])] //~ ERROR can't subscribe to topics
+    fn baz(_x: u32) {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,21 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[task(slots = [0..500])]
+    fn a() {} //~ ERROR tasks with `slots` need a major frame
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,27 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, frame = 1_000)]
+const APP: () = {
+    #[init(spawn = [b])] //~ ERROR it can't be spawned or scheduled
+    fn init() {}
+
+    #[task(slots = [0..500, 900..1_100])] //~ ERROR doesn't fit in the major frame
+    fn a() {}
+
+    #[task(slots = [400..600])] //~ ERROR overlaps the slot `0..500` of task `a`
+    fn b() {}
+
+    #[task(slots = [600..700])]
+    fn c(_x: u32) {} //~ ERROR time-triggered tasks can't take inputs
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -14,7 +14,7 @@ const APP: () = {
 
     #[task]
     fn foo() -> ! {
-        //~^ ERROR `task` handlers must have type signature `[unsafe] fn(..)`
+        //~^ ERROR `task` handlers must have type signature `[async] [unsafe] fn(..)`
         loop {}
     }
 
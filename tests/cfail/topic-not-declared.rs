@@ -0,0 +1,24 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[topic]
+    type Imu = u32;
+
+    #[init(publish = [Imu, Gps])] //~ ERROR this topic has NOT been declared
+    fn init() {}
+
+    #[task(subscribe = [Imu])]
+    fn foo(_x: u32) {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
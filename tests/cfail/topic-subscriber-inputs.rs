@@ -0,0 +1,27 @@
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[topic]
+    type Imu = u32;
+
+    #[init(publish = [Imu])]
+    fn init() {}
+
+    #[task(subscribe = [Imu])]
+    fn foo(_x: u32, _y: u32) {} //~ ERROR must take exactly one input
+
+    #[task(subscribe = [Imu])]
+    fn bar() {} //~ ERROR must take exactly one input
+
+    extern "C" {
+        fn UART0();
+    }
+};
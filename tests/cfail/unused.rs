@@ -0,0 +1,42 @@
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut X: u32 = 0; //~ ERROR resource `X` is never used
+
+    // only used by a task that never runs
+    static mut Y: u32 = 0; //~ ERROR resource `Y` is never used
+
+    #[topic]
+    type Imu = u32; //~ ERROR topic `Imu` is never published to
+
+    #[topic]
+    type Gps = u32; //~ ERROR topic `Gps` has no subscribers
+
+    #[init(publish = [Gps], spawn = [foo])]
+    fn init() {}
+
+    #[task]
+    fn foo() {}
+
+    #[task(resources = [Y], spawn = [baz])]
+    fn bar() {} //~ ERROR task `bar` is never spawned or scheduled
+
+    #[task(priority = 2)]
+    fn baz() {} //~ ERROR task `baz` is never spawned or scheduled
+
+    extern "C" {
+        #[priority = 1]
+        fn UART0();
+
+        fn UART1(); //~ ERROR free interrupt `UART1` doesn't dispatch any task
+    }
+};
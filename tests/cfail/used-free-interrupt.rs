@@ -16,6 +16,6 @@ const APP: () = {
     fn UART0() {} //~ ERROR free interrupts (`extern { .. }`) can't be used as interrupt handlers
 
     extern "C" {
-        fn UART0();
+        fn UART0(); //~ ERROR `UART0` is declared here as a free interrupt
     }
 };
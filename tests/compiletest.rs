@@ -38,8 +38,19 @@ fn cfail() {
         let f = f.unwrap().path();
         let name = f.file_stem().unwrap().to_str().unwrap();
 
+        // honor the `// edition:` header, like compiletest does for the compile-fail tests
+        let mut rustc = Command::new("rustc");
+        if let Some(edition) = fs::read_to_string(&f)
+            .unwrap()
+            .lines()
+            .next()
+            .and_then(|line| line.splitn(2, "// edition:").nth(1))
+        {
+            rustc.arg("--edition").arg(edition.trim());
+        }
+
         assert!(
-            Command::new("rustc")
+            rustc
                 .args(s.split_whitespace())
                 .arg(f.display().to_string())
                 .arg("-o")
@@ -0,0 +1,60 @@
+// edition:2018
+//! Check code generation of async software tasks
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::{app, Signal};
+
+static REPLY: Signal<u32> = Signal::new();
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+
+    #[init(spawn = [foo, baz])]
+    fn init() {
+        spawn.foo().unwrap();
+        spawn.baz(0).unwrap();
+    }
+
+    #[task(resources = [SHARED], spawn = [bar])]
+    async fn foo() {
+        spawn.bar().unwrap();
+
+        // reply or timeout
+        let _: Option<u32> = timer.timeout(1_000.cycles(), REPLY.wait()).await;
+
+        // reply
+        let _: u32 = REPLY.wait().await;
+
+        // a dropped `Delay` followed by an earlier one
+        drop(timer.delay(1_000.cycles()));
+        timer.delay(100.cycles()).await;
+
+        timer.at(scheduled + 2_000.cycles()).await;
+
+        resources.SHARED.claim(|shared| *shared += 1);
+    }
+
+    #[task(priority = 2, resources = [SHARED])]
+    fn bar() {
+        *resources.SHARED += 1;
+
+        let _: Option<u32> = REPLY.signal(0);
+        let _: Option<u32> = REPLY.take();
+    }
+
+    #[task(priority = 0)]
+    async fn baz(x: u32) {
+        let _: u32 = x;
+    }
+
+    extern "C" {
+        fn UART0();
+        fn UART1();
+    }
+};
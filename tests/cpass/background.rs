@@ -0,0 +1,49 @@
+//! Check code generation of background tasks (`priority = 0`) when there's no `idle`
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut OWNED: u32 = 0;
+    static mut SHARED: u32 = 0;
+
+    #[init(spawn = [foo])]
+    fn init() {
+        spawn.foo(0).unwrap();
+    }
+
+    #[interrupt(resources = [SHARED], spawn = [foo])]
+    fn UART0() {
+        *resources.SHARED += 1;
+
+        spawn.foo(1).unwrap();
+    }
+
+    #[task(priority = 0, capacity = 2, resources = [OWNED, SHARED], spawn = [bar, baz])]
+    fn foo(x: u32) {
+        *resources.OWNED += x;
+        resources.SHARED.claim(|shared| *shared += x);
+
+        spawn.bar().unwrap();
+        spawn.baz().unwrap();
+    }
+
+    #[task(priority = 0, resources = [OWNED])]
+    fn bar() {
+        *resources.OWNED += 1;
+    }
+
+    // the other levels are still dispatched by the free interrupts
+    #[task(priority = 1)]
+    fn baz() {}
+
+    extern "C" {
+        fn UART1();
+    }
+};
@@ -0,0 +1,25 @@
+//! Hardware tasks that are not named after the exception / interrupt they are bound to
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init]
+    fn init() {}
+
+    #[exception(binds = SVCall)]
+    fn foo() {}
+
+    #[interrupt(binds = UART0)]
+    fn bar() {}
+
+    // the task name doesn't collide with the vector of another task
+    #[interrupt(binds = UART1)]
+    fn SSI0() {}
+};
@@ -0,0 +1,42 @@
+//! Check code generation of `Control` handles
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut X: u32 = 0;
+
+    #[init(controls = [UART0, foo])]
+    fn init() {
+        let _: rtfm::Control = controls.UART0;
+
+        controls.foo.disable();
+    }
+
+    #[idle(resources = [X], controls = [UART0])]
+    fn idle() -> ! {
+        // the claim must not re-enable `UART0` if it gets disabled in the meantime
+        resources.X.claim(|_| controls.UART0.disable());
+
+        loop {}
+    }
+
+    #[interrupt(resources = [X], controls = [UART0, foo])]
+    fn UART0() {
+        if !controls.foo.is_enabled() {
+            controls.foo.enable();
+        }
+    }
+
+    #[interrupt(binds = UART1, enabled = false)]
+    fn foo() {}
+
+    #[interrupt(enabled = false)]
+    fn SSI0() {}
+};
@@ -0,0 +1,34 @@
+//! Free interrupts bound to a priority level
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo, bar, baz])]
+    fn init() {}
+
+    #[task]
+    fn foo() {}
+
+    #[task(priority = 2)]
+    fn bar() {}
+
+    #[task(priority = 3)]
+    fn baz() {}
+
+    extern "C" {
+        fn UART0();
+
+        #[priority = 2]
+        fn UART1();
+
+        #[priority = 1]
+        fn SSI0();
+    }
+};
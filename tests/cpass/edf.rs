@@ -0,0 +1,48 @@
+//! Check code generation of priority levels dispatched earliest deadline first
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[topic]
+    type Event = u32;
+
+    #[init(spawn = [a, d], schedule = [b], publish = [Event])]
+    fn init() {
+        let _ = spawn.a(0);
+        let _ = spawn.d();
+        let _ = schedule.b(start + 1_000.cycles(), 0);
+        let _ = publish.event(1);
+    }
+
+    #[interrupt(spawn = [a, c])]
+    fn UART0() {
+        let _ = spawn.a(1);
+        let _ = spawn.c();
+    }
+
+    #[task(deadline = 1_000, capacity = 2, schedule = [b])]
+    fn a(_x: u32) {
+        let _ = schedule.b(scheduled + 500.cycles(), 2);
+    }
+
+    #[task(deadline = 2_000, subscribe = [Event])]
+    fn b(_x: u32) {}
+
+    // the background level can be dispatched earliest deadline first too
+    #[task(priority = 0, deadline = 50_000)]
+    fn c() {}
+
+    #[task(priority = 0, deadline = 10_000)]
+    fn d() {}
+
+    extern "C" {
+        fn UART1();
+    }
+};
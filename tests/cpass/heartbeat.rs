@@ -0,0 +1,54 @@
+//! Check the heartbeat monitor, alone and together with the execution budgets
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [a, b])]
+    fn init() {
+        spawn.a().ok();
+        spawn.b(0).ok();
+    }
+
+    #[idle]
+    fn idle() -> ! {
+        loop {
+            let _: bool = heartbeats::healthy();
+        }
+    }
+
+    #[task(heartbeat = 1_000, wcet = 100)]
+    fn a() {}
+
+    #[task(priority = 0, heartbeat = 2_147_483_647)]
+    fn b(_x: u32) {}
+
+    #[overrun]
+    fn overrun() {
+        match task {
+            Task::a => {}
+        }
+    }
+
+    #[stalled]
+    fn stalled() {
+        static mut COUNT: u32 = 0;
+
+        *COUNT += 1;
+
+        match task {
+            Heartbeat::a => {}
+            Heartbeat::b => {}
+        }
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
@@ -0,0 +1,55 @@
+//! Check code generation of operating modes
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, frame = 1_000_000, modes = [Off, Fast, Slow])]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+
+    #[init]
+    fn init() {
+        // start in `Fast` rather than `Off`
+        mode.switch(Mode::Fast);
+    }
+
+    #[idle]
+    fn idle() -> ! {
+        mode.switch(Mode::Slow);
+
+        loop {}
+    }
+
+    #[task(slots = [0..10_000], modes = [Fast, Slow], resources = [SHARED])]
+    fn a() {
+        resources.SHARED.claim(|shared| *shared += 1);
+    }
+
+    // overlaps `c` but they never run in the same mode
+    #[task(priority = 2, slots = [100_000..200_000, 500_000..600_000], modes = [Fast])]
+    fn b() {
+        if scheduled == rtfm::Instant::artificial(0) {
+            mode.switch(Mode::Off);
+        }
+    }
+
+    #[task(priority = 2, slots = [150_000..250_000], modes = [Slow], resources = [SHARED])]
+    fn c() {
+        *resources.SHARED += 1;
+    }
+
+    #[interrupt(priority = 3)]
+    fn UART0() {
+        mode.switch(Mode::Fast);
+    }
+
+    extern "C" {
+        fn UART1();
+        fn SSI0();
+    }
+};
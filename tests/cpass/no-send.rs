@@ -19,7 +19,7 @@ unsafe impl Sync for NotSend {}
 
 #[app(device = lm3s6965)]
 const APP: () = {
-    #[init]
+    #[init(spawn = [foo])]
     fn init() {}
 
     #[task(spawn = [bar])]
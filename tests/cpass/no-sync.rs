@@ -20,7 +20,7 @@ unsafe impl Send for NotSync {}
 const APP: () = {
     static X: NotSync = NotSync { _0: PhantomData };
 
-    #[init]
+    #[init(spawn = [foo, bar])]
     fn init() {}
 
     #[task(resources = [X])]
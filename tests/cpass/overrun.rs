@@ -0,0 +1,57 @@
+//! Check code generation of execution budgets
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, frame = 1_000_000, modes = [Normal, Degraded])]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+
+    #[init(schedule = [a])]
+    fn init() {
+        let _ = schedule.a(start + 1_000.cycles());
+    }
+
+    #[task(wcet = 10_000, resources = [SHARED], modes = [Normal], slots = [0..100_000])]
+    fn periodic() {
+        resources.SHARED.claim(|shared| *shared += 1);
+    }
+
+    #[task(priority = 2, wcet = 5_000, resources = [SHARED], spawn = [b])]
+    fn a() {
+        *resources.SHARED += 1;
+
+        let _ = spawn.b(0);
+    }
+
+    #[task(capacity = 2)]
+    fn b(_x: u32) {}
+
+    #[interrupt(priority = 3, wcet = 1_000)]
+    fn UART0() {}
+
+    #[exception(wcet = 1_000)]
+    fn SVCall() {}
+
+    #[overrun]
+    fn overrun() {
+        static mut COUNT: u32 = 0;
+
+        *COUNT += 1;
+
+        match task {
+            Task::periodic => mode.switch(Mode::Degraded),
+            Task::a | Task::SVCall | Task::UART0 => {}
+        }
+    }
+
+    extern "C" {
+        fn UART1();
+        fn SSI0();
+    }
+};
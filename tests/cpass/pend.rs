@@ -0,0 +1,54 @@
+//! Check code generation of `pend`
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use lm3s6965::Interrupt;
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(pend = [UART0, foo], spawn = [bar])]
+    fn init() {
+        pend.UART0();
+        pend.foo();
+
+        // the escape hatch is still available
+        unsafe { rtfm::pend(Interrupt::UART0) }
+    }
+
+    #[idle(pend = [foo])]
+    fn idle() -> ! {
+        let _: idle::Pend = pend;
+
+        loop {}
+    }
+
+    #[exception(pend = [UART0])]
+    fn SVCall() {
+        pend.UART0();
+    }
+
+    #[interrupt(pend = [foo])]
+    fn UART0() {
+        pend.foo();
+    }
+
+    #[interrupt(binds = UART1, pend = [UART0])]
+    fn foo() {
+        pend.UART0();
+    }
+
+    #[task(pend = [UART0, foo])]
+    fn bar() {
+        pend.UART0();
+        pend.foo();
+    }
+
+    extern "C" {
+        fn SSI0();
+    }
+};
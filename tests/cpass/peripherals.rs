@@ -0,0 +1,76 @@
+//! Check code generation of device peripherals used as resources
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+// `lm3s6965` has no peripherals; this wrapper adds a couple
+pub mod device {
+    use core::marker::PhantomData;
+
+    pub use lm3s6965::{interrupt, Interrupt, NVIC_PRIO_BITS};
+
+    #[allow(non_snake_case)]
+    pub struct Peripherals {
+        pub GPIOA: GPIOA,
+        pub UART0: UART0,
+    }
+
+    impl Peripherals {
+        pub unsafe fn steal() -> Self {
+            Peripherals {
+                GPIOA: GPIOA {
+                    _marker: PhantomData,
+                },
+                UART0: UART0 {
+                    _marker: PhantomData,
+                },
+            }
+        }
+    }
+
+    pub struct GPIOA {
+        _marker: PhantomData<*const ()>,
+    }
+
+    unsafe impl Send for GPIOA {}
+
+    pub struct UART0 {
+        _marker: PhantomData<*const ()>,
+    }
+
+    unsafe impl Send for UART0 {}
+}
+
+#[app(device = device)]
+const APP: () = {
+    #[init(resources = [device::UART0], spawn = [foo])]
+    fn init() {
+        let _: &mut device::UART0 = resources.UART0;
+    }
+
+    #[idle(resources = [device::GPIOA])]
+    fn idle() -> ! {
+        resources.GPIOA.claim(|_: &mut device::GPIOA| {});
+
+        loop {}
+    }
+
+    #[interrupt(resources = [UART0])]
+    fn UART1() {
+        let _: &mut device::UART0 = resources.UART0;
+    }
+
+    #[task(priority = 2, resources = [GPIOA])]
+    fn foo() {
+        let _: &mut device::GPIOA = resources.GPIOA;
+    }
+
+    extern "C" {
+        fn SSI0();
+    }
+};
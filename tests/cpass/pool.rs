@@ -0,0 +1,48 @@
+//! Check code generation of memory pools
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::{app, pool::Box};
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[pool]
+    static A: [u8; 16] = [0; 16];
+
+    #[pool(capacity = 8)]
+    static B: u32 = 0;
+
+    static mut KEEP: Option<Box<B>> = None;
+
+    #[init(spawn = [foo])]
+    fn init() {
+        let _: Option<Box<A>> = A::alloc();
+
+        spawn.foo(A::alloc().unwrap(), B::alloc().unwrap()).ok();
+    }
+
+    #[idle(resources = [KEEP])]
+    fn idle() -> ! {
+        if let Some(b) = B::alloc() {
+            resources.KEEP.claim(|keep| *keep = Some(b));
+        }
+
+        loop {}
+    }
+
+    #[task(priority = 2, resources = [KEEP])]
+    fn foo(a: Box<A>, b: Box<B>) {
+        let _: &[u8; 16] = &a;
+        let _: u32 = *b;
+
+        *resources.KEEP = Some(b);
+    }
+
+    extern "C" {
+        fn UART0();
+    }
+};
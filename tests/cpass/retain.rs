@@ -0,0 +1,36 @@
+//! Retained resources
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    // only used by `init`
+    #[retain]
+    static mut BOOTS: u32 = 0;
+
+    // shared between tasks that run at different priorities
+    #[retain]
+    static mut LOG: [u8; 4] = [0; 4];
+
+    #[init(resources = [BOOTS, LOG])]
+    fn init() {
+        *resources.BOOTS += 1;
+        resources.LOG[0] = 0;
+    }
+
+    #[interrupt(resources = [LOG])]
+    fn UART0() {
+        resources.LOG.claim(|log| log[1] += 1);
+    }
+
+    #[interrupt(priority = 2, resources = [LOG])]
+    fn UART1() {
+        resources.LOG[2] += 1;
+    }
+};
@@ -0,0 +1,50 @@
+//! Check that `spawn` enforces the minimum inter-arrival time from every context
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::{app, Arrivals, Duration, Instant, ReleaseError};
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[init(spawn = [foo, bar])]
+    fn init() {
+        if let Err(e) = spawn.foo(0) {
+            let _: u32 = e.into_inner();
+        }
+        let _: Result<(), ReleaseError<()>> = spawn.bar();
+    }
+
+    #[idle(spawn = [foo])]
+    fn idle() -> ! {
+        let _: Result<(), ReleaseError<u32>> = spawn.foo(1);
+
+        let foo: &Arrivals = arrivals::foo();
+        let _: Option<Instant> = foo.last();
+        let _: Duration = foo.min_inter_arrival();
+        let _: u32 = foo.violations();
+
+        loop {}
+    }
+
+    #[interrupt(spawn = [foo])]
+    fn UART0() {
+        let _: Result<(), ReleaseError<u32>> = spawn.foo(2);
+    }
+
+    #[task(capacity = 2, min_inter_arrival = 1_000)]
+    fn foo(_x: u32) {}
+
+    #[task(priority = 2, min_inter_arrival = 2_147_483_647, spawn = [bar])]
+    fn bar() {
+        let _: Result<(), ReleaseError<()>> = spawn.bar();
+    }
+
+    extern "C" {
+        fn UART1();
+        fn SSI0();
+    }
+};
@@ -0,0 +1,55 @@
+//! Check that `post_init` gets the same context as `idle` and that `pre_init` is accepted
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+    static mut OWNED: u32 = 0;
+
+    #[allow(unsafe_code)]
+    #[pre_init]
+    unsafe fn pre_init() {}
+
+    #[init]
+    fn init() {}
+
+    #[post_init(resources = [OWNED, SHARED], spawn = [foo], pend = [UART1])]
+    fn post_init() {
+        static mut X: u32 = 0;
+
+        let _: &'static mut u32 = X;
+        let _: &mut u32 = resources.OWNED;
+        resources.SHARED.claim(|shared| *shared += 1);
+
+        spawn.foo().unwrap();
+        pend.UART1();
+    }
+
+    #[idle(resources = [OWNED])]
+    fn idle() -> ! {
+        *resources.OWNED += 1;
+
+        loop {}
+    }
+
+    #[interrupt(resources = [SHARED])]
+    fn UART1() {
+        *resources.SHARED += 1;
+    }
+
+    #[task]
+    fn foo() {}
+
+    extern "C" {
+        fn UART0();
+    }
+};
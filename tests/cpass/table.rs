@@ -0,0 +1,46 @@
+//! Check code generation of the time-triggered schedule table
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965, frame = 1_000_000)]
+const APP: () = {
+    static mut SHARED: u32 = 0;
+
+    #[init(schedule = [log])]
+    fn init() {
+        let _ = schedule.log(start + 100.cycles(), 0);
+    }
+
+    #[task(slots = [0..10_000, 500_000..510_000], resources = [SHARED], spawn = [log])]
+    fn a() {
+        resources.SHARED.claim(|shared| *shared += 1);
+
+        let _ = spawn.log(1);
+    }
+
+    #[task(priority = 2, slots = [10_000..20_000], resources = [SHARED])]
+    fn b() {
+        resources.SHARED.claim(|shared| *shared += 1);
+    }
+
+    #[task(capacity = 2)]
+    fn log(_x: u32) {}
+
+    #[interrupt(priority = 3, resources = [SHARED])]
+    fn UART0() {
+        *resources.SHARED += 1;
+
+        let _: u32 = slots::a().misses() + slots::b().overruns();
+    }
+
+    extern "C" {
+        fn UART1();
+        fn SSI0();
+    }
+};
@@ -0,0 +1,73 @@
+//! Check code generation of topics published from all kind of contexts
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[derive(Clone)]
+pub struct Reading {
+    _raw: [u8; 4],
+}
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[topic]
+    type Sensor = Reading;
+
+    // single subscriber; the message doesn't need to be `Clone`
+    #[topic]
+    type Tick = u32;
+
+    // no subscribers; publishing is a no-op
+    #[allow(unused)]
+    #[topic]
+    type Debug = ();
+
+    #[init(publish = [Sensor, Tick])]
+    fn init() {
+        let _ = publish.sensor(Reading { _raw: [0; 4] });
+        let _ = publish.tick(0);
+    }
+
+    #[idle(publish = [Tick, Debug])]
+    fn idle() -> ! {
+        loop {
+            if let Err(e) = publish.tick(1) {
+                for _ in e.overflowed() {}
+            }
+
+            let _ = publish.debug(());
+        }
+    }
+
+    #[exception(publish = [Sensor])]
+    fn SVCall() {
+        let _ = publish.sensor(Reading { _raw: [1; 4] });
+    }
+
+    #[interrupt(publish = [Sensor])]
+    fn UART0() {
+        let _ = publish.sensor(Reading { _raw: [2; 4] });
+    }
+
+    #[task(priority = 2, subscribe = [Sensor])]
+    fn a(_reading: Reading) {}
+
+    #[task(subscribe = [Sensor], publish = [Sensor])]
+    fn b(reading: Sensor) {
+        let _ = publish.sensor(reading);
+    }
+
+    #[task(priority = 3, capacity = 4, subscribe = [Tick])]
+    fn c(_x: u32) {}
+
+    extern "C" {
+        fn UART1();
+        fn I2C0();
+        fn SSI0();
+    }
+};
@@ -0,0 +1,29 @@
+//! Check that the unused items annotated with `#[allow(dead_code)]` / `#[allow(unused)]` don't
+//! generate warnings
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate lm3s6965;
+extern crate panic_halt;
+extern crate rtfm;
+
+use rtfm::app;
+
+#[app(device = lm3s6965)]
+const APP: () = {
+    #[allow(dead_code)]
+    static mut X: u32 = 0;
+
+    #[init]
+    fn init() {}
+
+    #[allow(unused)]
+    #[task]
+    fn foo() {}
+
+    extern "C" {
+        #[allow(dead_code)]
+        fn UART0();
+    }
+};